@@ -1,7 +1,13 @@
 
 
+use std::path::Path;
+
 use async_trait::async_trait;
 use agent_schema::Message;
+use agent_provider::LLMBase;
+
+/// Directory the actions write the project to until `Action::set_workspace` is called.
+pub const DEFAULT_WORKSPACE: &str = "workshop";
 
 #[async_trait]
 pub trait Action: Send + Sync {
@@ -10,6 +16,10 @@ pub trait Action: Send + Sync {
     fn name(&self) -> &str;
     fn set_prefix(&mut self, prefix: &str, profile: &str);
     fn get_prefix(&self) -> &str;
+    /// Replace the model used by the action, e.g. with per-role settings. No-op for actions without an LLM.
+    fn set_llm(&mut self, _llm: Box<dyn LLMBase>) {}
+    /// Write the files of the action under `workspace`. No-op for actions writing no files.
+    fn set_workspace(&mut self, _workspace: &Path) {}
     async fn aask(&self, prompt: &str) -> String;
     /// 这里接收的是 所有信息，但是不是所有行为都会用到
     /// 有些只需要一条，所以使用条件有限制
@@ -1,12 +1,13 @@
 // use std::env;
 use std::io::Write;
+use std::path::PathBuf;
 use std::{collections::HashMap, sync::{Mutex, Arc}};
 use async_trait::async_trait;
 use tracing::{debug, info};
 
 use agent_schema::Message;
 use agent_prompts::PromptTemplate;
-use crate::action_base::Action;
+use crate::action_base::{Action, DEFAULT_WORKSPACE};
 use agent_macro::ActionMacro;
 use agent_utils::CodeParser;
 
@@ -52,6 +53,7 @@ pub struct SearchArXiv {
     context: String,
     prefix: String,
     profile: String,
+    workspace: PathBuf,
 }
 impl SearchArXiv {
     pub fn new(name: &str, context: &str, prefix:&str, profile: &str, _llm: Arc<Mutex<dyn LLMBase>>) -> Self {
@@ -61,7 +63,8 @@ impl SearchArXiv {
             name: name.into(),
             context: context.into(),
             prefix: prefix.into(),
-            profile: profile.into()
+            profile: profile.into(),
+            workspace: PathBuf::from(DEFAULT_WORKSPACE),
         }
     }
     ///  Use the Arxiv tool.
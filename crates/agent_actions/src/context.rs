@@ -0,0 +1,111 @@
+use std::sync::OnceLock;
+
+use agent_schema::Message;
+use agent_utils::{generate_skeleton, skeleton_to_markdown, ClassDiagram, CodeParser, SkeletonLanguage};
+use tracing::warn;
+
+/// Language of the skeleton and of the code written from it, set by `set_skeleton_language`.
+static SKELETON_LANGUAGE: OnceLock<SkeletonLanguage> = OnceLock::new();
+
+/// Generate the skeleton and write the code in `language`, to be called at startup with the
+/// configured language before any action runs. Python when never called.
+pub fn set_skeleton_language(language: SkeletonLanguage) {
+    if SKELETON_LANGUAGE.set(language).is_err() {
+        warn!("the skeleton language was already set, ignoring {:?}", language);
+    }
+}
+
+pub(crate) fn skeleton_language() -> SkeletonLanguage {
+    SKELETON_LANGUAGE.get().copied().unwrap_or_default()
+}
+
+/// Render every message the role acts on, one `[cause_by]: content` entry each: the
+/// requirement, the summary of the older messages and the recent ones, as fitted by the
+/// role. The messages whose `cause_by` is in `rendered` have a prompt section of their own.
+pub(crate) fn messages_context(msgs: &[&Message], rendered: &[&str]) -> String {
+    msgs.iter()
+        .filter(|msg| !rendered.contains(&msg.cause_by.as_str()))
+        .map(|msg| msg.to_string())
+        .collect::<Vec<String>>()
+        .join("\n\n")
+}
+
+/// Render the `RepositorySummary` message, if any, as a prompt section so the
+/// action designs and writes changes relative to the existing code instead of a fresh project.
+pub(crate) fn repository_context(msgs: &[&Message]) -> String {
+    match msgs.iter().find(|msg| msg.cause_by == "RepositorySummary") {
+        Some(msg) => format!(
+            "## Existing Repository\nThis is NOT a new project. The requirement must be implemented as changes to the existing repository below: reuse its structure, naming and public APIs, and only list files that need to be created or modified.\n{}\n",
+            msg.content
+        ),
+        None => String::new(),
+    }
+}
+
+/// Render the `WriteApiSpec` message, if any, so the code and tests follow the exact
+/// endpoints and schemas of the API contract.
+pub(crate) fn api_spec_context(msgs: &[&Message]) -> String {
+    match msgs.iter().rev().find(|msg| msg.cause_by == "WriteApiSpec") {
+        Some(msg) => format!(
+            "## API Contract\nThe OpenAPI document below is the contract: use exactly these endpoints, parameters and schemas.\n{}\n",
+            msg.content
+        ),
+        None => String::new(),
+    }
+}
+
+/// Parse the class diagram of the latest `WriteDesign` message into a typed model.
+pub(crate) fn design_class_diagram(design: &str) -> Option<ClassDiagram> {
+    let mermaid = CodeParser::new()
+        .parse_code("Data structures and interface definitions", design, "mermaid")
+        .ok()?;
+    ClassDiagram::parse(&mermaid).ok()
+}
+
+fn latest_class_diagram(msgs: &[&Message]) -> Option<ClassDiagram> {
+    msgs.iter()
+        .rev()
+        .find(|msg| msg.cause_by == "WriteDesign")
+        .and_then(|msg| design_class_diagram(&msg.content))
+}
+
+/// The files the engineer writes: one per skeleton file of the design, the module file
+/// aside, and the `main` entry point.
+pub(crate) fn code_files(msgs: &[&Message]) -> Vec<String> {
+    let language = skeleton_language();
+    let mut files: Vec<String> = latest_class_diagram(msgs)
+        .map(|diagram| generate_skeleton(&diagram, language))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|file| file.path)
+        .filter(|path| path != "mod.rs")
+        .collect();
+    files.push(format!("main.{}", language.extension()));
+    files
+}
+
+/// Render the skeleton generated from the design so the engineer fills in the stubs
+/// instead of redefining the classes.
+pub(crate) fn skeleton_context(msgs: &[&Message]) -> String {
+    let Some(diagram) = latest_class_diagram(msgs) else {
+        return String::new();
+    };
+    let language = skeleton_language();
+    format!(
+        "## Code Skeleton\nThese files were generated from \"Data structures and interface definitions\". Keep every class, field and method signature unchanged and only implement the bodies.\n{}\n",
+        skeleton_to_markdown(&generate_skeleton(&diagram, language), language)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_files() {
+        let design = "## Data structures and interface definitions\n```mermaid\nclassDiagram\n    class HTTPServer{\n        +start() void\n    }\n    class Game{\n        +int score\n    }\n```\n";
+        let design = Message::new(design, "Architect", "WriteDesign", "");
+        assert_eq!(code_files(&[&design]), vec!["http_server.py", "game.py", "main.py"]);
+        assert_eq!(code_files(&[]), vec!["main.py"]);
+    }
+}
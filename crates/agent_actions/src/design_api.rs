@@ -1,83 +1,21 @@
+use std::path::PathBuf;
 use std::io::Write;
 use std::{env, fs};
 use std::{collections::HashMap, sync::{Mutex, Arc}};
-use agent_utils::{CodeParser, async_save_diagram};
+use agent_utils::{CodeParser, async_save_diagram, generate_skeleton};
 use async_trait::async_trait;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use agent_schema::Message;
-use agent_prompts::PromptTemplate;
-use crate::action_base::Action;
+use crate::action_base::{Action, DEFAULT_WORKSPACE};
+use crate::prompts::{prompt_template, prompt_text};
+use crate::context::{design_class_diagram, messages_context, repository_context, skeleton_language};
 use agent_macro::ActionMacro;
 
 pub use agent_provider::{LLM, LLMBase};
 
+const SKELETON_DIR: &str = "skeleton";
 
-const PROMPT_TEMPLATE: &str = r#"
-# Context
-{{context}}
-
-## Format example
-{{format_example}}
------
-Role: You are an architect; the goal is to design a SOTA PEP8-compliant python system; make the best use of good open source tools
-Requirement: Fill in the following missing information based on the context, note that all sections are response with code form seperatedly
-Max Output: 8192 chars or 2048 tokens. Try to use them up.
-Attention: Use '##' to split sections, not '#', and '## <SECTION_NAME>' SHOULD WRITE BEFORE the code and triple quote.
-
-## Implementation approach: Provide as Plain text. Analyze the difficult points of the requirements, select the appropriate open-source framework.
-
-## Python package name: Provide as Python str with python triple quoto, concise and clear, characters only use a combination of all lowercase and underscores
-
-## File list: Provided as Python list[str], the list of ONLY REQUIRED files needed to write the program(LESS IS MORE!). Only need relative paths, comply with PEP8 standards. ALWAYS write a main.rs or app.rs here
-
-## Data structures and interface definitions: Use mermaid classDiagram code syntax, including classes (INCLUDING __init__ method) and functions (with type annotations), CLEARLY MARK the RELATIONSHIPS between classes, and comply with PEP8 standards. The data structures SHOULD BE VERY DETAILED and the API should be comprehensive with a complete design. 
-
-## Program call flow: Use sequenceDiagram code syntax, COMPLETE and VERY DETAILED, using CLASSES AND API DEFINED ABOVE accurately, covering the CRUD AND INIT of each object, SYNTAX MUST BE CORRECT.
-
-## Anything UNCLEAR: Provide as Plain text. Make clear here.
-
-"#;
-
-const FORMAT_EXAMPLE: &str = r#"
----
-## Implementation approach
-We will ...
-
-## Python package name
-```python
-"snake_game"
-```
-
-## File list
-```python
-[
-    "main.py",
-]
-```
-
-## Data structures and interface definitions
-```mermaid
-classDiagram
-    class Game{
-        +int score
-    }
-    ...
-    Game "1" -- "1" Food: has
-```
-
-## Program call flow
-```mermaid
-sequenceDiagram
-    participant M as Main
-    ...
-    G->>M: end game
-```
-
-## Anything UNCLEAR
-The requirement is clear to me.
----
-"#;
 
 #[derive(Debug, ActionMacro)]
 pub struct WriteDesign {
@@ -86,6 +24,7 @@ pub struct WriteDesign {
     context: String,
     prefix: String,
     profile: String,
+    workspace: PathBuf,
 }
 impl WriteDesign {
     pub fn new(name: &str, context: &str, prefix:&str, profile: &str, _llm: Arc<Mutex<dyn LLMBase>>) -> Self {
@@ -95,31 +34,54 @@ impl WriteDesign {
             name: name.into(),
             context: context.into(),
             prefix: prefix.into(),
-            profile: profile.into()
+            profile: profile.into(),
+            workspace: PathBuf::from(DEFAULT_WORKSPACE),
         }
     }
 
     async fn _build_prompt(&self, msgs: Vec<&Message>) -> String {
-        let template = PromptTemplate::new(PROMPT_TEMPLATE);
+        let template = prompt_template("write_design");
+        let repository = repository_context(&msgs);
+        let context = messages_context(&msgs, &["RepositorySummary"]);
         let mut args = HashMap::new();
-        // TODO 待优化
-        args.insert("context", msgs[0].content.as_str());
-        args.insert("format_example", FORMAT_EXAMPLE);
+        args.insert("repository", repository.as_str());
+        args.insert("context", context.as_str());
+        args.insert("format_example", prompt_text("write_design_format_example"));
         template.render(&args)
     }
+    /// Generate stub files for every class of the design into `skeleton` in the workspace.
+    fn _save_skeleton(&self, llm_response: &str) {
+        let Some(diagram) = design_class_diagram(llm_response) else {
+            warn!("【WriteDesign】unable to parse the class diagram, no skeleton generated");
+            return;
+        };
+        let language = skeleton_language();
+        let dir = self.workspace.join(SKELETON_DIR);
+        for file in generate_skeleton(&diagram, language) {
+            let path = dir.join(&file.path);
+            let saved = fs::create_dir_all(&dir).and_then(|_| fs::write(&path, file.content.as_bytes()));
+            match saved {
+                Ok(_) => debug!("saved {}", path.display()),
+                Err(e) => warn!("failed to save {}: {}", path.display(), e),
+            }
+        }
+    }
+
     ///save prd.md and competitive_quadrant_chart.png
     async fn _post_processing(&self, _msgs: Vec<&Message>, llm_response: String) -> String {
         info!("【WriteDesign】 llm_response: {}", llm_response);
         // save the prd.md and competitive_quadrant_chart.png
+        fs::create_dir_all(&self.workspace).unwrap();
         {
             let mermaid = CodeParser::new().parse_code("Data structures and interface definitions", &llm_response, "mermaid")
                 .expect("unable to parse mermaid code for Data structures and interface definitions");
 
-            let res = async_save_diagram(&mermaid, "workshop/Data_structures_and_interface_definitions.png").await;
+            let diagram = self.workspace.join("Data_structures_and_interface_definitions.png").to_string_lossy().to_string();
+            let res = async_save_diagram(&mermaid, &diagram).await;
             if let Ok(_res) = res {
                 debug!("save mermaid:\n {}", mermaid);
             } else {
-                info!("failed to save workshop/competitive_quadrant_chart.png :\n {}", mermaid);
+                info!("failed to save {} :\n {}", diagram, mermaid);
             }
         }
 
@@ -127,14 +89,16 @@ impl WriteDesign {
             let mermaid = CodeParser::new().parse_code("Program call flow", &llm_response, "mermaid")
             .expect("unable to parse mermaid code for Program call flow");
 
-            let res = async_save_diagram(&mermaid, "workshop/Program_call_flow.png").await;
+            let diagram = self.workspace.join("Program_call_flow.png").to_string_lossy().to_string();
+            let res = async_save_diagram(&mermaid, &diagram).await;
             if let Ok(_res) = res {
                 debug!("save mermaid:\n {}", mermaid);
             } else {
-                info!("failed to save workshop/competitive_quadrant_chart.png :\n {}", mermaid);
+                info!("failed to save {} :\n {}", diagram, mermaid);
             }
         }
-        let mut file = fs::File::create("workshop/ArchitectDesign.md").unwrap();
+        self._save_skeleton(&llm_response);
+        let mut file = fs::File::create(self.workspace.join("ArchitectDesign.md")).unwrap();
         file.write_all(llm_response.as_bytes()).expect("failed to write prd.md");
         llm_response
     }
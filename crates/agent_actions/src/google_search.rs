@@ -5,7 +5,7 @@ use async_trait::async_trait;
 
 use agent_provider::{LLMBase, LLM};
 use agent_schema::Message;
-use agent_tools::{types::SearchEngine, GoogleSearchClient};
+use agent_tools::{search_engine, types::SearchEngine};
 // use agent_macro::ActionMacro;
 
 use crate::action_base::Action;
@@ -28,7 +28,7 @@ impl GoogleSearch {
     ) -> Self {
         Self {
             _llm: Box::new(LLM::new()),
-            google_search: Box::new(GoogleSearchClient),
+            google_search: search_engine(),
             name: name.into(),
             context: context.into(),
             prefix: prefix.into(),
@@ -3,20 +3,30 @@ mod action_base;
 mod add_requirement;
 mod write_prd;
 mod design_api;
+mod write_api_spec;
 mod project_management;
 mod write_code;
+mod write_tests;
 mod search_and_summarize;
 mod google_search;
+mod prompts;
+mod context;
+mod registry;
 // mod arxiv_search;
 
 
 
-pub use action_base::Action;
+pub use action_base::{Action, DEFAULT_WORKSPACE};
+pub use context::set_skeleton_language;
+pub use prompts::{load_prompts, validate_prompts};
+pub use registry::{create_action, ACTION_NAMES};
 pub use write_prd::WritePRD;
 pub use add_requirement::BossRequirement;
 pub use design_api::WriteDesign;
+pub use write_api_spec::WriteApiSpec;
 pub use project_management::WriteTasks;
 pub use write_code::WriteCode;
+pub use write_tests::WriteTests;
 pub use search_and_summarize::SearchAndSummarize;
 pub use google_search::GoogleSearch;
 // pub use arxiv_search::SearchArXiv;
@@ -1,40 +1,16 @@
-use std::{collections::HashMap, sync::{Mutex, Arc}, fs, io::Write};
+use std::{collections::HashMap, path::PathBuf, sync::{Mutex, Arc}, fs, io::Write};
 use agent_utils::CodeParser;
 use async_trait::async_trait;
 use tracing::{debug, info};
 
 use agent_schema::Message;
-use agent_prompts::PromptTemplate;
-use crate::action_base::Action;
+use crate::action_base::{Action, DEFAULT_WORKSPACE};
+use crate::prompts::prompt_template;
+use crate::context::{messages_context, repository_context};
 use agent_macro::ActionMacro;
 pub use agent_provider::{LLM, LLMBase};
 
 
-const PROMPT_TEMPLATE: &str = r#"
-# Context
-{{context}}
------
-Role: You are a project manager; the goal is to break down tasks according to PRD/technical design, give a task list, and analyze task dependencies to start with the prerequisite modules
-Requirements: Based on the context, fill in the following missing information, note that all sections are returned in Python code triple quote form seperatedly. Here the granularity of the task is a file, if there are any missing files, you can supplement them
-Attention: Use '##' to split sections, not '#', and '## <SECTION_NAME>' SHOULD WRITE BEFORE the code and triple quote.
-
-## Required Python third-party packages: Provided in requirements.txt format
-
-## Required Other language third-party packages: Provided in requirements.txt format
-
-## Full API spec: Use OpenAPI 3.0. Describe all APIs that may be used by both frontend and backend.
-
-## Logic Analysis: Provided as a Python list[str, str]. the first is filename, the second is class/method/function should be implemented in this file. Analyze the dependencies between the files, which work should be done first
-
-## Task list: Provided as Python list[str]. Each str is a filename, the more at the beginning, the more it is a prerequisite dependency, should be done first
-
-## Shared Knowledge: Anything that should be public like utils' functions, config's variables details that should make clear first. 
-
-## Anything UNCLEAR: Provide as Plain text. Make clear here. For example, don't forget a main entry. don't forget to init 3rd party libs.
-
-"#;
-
-
 #[derive(Debug, ActionMacro)]
 pub struct WriteTasks {
     _llm: Box<dyn LLMBase>,
@@ -42,6 +18,7 @@ pub struct WriteTasks {
     context: String,
     prefix: String,
     profile: String,
+    workspace: PathBuf,
 }
 impl WriteTasks {
     pub fn new(name: &str, context: &str, prefix:&str, profile: &str, _llm: Arc<Mutex<dyn LLMBase>>) -> Self {
@@ -51,15 +28,18 @@ impl WriteTasks {
             name: name.into(),
             context: context.into(),
             prefix: prefix.into(),
-            profile: profile.into()
+            profile: profile.into(),
+            workspace: PathBuf::from(DEFAULT_WORKSPACE),
         }
     }
 
     async fn _build_prompt(&self, msgs: Vec<&Message>) -> String {
-        let template = PromptTemplate::new(PROMPT_TEMPLATE);
+        let template = prompt_template("write_tasks");
+        let repository = repository_context(&msgs);
+        let context = messages_context(&msgs, &["RepositorySummary"]);
         let mut args = HashMap::new();
-        // TODO 待优化
-        args.insert("context", msgs[0].content.as_str());
+        args.insert("repository", repository.as_str());
+        args.insert("context", context.as_str());
         // args.insert("search_information", "");
         let prompt = template.render(&args); 
         // debug!("{:?}", self);
@@ -70,7 +50,8 @@ impl WriteTasks {
         {
             let requirements = CodeParser::new().parse_code("Required Python third-party packages", &llm_response, "plaintext")
                 .expect("unable to parse mermaid code for Competitive Quadrant Chart");
-            let mut file = fs::File::create("workshop/requirements.txt").unwrap();
+            fs::create_dir_all(&self.workspace).unwrap();
+            let mut file = fs::File::create(self.workspace.join("requirements.txt")).unwrap();
             file.write_all(requirements.as_bytes()).expect("failed to write prd.md");
         }
         llm_response
@@ -0,0 +1,76 @@
+use std::path::Path;
+use std::sync::OnceLock;
+
+use tracing::{info, warn};
+
+use agent_prompts::{PromptRegistry, PromptTemplate};
+
+/// Action prompts: the files in `agent_actions/prompts` are compiled in as defaults and can be
+/// overridden without recompiling, see `PromptRegistry`. Set by `load_prompts`, or with the
+/// env-specified overrides only when an action runs first.
+static PROMPTS: OnceLock<PromptRegistry> = OnceLock::new();
+
+fn builtin_prompts() -> PromptRegistry {
+    let mut registry = PromptRegistry::new();
+    registry.register("write_prd", include_str!("../prompts/write_prd.md"), &["repository", "requirements", "search_information"]);
+    registry.register("write_design", include_str!("../prompts/write_design.md"), &["repository", "context", "format_example"]);
+    registry.register("write_design_format_example", include_str!("../prompts/write_design_format_example.md"), &[]);
+    registry.register("write_api_spec", include_str!("../prompts/write_api_spec.md"), &["context", "validation_errors"]);
+    registry.register("write_tasks", include_str!("../prompts/write_tasks.md"), &["repository", "context"]);
+    registry.register("write_code", include_str!("../prompts/write_code.md"), &["repository", "api_spec", "skeleton", "context", "filenames"]);
+    registry.register("write_tests", include_str!("../prompts/write_tests.md"), &["repository", "api_spec", "context"]);
+    registry.register("search_and_summarize", include_str!("../prompts/search_and_summarize.md"), &["CONTEXT", "QUERY_HISTORY", "QUERY"]);
+    registry
+}
+
+fn prompts() -> &'static PromptRegistry {
+    PROMPTS.get_or_init(|| {
+        let mut registry = builtin_prompts();
+        for error in registry.load_overrides(None).1 {
+            warn!("failed to load prompt template overrides from {}", error);
+        }
+        registry
+    })
+}
+
+/// Load the action prompts with the overrides of the project in `project_root`, to be called at
+/// startup before any action runs. Every override directory is read, the failed ones are returned.
+pub fn load_prompts(project_root: &Path) -> Result<(), String> {
+    let mut registry = builtin_prompts();
+    let (loaded, errors) = registry.load_overrides(Some(project_root));
+    if loaded > 0 {
+        info!("loaded {} prompt template overrides", loaded);
+    }
+    if PROMPTS.set(registry).is_err() {
+        warn!("the action prompts were already loaded, ignoring the overrides of {}", project_root.display());
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+/// The template registered as `name`, panics on an unknown name since that is a programming error.
+pub(crate) fn prompt_template(name: &str) -> PromptTemplate {
+    prompts().template(name).unwrap_or_else(|| panic!("unknown prompt template {}", name))
+}
+
+pub(crate) fn prompt_text(name: &str) -> &'static str {
+    prompts().text(name).unwrap_or_else(|| panic!("unknown prompt template {}", name))
+}
+
+/// Validate all action prompts (including overrides), to be called at startup.
+pub fn validate_prompts() -> Result<(), String> {
+    prompts().validate().map_err(|errors| errors.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_prompts_are_valid() {
+        assert_eq!(validate_prompts(), Ok(()));
+    }
+}
@@ -0,0 +1,35 @@
+use std::sync::{Arc, Mutex};
+
+use agent_provider::LLMBase;
+
+use crate::action_base::Action;
+use crate::{GoogleSearch, SearchAndSummarize, WriteApiSpec, WriteCode, WriteDesign, WritePRD, WriteTasks, WriteTests};
+
+/// Names accepted by `create_action`, i.e. the `cause_by` of the messages the actions publish.
+pub const ACTION_NAMES: [&str; 8] = [
+    "WritePRD",
+    "WriteDesign",
+    "WriteApiSpec",
+    "WriteTasks",
+    "WriteCode",
+    "WriteTests",
+    "SearchAndSummarize",
+    "GoogleSearch",
+];
+
+/// Create a registered action by name with the `llm` of its role, the way the built-in roles
+/// construct theirs. Returns `None` for an unknown name.
+pub fn create_action(action: &str, name: &str, profile: &str, prefix: &str, llm: Arc<Mutex<dyn LLMBase>>) -> Option<Box<dyn Action>> {
+    let action: Box<dyn Action> = match action {
+        "WritePRD" => Box::new(WritePRD::new(name, profile, prefix, profile, llm)),
+        "WriteDesign" => Box::new(WriteDesign::new(name, profile, prefix, profile, llm)),
+        "WriteApiSpec" => Box::new(WriteApiSpec::new(name, profile, prefix, profile, llm)),
+        "WriteTasks" => Box::new(WriteTasks::new(name, profile, prefix, profile, llm)),
+        "WriteCode" => Box::new(WriteCode::new(name, profile, prefix, profile, llm)),
+        "WriteTests" => Box::new(WriteTests::new(name, profile, prefix, profile, llm)),
+        "SearchAndSummarize" => Box::new(SearchAndSummarize::new(name, profile, prefix, profile, llm)),
+        "GoogleSearch" => Box::new(GoogleSearch::new(name, profile, prefix, profile, llm)),
+        _ => return None,
+    };
+    Some(action)
+}
@@ -1,16 +1,16 @@
-
 // use std::env;
 use std::io::Write;
+use std::path::PathBuf;
 use std::{collections::HashMap, sync::{Mutex, Arc}};
 use async_trait::async_trait;
 use tracing::{debug, info};
 
 use agent_schema::Message;
-use agent_prompts::PromptTemplate;
-use crate::action_base::Action;
+use crate::action_base::{Action, DEFAULT_WORKSPACE};
+use crate::prompts::prompt_template;
 use agent_macro::ActionMacro;
 use agent_utils::CodeParser;
-use agent_tools::SerpAPIWrapper;
+use agent_tools::{search_engine, types::SearchEngine};
 
 pub use agent_provider::{LLM, LLMBase};
 
@@ -41,22 +41,6 @@ A: MLOps competitors
 
 // const SEARCH_AND_SUMMARIZE_SYSTEM_EN_US: &str = SEARCH_AND_SUMMARIZE_SYSTEM.(LANG="en-us")
 
-const SEARCH_AND_SUMMARIZE_PROMPT: &str = r#"
-### Reference Information
-{{CONTEXT}}
-
-### Dialogue History
-{{QUERY_HISTORY}}
-{{QUERY}}
-
-### Current Question
-{{QUERY}}
-
-### Current Reply: Based on the information, please write the reply to the Question
-
-
-"#;
-
 
 const SEARCH_AND_SUMMARIZE_SALES_SYSTEM: &str = r#"
 ## Requirements
@@ -104,8 +88,6 @@ You are a member of a professional butler team and will provide helpful suggesti
 "#;
 
 
-
-
 #[derive(Debug, ActionMacro)]
 pub struct SearchAndSummarize {
     _llm: Box<dyn LLMBase>,
@@ -113,7 +95,8 @@ pub struct SearchAndSummarize {
     context: String,
     prefix: String,
     profile: String,
-    search_engine: SerpAPIWrapper,
+    workspace: PathBuf,
+    search_engine: Box<dyn SearchEngine>,
 }
 impl SearchAndSummarize {
     pub fn new(name: &str, context: &str, prefix:&str, profile: &str, _llm: Arc<Mutex<dyn LLMBase>>) -> Self {
@@ -124,17 +107,18 @@ impl SearchAndSummarize {
             context: context.into(),
             prefix: prefix.into(),
             profile: profile.into(),
-            search_engine: SerpAPIWrapper::default(),
+            workspace: PathBuf::from(DEFAULT_WORKSPACE),
+            search_engine: search_engine(),
         }
     }
 
     async fn _build_prompt(&self, msgs: Vec<&Message>) -> String {
         let query = msgs[0].content.as_str();
-        let rsp = self.search_engine.run(query).await;
+        let rsp = self.search_engine.search(query, false).await.map(|results| serde_json::to_string(&results).unwrap_or_default());
 
         match rsp {
             Ok(rsp) => {
-                let template = PromptTemplate::new(SEARCH_AND_SUMMARIZE_PROMPT);
+                let template = prompt_template("search_and_summarize");
                 let mut args = HashMap::new();
                 // TODO 待优化
                 args.insert("ROLE", self.profile.as_str());
@@ -147,7 +131,6 @@ impl SearchAndSummarize {
         }
 
 
-
     }
 
     async fn _post_processing(&self, _msgs: Vec<&Message>, llm_response: String) -> String {
@@ -163,7 +146,6 @@ impl SearchAndSummarize {
 }
 
 
-
 const PROMPT_TEMPLATE_RESPONSE_SAMPLE_FULL: &str = "";
 
 // class SearchAndSummarize(Action):
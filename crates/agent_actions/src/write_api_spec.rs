@@ -0,0 +1,144 @@
+use std::{collections::HashMap, path::PathBuf, sync::{Mutex, Arc}, fs};
+use async_trait::async_trait;
+use tracing::{debug, info, warn};
+
+use agent_schema::Message;
+use crate::action_base::{Action, DEFAULT_WORKSPACE};
+use crate::prompts::prompt_template;
+use agent_macro::ActionMacro;
+use agent_utils::{CodeParser, validate_openapi, openapi_endpoints};
+
+pub use agent_provider::{LLM, LLMBase};
+
+const API_SPEC_FILE: &str = "openapi.yaml";
+
+/// Derive an OpenAPI 3 document from the architect's design, validate it and save it
+/// to `openapi.yaml` in the workspace, so the engineer and QA work against an exact contract.
+#[derive(Debug, ActionMacro)]
+pub struct WriteApiSpec {
+    _llm: Box<dyn LLMBase>,
+    prefix: String,
+    profile: String,
+    workspace: PathBuf,
+}
+impl WriteApiSpec {
+    pub fn new(_name: &str, _context: &str, prefix:&str, profile: &str, _llm: Arc<Mutex<dyn LLMBase>>) -> Self {
+
+        Self {
+            _llm: Box::new(LLM::new()),
+            prefix: prefix.into(),
+            profile: profile.into(),
+            workspace: PathBuf::from(DEFAULT_WORKSPACE),
+        }
+    }
+
+    fn _render(&self, context: &str, validation_errors: &str) -> String {
+        let template = prompt_template("write_api_spec");
+        let mut args = HashMap::new();
+        args.insert("context", context);
+        args.insert("validation_errors", validation_errors);
+        template.render(&args)
+    }
+
+    async fn _build_prompt(&self, msgs: Vec<&Message>) -> String {
+        let design = msgs.iter().rev().find(|msg| msg.cause_by == "WriteDesign").unwrap_or(&msgs[0]);
+        self._render(&design.content, "")
+    }
+
+    fn _parse_spec(llm_response: &str) -> Result<String, Vec<String>> {
+        let spec = CodeParser::new()
+            .parse_code("OpenAPI Spec", llm_response, "yaml")
+            .map_err(|_| vec!["no ```yaml block found in the `## OpenAPI Spec` section".to_string()])?;
+        validate_openapi(&spec)?;
+        Ok(spec)
+    }
+
+    /// Validate the document, ask the LLM once to fix it if needed, and save it.
+    async fn _post_processing(&self, msgs: Vec<&Message>, llm_response: String) -> String {
+        let mut llm_response = llm_response;
+        let mut parsed = Self::_parse_spec(&llm_response);
+        if let Err(errors) = &parsed {
+            warn!("【WriteApiSpec】invalid OpenAPI document, asking for a fix: {:?}", errors);
+            let design = msgs.iter().rev().find(|msg| msg.cause_by == "WriteDesign").unwrap_or(&msgs[0]);
+            let feedback = format!(
+                "\n## Validation errors of your previous answer, fix all of them\n{}\n",
+                errors.iter().map(|e| format!("- {}", e)).collect::<Vec<String>>().join("\n")
+            );
+            llm_response = self.aask(&self._render(&design.content, &feedback)).await;
+            parsed = Self::_parse_spec(&llm_response);
+        }
+
+        match parsed {
+            Ok(spec) => {
+                info!("【WriteApiSpec】endpoints: {:?}", openapi_endpoints(&spec));
+                let path = self.workspace.join(API_SPEC_FILE);
+                let saved = fs::create_dir_all(&self.workspace).and_then(|_| fs::write(&path, spec.as_bytes()));
+                match saved {
+                    Ok(_) => debug!("saved {}", path.display()),
+                    Err(e) => warn!("failed to save {}: {}", path.display(), e),
+                }
+            }
+            Err(errors) => warn!("【WriteApiSpec】OpenAPI document is still invalid, not saved: {:?}", errors),
+        }
+        llm_response
+    }
+}
+
+/// The answer `run` (derived by `ActionMacro`) post-processes instead of asking the LLM when it is faked.
+const PROMPT_TEMPLATE_RESPONSE_SAMPLE_FULL: &str = r#"
+## OpenAPI Spec
+```yaml
+openapi: 3.0.0
+info:
+  title: Snake Game API
+  version: 1.0.0
+paths:
+  /games:
+    post:
+      summary: Start a new game
+      responses:
+        '201':
+          description: The created game
+          content:
+            application/json:
+              schema:
+                $ref: '#/components/schemas/Game'
+  /games/{id}/moves:
+    post:
+      summary: Move the snake in a direction
+      parameters:
+        - name: id
+          in: path
+          required: true
+          schema:
+            type: string
+      requestBody:
+        content:
+          application/json:
+            schema:
+              $ref: '#/components/schemas/Move'
+      responses:
+        '200':
+          description: The game after the move
+          content:
+            application/json:
+              schema:
+                $ref: '#/components/schemas/Game'
+components:
+  schemas:
+    Game:
+      type: object
+      properties:
+        score:
+          type: integer
+        game_over:
+          type: boolean
+    Move:
+      type: object
+      required: [direction]
+      properties:
+        direction:
+          type: string
+          enum: [up, down, left, right]
+```
+"#;
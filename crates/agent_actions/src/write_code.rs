@@ -1,37 +1,21 @@
 
 // use std::env;
 use std::io::Write;
+use std::path::PathBuf;
 use std::{collections::HashMap, sync::{Mutex, Arc}};
 use async_trait::async_trait;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use agent_schema::Message;
-use agent_prompts::PromptTemplate;
-use crate::action_base::Action;
+use crate::action_base::{Action, DEFAULT_WORKSPACE};
+use crate::prompts::prompt_template;
+use crate::context::{api_spec_context, code_files, messages_context, repository_context, skeleton_context, skeleton_language};
 use agent_macro::ActionMacro;
 use agent_utils::CodeParser;
 
 pub use agent_provider::{LLM, LLMBase};
 
 
-const PROMPT_TEMPLATE: &str = r#"
-# Context
-{{context}}
------
-NOTICE
-1. Role: You are an engineer; the main goal is to write PEP8 compliant, elegant, modular, easy to read and maintain Python 3.9 code (but you can also use other programming language)
-2. Requirement: Based on the context, implement one following code file, note to return only in code form, your code will be part of the entire project, so please implement complete, reliable, reusable code snippets
-3. Attention1: Use '##' to split sections, not '#', and '## <SECTION_NAME>' SHOULD WRITE BEFORE the code.
-4. Attention2: If there is any setting, ALWAYS SET A DEFAULT VALUE, ALWAYS USE STRONG TYPE AND EXPLICIT VARIABLE.
-5. Attention3: YOU MUST FOLLOW "Data structures and interface definitions". DONT CHANGE ANY DESIGN.
-6. Think before writing: What should be implemented and provided in this document?
-7. CAREFULLY CHECK THAT YOU DONT MISS ANY NECESSARY CLASS/FUNCTION IN THIS FILE.
-Attention: Use '##' to split sections, not '#', and '## <SECTION_NAME>' SHOULD WRITE BEFORE the code and triple quote.
-
-## {{filename}}: Write code with triple quoto. Do your best to implement THIS ONLY ONE FILE. ONLY USE EXISTING API. IF NO API, IMPLEMENT IT.
-
-"#;
-
 // ## {filename}: Please encapsulate your code within triple quotes. Focus your efforts on implementing ONLY WITHIN THIS FILE. Any class or function labeled as MISSING-DESIGN should be implemented IN THIS FILE ALONE. Do NOT make changes to any other files.
 
 const PROMPT_TEMPLATE_RESPONSE_SAMPLE1: &str = r#"
@@ -47,6 +31,7 @@ pub struct WriteCode {
     context: String,
     prefix: String,
     profile: String,
+    workspace: PathBuf,
 }
 impl WriteCode {
     pub fn new(name: &str, context: &str, prefix:&str, profile: &str, _llm: Arc<Mutex<dyn LLMBase>>) -> Self {
@@ -56,35 +41,47 @@ impl WriteCode {
             name: name.into(),
             context: context.into(),
             prefix: prefix.into(),
-            profile: profile.into()
+            profile: profile.into(),
+            workspace: PathBuf::from(DEFAULT_WORKSPACE),
         }
     }
 
     async fn _build_prompt(&self, msgs: Vec<&Message>) -> String {
-        let template = PromptTemplate::new(PROMPT_TEMPLATE);
+        let template = prompt_template("write_code");
+        let repository = repository_context(&msgs);
+        let api_spec = api_spec_context(&msgs);
+        let skeleton = skeleton_context(&msgs);
+        let context = messages_context(&msgs, &["RepositorySummary", "WriteApiSpec"]);
+        let filenames = code_files(&msgs).join(", ");
         let mut args = HashMap::new();
-        // TODO 待优化
-        args.insert("context", msgs[0].content.as_str());
-        args.insert("filename", "main.py");
+        args.insert("repository", repository.as_str());
+        args.insert("api_spec", api_spec.as_str());
+        args.insert("skeleton", skeleton.as_str());
+        args.insert("context", context.as_str());
+        args.insert("filenames", filenames.as_str());
         template.render(&args)
     }
 
-    async fn _post_processing(&self, _msgs: Vec<&Message>, llm_response: String) -> String {
-        // info!("{}", _msgs[0].content.as_str());
-        // design = [i for i in context if i.cause_by == WriteDesign][0]
-        // save_code
-        let code = CodeParser::new().parse_code("main.py", &llm_response, "python").expect("code parsing error");
-        info!("_post_processing \n {}", code);
-        let mut file = std::fs::File::create("data.py").expect("create failed");
-        file.write_all(code.as_bytes()).expect("write failed");
-        info!("code written to file" );
+    /// Save every file of `code_files` the answer has a section for.
+    async fn _post_processing(&self, msgs: Vec<&Message>, llm_response: String) -> String {
+        let fence = skeleton_language().fence();
+        std::fs::create_dir_all(&self.workspace).expect("create failed");
+        for filename in code_files(&msgs) {
+            let Ok(code) = CodeParser::new().parse_code(&filename, &llm_response, fence) else {
+                warn!("【WriteCode】no code for {} in the answer", filename);
+                continue;
+            };
+            info!("_post_processing {}\n {}", filename, code);
+            let path = self.workspace.join(&filename);
+            let mut file = std::fs::File::create(&path).expect("create failed");
+            file.write_all(code.as_bytes()).expect("write failed");
+            debug!("code written to {}", path.display());
+        }
         llm_response
     }
 }
 
 
-
-
 const PROMPT_TEMPLATE_RESPONSE_SAMPLE_FULL: &str = r#"
 ## main.py
 
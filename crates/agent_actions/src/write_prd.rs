@@ -1,63 +1,16 @@
-use std::{collections::HashMap, sync::{Mutex, Arc}, fs, io::Write};
+use std::{collections::HashMap, path::PathBuf, sync::{Mutex, Arc}, fs, io::Write};
 use async_trait::async_trait;
 use tracing::{debug, info};
 
 use agent_schema::Message;
-use agent_prompts::PromptTemplate;
-use crate::action_base::Action;
+use crate::action_base::{Action, DEFAULT_WORKSPACE};
+use crate::prompts::prompt_template;
+use crate::context::{messages_context, repository_context};
 use agent_macro::ActionMacro;
 use agent_utils::{CodeParser, async_save_diagram};
 
 pub use agent_provider::{LLM, LLMBase};
 
-const PROMPT_TEMPLATE: &str = r#"
-# Context
-## Original Requirements
-{{requirements}}
-
-## Search Information
-{{search_information}}
-
-## mermaid quadrantChart code syntax example. DONT USE QUOTO IN CODE DUE TO INVALID SYNTAX. Replace the <Campain X> with REAL COMPETITOR NAME
-```mermaid
-quadrantChart
-    title Reach and engagement of campaigns
-    x-axis Low Reach --> High Reach
-    y-axis Low Engagement --> High Engagement
-    quadrant-1 We should expand
-    quadrant-2 Need to promote
-    quadrant-3 Re-evaluate
-    quadrant-4 May be improved
-    "Campaign: A": [0.3, 0.6]
-    "Campaign B": [0.45, 0.23]
-    "Campaign C": [0.57, 0.69]
-    "Campaign D": [0.78, 0.34]
-    "Campaign E": [0.40, 0.34]
-    "Campaign F": [0.35, 0.78]
-    "Our Target Product": [0.5, 0.6]
-```
------
-Role: You are a professional product manager; the goal is to design a concise, usable, efficient product
-Requirements: According to the context, fill in the following missing information, note that each sections are returned in Python code triple quote form seperatedly. If the requirements are unclear, ensure minimum viability and avoid excessive design
-ATTENTION: Use '##' to SPLIT SECTIONS, not '#'. AND '## <SECTION_NAME>' SHOULD WRITE BEFORE the code and triple quote.
-
-## Original Requirements: Provide as Plain text, place the polished complete original requirements here
-
-## Product Goals: Provided as Python list[str], up to 3 clear, orthogonal product goals. If the requirement itself is simple, the goal should also be simple
-
-## User Stories: Provided as Python list[str], up to 5 scenario-based user stories, If the requirement itself is simple, the user stories should also be less
-
-## Competitive Analysis: Provided as Python list[str], up to 7 competitive product analyses, consider as similar competitors as possible
-
-## Competitive Quadrant Chart: Use mermaid quadrantChart code syntax. up to 14 competitive products. Translation: Distribute these competitor scores evenly between 0 and 1, trying to conform to a normal distribution centered around 0.5 as much as possible.
-
-## Requirement Analysis: Provide as Plain text. Be simple. LESS IS MORE. Make your requirements less dumb. Delete the parts unnessasery.
-
-## Requirement Pool: Provided as Python list[str, str], the parameters are requirement description, priority(P0/P1/P2), respectively, comply with PEP standards; no more than 5 requirements and consider to make its difficulty lower
-
-## Anything UNCLEAR: Provide as Plain text. Make clear here.
-
-"#;
 
 #[derive(Debug, ActionMacro)]
 pub struct WritePRD {
@@ -66,6 +19,7 @@ pub struct WritePRD {
     context: String,
     prefix: String,
     profile: String,
+    workspace: PathBuf,
 }
 impl WritePRD {
     pub fn new(name: &str, context: &str, prefix:&str, profile: &str, _llm: Arc<Mutex<dyn LLMBase>>) -> Self {
@@ -75,15 +29,18 @@ impl WritePRD {
             name: name.into(),
             context: context.into(),
             prefix: prefix.into(),
-            profile: profile.into()
+            profile: profile.into(),
+            workspace: PathBuf::from(DEFAULT_WORKSPACE),
         }
     }
 
     async fn _build_prompt(&self, msgs: Vec<&Message>) -> String {
-        let template = PromptTemplate::new(PROMPT_TEMPLATE);
+        let template = prompt_template("write_prd");
+        let repository = repository_context(&msgs);
+        let requirements = messages_context(&msgs, &["RepositorySummary"]);
         let mut args = HashMap::new();
-        // TODO 待优化
-        args.insert("requirements", msgs[0].content.as_str());
+        args.insert("repository", repository.as_str());
+        args.insert("requirements", requirements.as_str());
         args.insert("search_information", "");
         let prompt = template.render(&args); 
         prompt
@@ -95,13 +52,15 @@ impl WritePRD {
         let mermaid = CodeParser::new().parse_code("Competitive Quadrant Chart", &llm_response, "mermaid")
             .expect("unable to parse mermaid code for Competitive Quadrant Chart");
         // debug!("mermaid:\n {}", mermaid);
-        let res = async_save_diagram(&mermaid, "workshop/competitive_quadrant_chart.png").await;
+        fs::create_dir_all(&self.workspace).unwrap();
+        let chart = self.workspace.join("competitive_quadrant_chart.png").to_string_lossy().to_string();
+        let res = async_save_diagram(&mermaid, &chart).await;
         if let Ok(_res) = res {
             debug!("save mermaid:\n {}", mermaid);
         } else {
-            info!("failed to save workshop/competitive_quadrant_chart.png :\n {}", mermaid);
+            info!("failed to save {} :\n {}", chart, mermaid);
         }
-        let mut file = fs::File::create("workshop/prd.md").unwrap();
+        let mut file = fs::File::create(self.workspace.join("prd.md")).unwrap();
         file.write_all(llm_response.as_bytes()).expect("failed to write prd.md");
         llm_response
     }
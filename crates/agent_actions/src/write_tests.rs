@@ -0,0 +1,94 @@
+use std::{collections::HashMap, path::PathBuf, sync::{Mutex, Arc}, fs};
+use async_trait::async_trait;
+use tracing::{debug, info, warn};
+
+use agent_schema::Message;
+use crate::action_base::{Action, DEFAULT_WORKSPACE};
+use crate::prompts::prompt_template;
+use crate::context::{api_spec_context, repository_context};
+use agent_macro::ActionMacro;
+use agent_utils::CodeParser;
+
+pub use agent_provider::{LLM, LLMBase};
+
+const TESTS_FILE: &str = "tests/test_api.py";
+
+/// Write pytest tests exercising every operation of the `WriteApiSpec` contract and save
+/// them to `tests/test_api.py` in the workspace.
+#[derive(Debug, ActionMacro)]
+pub struct WriteTests {
+    _llm: Box<dyn LLMBase>,
+    prefix: String,
+    profile: String,
+    workspace: PathBuf,
+}
+impl WriteTests {
+    pub fn new(_name: &str, _context: &str, prefix:&str, profile: &str, _llm: Arc<Mutex<dyn LLMBase>>) -> Self {
+
+        Self {
+            _llm: Box::new(LLM::new()),
+            prefix: prefix.into(),
+            profile: profile.into(),
+            workspace: PathBuf::from(DEFAULT_WORKSPACE),
+        }
+    }
+
+    async fn _build_prompt(&self, msgs: Vec<&Message>) -> String {
+        let template = prompt_template("write_tests");
+        let repository = repository_context(&msgs);
+        let api_spec = api_spec_context(&msgs);
+        let design = msgs.iter().rev().find(|msg| msg.cause_by == "WriteDesign").unwrap_or(&msgs[0]);
+        let mut args = HashMap::new();
+        args.insert("repository", repository.as_str());
+        args.insert("api_spec", api_spec.as_str());
+        args.insert("context", design.content.as_str());
+        template.render(&args)
+    }
+
+    async fn _post_processing(&self, _msgs: Vec<&Message>, llm_response: String) -> String {
+        let Ok(tests) = CodeParser::new().parse_code("test_api.py", &llm_response, "python") else {
+            warn!("【WriteTests】no ```python block found in the `## test_api.py` section, not saved");
+            return llm_response;
+        };
+        let path = self.workspace.join(TESTS_FILE);
+        let saved = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, tests.as_bytes()));
+        match saved {
+            Ok(_) => info!("【WriteTests】saved {}", path.display()),
+            Err(e) => warn!("failed to save {}: {}", path.display(), e),
+        }
+        debug!("{}", tests);
+        llm_response
+    }
+}
+
+/// The answer `run` (derived by `ActionMacro`) post-processes instead of asking the LLM when it is faked.
+const PROMPT_TEMPLATE_RESPONSE_SAMPLE_FULL: &str = r#"
+## test_api.py
+```python
+import requests
+
+BASE_URL = "http://localhost:5000"
+
+
+def test_start_game():
+    response = requests.post(f"{BASE_URL}/games")
+    assert response.status_code == 201
+    game = response.json()
+    assert game["score"] == 0
+    assert game["game_over"] is False
+
+
+def test_move_rejects_unknown_direction():
+    game = requests.post(f"{BASE_URL}/games").json()
+    response = requests.post(f"{BASE_URL}/games/{game['id']}/moves", json={"direction": "sideways"})
+    assert response.status_code == 400
+
+
+def test_move_missing_game():
+    response = requests.post(f"{BASE_URL}/games/missing/moves", json={"direction": "up"})
+    assert response.status_code == 404
+```
+"#;
@@ -0,0 +1,319 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use agent_provider::LLM;
+use agent_schema::Message;
+
+/// Characters of each artifact shown to the judge, the beginning of long code is enough
+/// to tell whether a requirement is addressed.
+const ARTIFACT_CHARS: usize = 6000;
+
+const JUDGE_PROMPT: &str = r#"You are the acceptance tester of a software company.
+
+## Original requirement
+{requirement}
+
+## Deliverables
+{artifacts}
+
+## Acceptance criteria
+{criteria}
+
+Decide for every criterion whether the deliverables satisfy it. Answer with exactly one
+line per criterion, in order, formatted as `<number>. PASS - <reason>` or
+`<number>. FAIL - <reason>`, and nothing else."#;
+
+/// One requirement of the checklist. It is met when all its checks hold; an item without
+/// checks is left to the judge.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChecklistItem {
+    pub requirement: String,
+    /// A message caused by this action was published, e.g. `WriteCode`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published: Option<String>,
+    /// This file exists in the workspace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    /// A deliverable mentions this text, case insensitive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mentions: Option<String>,
+}
+
+impl ChecklistItem {
+    fn is_judged(&self) -> bool {
+        self.published.is_none() && self.file.is_none() && self.mentions.is_none()
+    }
+
+    /// The failed check, if any.
+    fn check(&self, artifacts: &[Message], workspace: &Path) -> Option<String> {
+        if let Some(action) = &self.published {
+            if !artifacts.iter().any(|msg| &msg.cause_by == action) {
+                return Some(format!("no {} was published", action));
+            }
+        }
+        if let Some(file) = &self.file {
+            if !workspace.join(file).exists() {
+                return Some(format!("{} is missing from the workspace", file.display()));
+            }
+        }
+        if let Some(text) = &self.mentions {
+            let text = text.to_lowercase();
+            if !artifacts.iter().any(|msg| msg.content.to_lowercase().contains(&text)) {
+                return Some(format!("no deliverable mentions {}", text));
+            }
+        }
+        None
+    }
+}
+
+/// Decides the checklist items that need reading the deliverables.
+#[async_trait]
+pub trait Judge: Send + Sync {
+    /// Answer a prompt built by `AcceptanceEvaluator`.
+    async fn ask(&self, prompt: &str) -> anyhow::Result<String>;
+}
+
+/// Judge with the configured LLM.
+#[derive(Debug, Clone, Default)]
+pub struct LlmJudge {
+    llm: LLM,
+}
+
+impl LlmJudge {
+    pub fn new(llm: LLM) -> Self {
+        Self { llm }
+    }
+}
+
+#[async_trait]
+impl Judge for LlmJudge {
+    async fn ask(&self, prompt: &str) -> anyhow::Result<String> {
+        if agent_provider::is_llm_fake() {
+            agent_provider::record_prompt(prompt);
+            return Ok(String::new());
+        }
+        self.llm.aask(prompt).await.map_err(|e| anyhow::anyhow!("the judge failed: {}", e))
+    }
+}
+
+/// The outcome of one checklist item.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CriterionResult {
+    pub requirement: String,
+    pub met: bool,
+    pub reason: String,
+}
+
+/// The checklist as evaluated against the deliverables.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AcceptanceReport {
+    pub results: Vec<CriterionResult>,
+}
+
+impl AcceptanceReport {
+    pub fn accepted(&self) -> bool {
+        self.results.iter().all(|result| result.met)
+    }
+
+    pub fn unmet(&self) -> Vec<&CriterionResult> {
+        self.results.iter().filter(|result| !result.met).collect()
+    }
+}
+
+impl fmt::Display for AcceptanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let met = self.results.iter().filter(|result| result.met).count();
+        write!(f, "{}/{} requirements met", met, self.results.len())?;
+        for result in self.unmet() {
+            write!(f, "\n- {}: {}", result.requirement, result.reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the deliverables of a run against the boss requirement: the checks of the
+/// checklist first, then, once they all hold, the judge on the remaining items.
+pub struct AcceptanceEvaluator {
+    pub checklist: Vec<ChecklistItem>,
+    judge: Box<dyn Judge>,
+}
+
+impl AcceptanceEvaluator {
+    pub fn new(checklist: Vec<ChecklistItem>, judge: Box<dyn Judge>) -> Self {
+        Self { checklist, judge }
+    }
+
+    /// The default pipeline produced a PRD, a design and code that fulfil the requirement.
+    pub fn default_checklist() -> Vec<ChecklistItem> {
+        let published = |requirement: &str, action: &str| ChecklistItem {
+            requirement: requirement.into(),
+            published: Some(action.into()),
+            ..Default::default()
+        };
+        vec![
+            published("A PRD was written", "WritePRD"),
+            published("A system design was written", "WriteDesign"),
+            published("Code was written", "WriteCode"),
+            ChecklistItem { requirement: "The deliverables fulfil the original requirement".into(), ..Default::default() },
+        ]
+    }
+
+    /// Read a checklist from a YAML list of `ChecklistItem`.
+    pub fn load_checklist(path: &Path) -> anyhow::Result<Vec<ChecklistItem>> {
+        let yaml = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+        let checklist: Vec<ChecklistItem> =
+            serde_yaml::from_str(&yaml).with_context(|| format!("invalid checklist {}", path.display()))?;
+        if checklist.is_empty() {
+            anyhow::bail!("the checklist {} is empty", path.display());
+        }
+        Ok(checklist)
+    }
+
+    /// Evaluate the published messages. The judge is only asked when every check holds,
+    /// before that its items are reported as pending.
+    pub async fn evaluate(&self, artifacts: &[Message], workspace: &Path) -> AcceptanceReport {
+        let mut results: Vec<CriterionResult> = self
+            .checklist
+            .iter()
+            .map(|item| {
+                let failed = if item.is_judged() { None } else { item.check(artifacts, workspace) };
+                CriterionResult {
+                    requirement: item.requirement.clone(),
+                    met: failed.is_none() && !item.is_judged(),
+                    reason: failed.unwrap_or_else(|| "checked".into()),
+                }
+            })
+            .collect();
+        let judged: Vec<usize> = (0..self.checklist.len()).filter(|idx| self.checklist[*idx].is_judged()).collect();
+        if judged.is_empty() {
+            return AcceptanceReport { results };
+        }
+        if self.checklist.iter().zip(&results).any(|(item, result)| !item.is_judged() && !result.met) {
+            for idx in judged {
+                results[idx].reason = "not judged until the other requirements are met".into();
+            }
+            return AcceptanceReport { results };
+        }
+
+        let prompt = self._judge_prompt(artifacts, &judged);
+        let verdicts = match self.judge.ask(&prompt).await {
+            Ok(answer) => parse_verdicts(&answer),
+            Err(e) => {
+                tracing::warn!("acceptance judge failed: {:#}", e);
+                vec![]
+            }
+        };
+        for (n, idx) in judged.into_iter().enumerate() {
+            let (met, reason) = verdicts
+                .iter()
+                .find(|(number, _, _)| *number == n + 1)
+                .map(|(_, met, reason)| (*met, reason.clone()))
+                .unwrap_or((false, "the judge gave no verdict".into()));
+            results[idx].met = met;
+            results[idx].reason = reason;
+        }
+        AcceptanceReport { results }
+    }
+
+    fn _judge_prompt(&self, artifacts: &[Message], judged: &[usize]) -> String {
+        let requirement = artifacts
+            .iter()
+            .filter(|msg| msg.cause_by == "BossRequirement")
+            .map(|msg| msg.content.as_str())
+            .collect::<Vec<&str>>()
+            .join("\n");
+        let deliverables = artifacts
+            .iter()
+            .filter(|msg| msg.cause_by != "BossRequirement")
+            .map(|msg| format!("### {} ({})\n{}", msg.cause_by, msg.role, truncate(&msg.content, ARTIFACT_CHARS)))
+            .collect::<Vec<String>>()
+            .join("\n\n");
+        let criteria = judged
+            .iter()
+            .enumerate()
+            .map(|(n, idx)| format!("{}. {}", n + 1, self.checklist[*idx].requirement))
+            .collect::<Vec<String>>()
+            .join("\n");
+        JUDGE_PROMPT
+            .replace("{requirement}", &requirement)
+            .replace("{artifacts}", &deliverables)
+            .replace("{criteria}", &criteria)
+    }
+}
+
+/// `(number, met, reason)` of every `<number>. PASS|FAIL - <reason>` line.
+fn parse_verdicts(answer: &str) -> Vec<(usize, bool, String)> {
+    answer
+        .lines()
+        .filter_map(|line| {
+            let (number, rest) = line.trim().split_once(['.', ')'])?;
+            let number = number.trim().parse().ok()?;
+            let rest = rest.trim();
+            let met = if rest.starts_with("PASS") {
+                true
+            } else if rest.starts_with("FAIL") {
+                false
+            } else {
+                return None;
+            };
+            let reason = rest[4..].trim_start_matches([' ', '-', ':']).trim().to_string();
+            Some((number, met, reason))
+        })
+        .collect()
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}\n[... truncated]", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct ScriptedJudge(&'static str);
+
+    #[async_trait]
+    impl Judge for ScriptedJudge {
+        async fn ask(&self, prompt: &str) -> anyhow::Result<String> {
+            assert!(prompt.contains("1. The snake grows"));
+            Ok(self.0.to_string())
+        }
+    }
+
+    fn message(cause_by: &str, content: &str) -> Message {
+        Message { content: content.into(), cause_by: cause_by.into(), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_acceptance_report() {
+        let checklist = vec![
+            ChecklistItem { requirement: "Code was written".into(), published: Some("WriteCode".into()), ..Default::default() },
+            ChecklistItem { requirement: "The snake grows".into(), ..Default::default() },
+            ChecklistItem { requirement: "There is a score".into(), ..Default::default() },
+        ];
+        let workspace = Path::new(crate::DEFAULT_WORKSPACE);
+        let evaluator = AcceptanceEvaluator::new(checklist.clone(), Box::new(ScriptedJudge("1. PASS - it grows\n2) FAIL: no score")));
+
+        let mut artifacts = vec![message("BossRequirement", "a snake game")];
+        let report = evaluator.evaluate(&artifacts, workspace).await;
+        assert_eq!(report.unmet().len(), 3);
+        assert_eq!(report.results[0].reason, "no WriteCode was published");
+
+        artifacts.push(message("WriteCode", "def grow(): ..."));
+        let report = evaluator.evaluate(&artifacts, workspace).await;
+        assert!(!report.accepted());
+        assert_eq!(report.unmet()[0].requirement, "There is a score");
+        assert_eq!(report.unmet()[0].reason, "no score");
+        assert!(report.to_string().starts_with("2/3 requirements met"));
+
+        let evaluator = AcceptanceEvaluator::new(checklist, Box::new(ScriptedJudge("1. PASS\n2. PASS")));
+        assert!(evaluator.evaluate(&artifacts, workspace).await.accepted());
+    }
+}
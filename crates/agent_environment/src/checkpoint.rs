@@ -0,0 +1,132 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use agent_schema::Message;
+
+use crate::transcript::Transcript;
+
+/// File of the latest checkpoint inside a run directory.
+pub const CHECKPOINT_FILE: &str = "checkpoint.json";
+
+/// Everything needed to continue a run: the shared memory, every role's progress and
+/// the files the roles had written to the workspace.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub memory: Vec<Message>,
+    pub roles: Vec<RoleCheckpoint>,
+    pub goal: Option<String>,
+    pub workspace: Vec<WorkspaceFile>,
+    #[serde(default)]
+    pub transcript: Transcript,
+}
+
+/// A role as of its last completed turn.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoleCheckpoint {
+    pub profile: String,
+    /// Index of the action it ran last, see `RoleContext::state`.
+    pub state: i32,
+    /// Turns taken so far, counted against the round limit.
+    pub turns: usize,
+    pub memory: Vec<Message>,
+}
+
+/// A workspace file as it was when the checkpoint was written.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkspaceFile {
+    /// Relative to the workspace directory.
+    pub path: PathBuf,
+    pub size: u64,
+    /// FNV-1a hash of the content.
+    pub hash: u64,
+}
+
+impl Checkpoint {
+    /// Write `checkpoint.json` in `run_dir` through a temporary file, so a crash while
+    /// saving leaves the previous checkpoint intact.
+    pub fn save(&self, run_dir: &Path) -> anyhow::Result<()> {
+        fs::create_dir_all(run_dir).with_context(|| format!("failed to create {}", run_dir.display()))?;
+        let path = run_dir.join(CHECKPOINT_FILE);
+        let tmp = run_dir.join(format!("{}.tmp", CHECKPOINT_FILE));
+        fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&tmp, &path).with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn load(run_dir: &Path) -> anyhow::Result<Checkpoint> {
+        let path = run_dir.join(CHECKPOINT_FILE);
+        let json = fs::read(&path).with_context(|| format!("no checkpoint at {}", path.display()))?;
+        serde_json::from_slice(&json).with_context(|| format!("invalid checkpoint {}", path.display()))
+    }
+}
+
+/// The files under `workspace`, sorted by path. A missing workspace is empty.
+pub fn workspace_manifest(workspace: &Path) -> Vec<WorkspaceFile> {
+    let mut files = vec![];
+    let mut dirs = vec![workspace.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else { continue };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if let Ok(content) = fs::read(&path) {
+                files.push(WorkspaceFile {
+                    path: path.strip_prefix(workspace).unwrap_or(&path).to_path_buf(),
+                    size: content.len() as u64,
+                    hash: fnv1a(&content),
+                });
+            }
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    files
+}
+
+/// Describe how the workspace differs from `manifest`, one line per missing or changed file.
+pub fn workspace_changes(workspace: &Path, manifest: &[WorkspaceFile]) -> Vec<String> {
+    let current = workspace_manifest(workspace);
+    manifest
+        .iter()
+        .filter_map(|file| match current.iter().find(|f| f.path == file.path) {
+            None => Some(format!("{} is missing", file.path.display())),
+            Some(f) if f != file => Some(format!("{} has changed", file.path.display())),
+            Some(_) => None,
+        })
+        .collect()
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspace = dir.path().join("workshop");
+        fs::create_dir_all(workspace.join("src")).unwrap();
+        fs::write(workspace.join("src/main.py"), "print('hi')").unwrap();
+
+        let checkpoint = Checkpoint {
+            memory: vec![Message { content: "idea".into(), cause_by: "BossRequirement".into(), ..Default::default() }],
+            roles: vec![RoleCheckpoint { profile: "Architect".into(), state: 0, turns: 1, memory: vec![] }],
+            goal: None,
+            workspace: workspace_manifest(&workspace),
+            transcript: Transcript::default(),
+        };
+        checkpoint.save(dir.path()).unwrap();
+        assert_eq!(Checkpoint::load(dir.path()).unwrap(), checkpoint);
+        assert_eq!(checkpoint.workspace[0].path, PathBuf::from("src/main.py"));
+        assert!(workspace_changes(&workspace, &checkpoint.workspace).is_empty());
+
+        fs::write(workspace.join("src/main.py"), "print('bye')").unwrap();
+        assert_eq!(workspace_changes(&workspace, &checkpoint.workspace), vec!["src/main.py has changed"]);
+    }
+}
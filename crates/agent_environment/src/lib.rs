@@ -1,35 +1,111 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use std::sync::mpsc;
 
+use chrono::{DateTime, Utc};
+use futures::future::{join_all, select, Either};
+use futures::pin_mut;
 
-use agent_schema::Message;
-use agent_memory::Memory;
-use agent_roles::Role;
-use tracing::info;
+use agent_provider::capture_prompts;
+use agent_schema::{Message, MESSAGE_ROUTE_TO_ALL};
+use agent_memory::{Embedder, LocalVectorIndex, Memory, MemoryBackend, MemoryNamespace, SemanticIndex};
+use agent_roles::{AskHuman, Role};
+use tracing::{info, warn};
+
+mod acceptance;
+mod checkpoint;
+mod message_broker;
+mod scheduler;
+mod transcript;
+
+pub use agent_actions::DEFAULT_WORKSPACE;
+pub use acceptance::{AcceptanceEvaluator, AcceptanceReport, ChecklistItem, CriterionResult, Judge, LlmJudge};
+pub use checkpoint::{Checkpoint, RoleCheckpoint, WorkspaceFile, CHECKPOINT_FILE, workspace_changes, workspace_manifest};
+pub use message_broker::{BrokerStatus, MessageBroker, Subscription, Topic, DEFAULT_BROKER_CAPACITY};
+pub use scheduler::StopReason;
+pub use transcript::{Transcript, TranscriptEntry, TranscriptFiles};
 
 /// "Environment, hosting a batch of roles, roles can publish messages to the environment, and can be observed by other roles."
 pub struct Environment {
     pub roles: HashMap<String, Box<dyn Role>>,
-    pub message_queue: Arc<Mutex<mpsc::Sender<Message>>>,
+    /// Delivers the published messages to the roles, see `MessageBroker`.
+    pub broker: Arc<MessageBroker>,
     pub memory: Arc<Mutex<Memory>>,
-    pub history: String,
+    /// Every published message with its round, timestamps and prompts.
+    transcript: Mutex<Transcript>,
+    human_gates: Option<(Arc<dyn AskHuman>, HashSet<String>)>,
+    /// Profiles in hiring order.
+    order: Vec<String>,
+    /// The broker subscription of every role, by profile.
+    subscriptions: HashMap<String, Subscription>,
+    /// `cause_by` of the message that completes the run.
+    goal: Option<String>,
+    /// Every role as of its last completed turn, by profile.
+    progress: Mutex<HashMap<String, RoleCheckpoint>>,
+    /// Where a checkpoint is written after each turn, none when unset.
+    run_dir: Option<PathBuf>,
+    /// Directory the actions write their artifacts to, listed in the checkpoints.
+    workspace: PathBuf,
+    /// Ends the run once the deliverables pass its checklist.
+    acceptance: Option<AcceptanceEvaluator>,
+    /// The latest evaluation of `acceptance`.
+    report: Mutex<Option<AcceptanceReport>>,
+    /// Keeps the roles' memories across runs, under the project name.
+    memory_backend: Option<(Arc<dyn MemoryBackend>, String)>,
+    /// Embeds the roles' memories for `Memory::remember`.
+    embedder: Option<Arc<dyn Embedder>>,
 }
 
 impl Environment {
     pub fn new() -> Self {
-        let (tx, _) = mpsc::channel(); // Adjust the channel size as needed
+        Self::with_capacity(DEFAULT_BROKER_CAPACITY)
+    }
+
+    /// An environment whose broker holds at most `capacity` unread messages per role before
+    /// the other roles publishing to it wait.
+    pub fn with_capacity(capacity: usize) -> Self {
         Self {
             roles: HashMap::new(),
-            message_queue: Arc::new(Mutex::new(tx)),
+            broker: Arc::new(MessageBroker::new(capacity)),
             memory:  Arc::new(Mutex::new(Memory::new())),
-            history: String::new(),
+            transcript: Mutex::new(Transcript::default()),
+            human_gates: None,
+            order: Vec::new(),
+            subscriptions: HashMap::new(),
+            goal: None,
+            progress: Mutex::new(HashMap::new()),
+            run_dir: None,
+            workspace: PathBuf::from(DEFAULT_WORKSPACE),
+            acceptance: None,
+            report: Mutex::new(None),
+            memory_backend: None,
+            embedder: None,
         }
     }
     /// Add a role in the current environment.
     pub fn add_role(&mut self, mut role: Box<dyn Role>) {
         role.set_env_global_memory(self.memory.clone());
-        self.roles.insert(role._get_profile().to_string(), role);
+        role.set_workspace(&self.workspace);
+        if let Some((ask_human, actions)) = &self.human_gates {
+            role.set_ask_human(ask_human.clone(), actions.clone());
+        }
+        let profile = role._get_profile().to_string();
+        if let Some((backend, project)) = &self.memory_backend {
+            if let Err(e) = Self::_attach_memory(role.as_ref(), backend, project) {
+                warn!("the memory of {} is not persisted: {:#}", profile, e);
+            }
+        }
+        if let Some(embedder) = &self.embedder {
+            Self::_index_memory(role.as_ref(), embedder);
+        }
+        let topic = Topic::new(&profile, role._get_rc().watch().clone());
+        self.subscriptions.insert(profile.clone(), self.broker.subscribe(topic));
+        let progress = RoleCheckpoint { profile: profile.clone(), state: -1, ..Default::default() };
+        self.progress.lock().unwrap().insert(profile.clone(), progress);
+        if self.roles.insert(profile.clone(), role).is_none() {
+            self.order.push(profile);
+        }
     }
 
     /// Add a batch of characters in the current environment.
@@ -39,25 +115,268 @@ impl Environment {
         }
     }
 
+    /// Pause every role after the given actions until `ask_human` approves their output.
+    pub fn set_ask_human(&mut self, ask_human: Arc<dyn AskHuman>, actions: HashSet<String>) {
+        for role in self.roles.values_mut() {
+            role.set_ask_human(ask_human.clone(), actions.clone());
+        }
+        self.human_gates = Some((ask_human, actions));
+    }
+
+    /// Let every role critique and revise its action outputs up to `max_rounds` times.
+    pub fn set_reflection(&mut self, max_rounds: usize) {
+        for role in self.roles.values_mut() {
+            role.set_reflection(max_rounds);
+        }
+    }
+
+    /// Stop the run as soon as a message caused by `cause_by` (e.g. `WriteCode`) is published.
+    pub fn set_goal(&mut self, cause_by: &str) {
+        self.goal = Some(cause_by.to_string());
+    }
+
+    /// Evaluate the deliverables whenever messages are published and stop the run once
+    /// they are accepted, see `acceptance_report` for what is still unmet.
+    pub fn set_acceptance(&mut self, evaluator: AcceptanceEvaluator) {
+        self.acceptance = Some(evaluator);
+    }
+
+    /// The latest acceptance evaluation, none before the first or without evaluator.
+    pub fn acceptance_report(&self) -> Option<AcceptanceReport> {
+        self.report.lock().unwrap().clone()
+    }
+
+    /// Persist the memory of every role in `backend`, namespaced by `project` and profile,
+    /// so that the roles remember earlier runs of the same project.
+    pub fn set_memory_backend(&mut self, backend: Arc<dyn MemoryBackend>, project: &str) -> anyhow::Result<()> {
+        for role in self.roles.values() {
+            Self::_attach_memory(role.as_ref(), &backend, project)?;
+        }
+        self.memory_backend = Some((backend, project.to_string()));
+        Ok(())
+    }
+
+    fn _attach_memory(role: &dyn Role, backend: &Arc<dyn MemoryBackend>, project: &str) -> anyhow::Result<()> {
+        let namespace = MemoryNamespace::new(project, role._get_profile());
+        role._get_rc_memory().attach(backend.clone(), namespace)
+    }
+
+    /// Embed the memory of every role with `embedder`, each in its own in-process index, so
+    /// that the roles recall their messages by relevance.
+    pub fn set_embedder(&mut self, embedder: Arc<dyn Embedder>) {
+        for role in self.roles.values() {
+            Self::_index_memory(role.as_ref(), &embedder);
+        }
+        self.embedder = Some(embedder);
+    }
+
+    fn _index_memory(role: &dyn Role, embedder: &Arc<dyn Embedder>) {
+        let semantic = SemanticIndex::new(embedder.clone(), Arc::new(LocalVectorIndex::default()));
+        role._get_rc_memory().set_semantic_index(semantic);
+    }
+
+    /// Write a checkpoint to `run_dir` after every turn, see `restore`.
+    pub fn set_run_dir(&mut self, run_dir: &Path) {
+        self.run_dir = Some(run_dir.to_path_buf());
+    }
+
+    /// The directory the actions write to, `workshop` by default.
+    pub fn set_workspace(&mut self, workspace: &Path) {
+        self.workspace = workspace.to_path_buf();
+        for role in self.roles.values_mut() {
+            role.set_workspace(workspace);
+        }
+    }
+
+    /// Snapshot of the run: the shared memory and every role as of its last completed turn.
+    /// A role's reply reaches the shared memory before its turn completes, the replies of
+    /// turns still running are left out so that they are not there twice once run again.
+    pub fn checkpoint(&self) -> Checkpoint {
+        let progress = self.progress.lock().unwrap();
+        let completed = |message: &Message| progress.get(&message.role).map_or(true, |role| message.round <= role.turns);
+        let mut transcript = self.transcript();
+        transcript.entries.retain(|entry| completed(&entry.message));
+        Checkpoint {
+            memory: self.memory.lock().unwrap().get(0).into_iter().filter(|message| completed(message)).cloned().collect(),
+            roles: self.order.iter().filter_map(|profile| progress.get(profile).cloned()).collect(),
+            goal: self.goal.clone(),
+            workspace: workspace_manifest(&self.workspace),
+            transcript,
+        }
+    }
+
+    pub fn transcript(&self) -> Transcript {
+        self.transcript.lock().unwrap().clone()
+    }
+
+    /// The published messages, one `[cause_by]: content` entry per line.
+    pub fn history(&self) -> String {
+        self.transcript
+            .lock()
+            .unwrap()
+            .entries
+            .iter()
+            .map(|entry| entry.message.to_string())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Continue from a checkpoint, with the same team hired. The shared messages are
+    /// published again and the roles skip those they had already handled, so only the
+    /// turns unfinished at the time of the checkpoint run again.
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        for change in workspace_changes(&self.workspace, &checkpoint.workspace) {
+            warn!("workspace differs from the checkpoint: {}", change);
+        }
+        for role in checkpoint.roles {
+            let Some(hired) = self.roles.get(&role.profile) else {
+                warn!("checkpoint has a role {} that is not hired, skipping it", role.profile);
+                continue;
+            };
+            let rc = hired._get_rc();
+            rc.role_memory.lock().unwrap().add_batch(role.memory.clone());
+            rc.set_state(role.state);
+            self.progress.lock().unwrap().insert(role.profile.clone(), role);
+        }
+        // replayed messages are already in the restored transcript
+        for message in checkpoint.memory {
+            self.memory.lock().unwrap().add(message.clone());
+            self.broker.publish_now(message);
+        }
+        *self.transcript.lock().unwrap() = checkpoint.transcript;
+        if self.goal.is_none() {
+            self.goal = checkpoint.goal;
+        }
+    }
+
     // fn set_manager(&mut self, manager: Box<dyn Manager>) {
     //     // Placeholder for set_manager method
     // }
 
-    /// Post information to the current environment.
+    /// Post information to the current environment. Roles observe it according to its
+    /// `send_to` recipients, or their watched `cause_by` when it has none.
     pub fn publish_message(&mut self, message: Message) {
-        // Placeholder for publish_message method
-        self.memory.lock().unwrap().add(message);
+        for recipient in &message.send_to {
+            if recipient != MESSAGE_ROUTE_TO_ALL && !self.roles.contains_key(recipient) {
+                warn!("message from {} is addressed to unknown role {}", message.role, recipient);
+            }
+        }
+        let now = Utc::now();
+        self._record(0, now, message.clone(), vec![]);
+        self.memory.lock().unwrap().add(message.clone());
+        self.broker.publish_now(message);
     }
 
-    /// Process all Role runs at once.
-    pub async fn run(&mut self, _k: usize) {
-        // Placeholder for run method
-        // let msg = Message::default();
-        // let msg = Message::form("Hello", "user", "cause_by", "instruct_content");
-        for role in self.roles.values() {
-            info!("----------------------------  Running role {:?} -----------------------", role._get_profile());
-            role.run(None).await;
+    fn _record(&self, round: usize, started_at: DateTime<Utc>, message: Message, prompts: Vec<String>) {
+        let entry = TranscriptEntry { round, started_at, published_at: Utc::now(), message, prompts };
+        self.transcript.lock().unwrap().entries.push(entry);
+    }
+
+    /// Run the roles until none has new input, the goal is met or a role has reacted
+    /// `max_rounds` times. Every role awaits the messages of its topic on the broker and
+    /// reacts as soon as some arrive, concurrently with the others.
+    pub async fn run(&mut self, max_rounds: usize) -> StopReason {
+        for subscription in self.subscriptions.values() {
+            subscription.reopen();
+        }
+        let done = self.progress.lock().unwrap().values().map(|role| role.turns).max().unwrap_or(0);
+        let turns = AtomicUsize::new(done);
+        let roles = join_all(self.order.iter().map(|profile| self._role_loop(profile, max_rounds, &turns)));
+        let supervisor = self._supervise(max_rounds, &turns);
+        pin_mut!(roles, supervisor);
+        match select(roles, supervisor).await {
+            // every role used its turns, the supervisor has the last word
+            Either::Left((_, supervisor)) => supervisor.await,
+            Either::Right((reason, _)) => reason,
+        }
+    }
+
+    /// Feed a role the messages of its topic until it has used `max_rounds` turns.
+    async fn _role_loop(&self, profile: &str, max_rounds: usize, turns: &AtomicUsize) {
+        let role = self.roles[profile].as_ref();
+        let subscription = &self.subscriptions[profile];
+        let mut turn = self.progress.lock().unwrap().get(profile).map_or(0, |role| role.turns);
+        while turn < max_rounds {
+            let Some(news) = subscription.recv().await else { return };
+            info!("----------------------------  Running role {:?}, turn {} -----------------------", profile, turn + 1);
+            let started_at = Utc::now();
+            role._get_rc().set_round(turn + 1);
+            let (rsp, prompts) = capture_prompts(role.run_with(news)).await;
+            // nothing to do when every message was handled before a resume
+            let Some(rsp) = rsp else { continue };
+            turn += 1;
+            turns.fetch_max(turn, Ordering::SeqCst);
+            self._record(turn, started_at, rsp.clone(), prompts);
+            self.broker.publish(rsp).await;
+            self._complete_turn(role, turn);
+        }
+        subscription.close();
+    }
+
+    /// Record the role's progress, write the checkpoint and the remembered messages.
+    fn _complete_turn(&self, role: &dyn Role, turn: usize) {
+        let rc = role._get_rc();
+        let progress = RoleCheckpoint {
+            profile: role._get_profile().to_string(),
+            state: rc.state(),
+            turns: turn,
+            memory: role._get_rc_memory().get(0).into_iter().cloned().collect(),
+        };
+        self.progress.lock().unwrap().insert(progress.profile.clone(), progress);
+        if let Some(run_dir) = &self.run_dir {
+            if let Err(e) = self.checkpoint().save(run_dir) {
+                warn!("failed to checkpoint the run: {:#}", e);
+            }
+        }
+        if let Some((backend, _)) = &self.memory_backend {
+            if let Err(e) = backend.flush() {
+                warn!("failed to persist the memories: {:#}", e);
+            }
+        }
+    }
+
+    /// Wait until the goal is met, the deliverables are accepted or every role waits for input.
+    async fn _supervise(&self, max_rounds: usize, turns: &AtomicUsize) -> StopReason {
+        let mut evaluated = None;
+        loop {
+            let (status, mut changed) = self.broker.status();
+            let rounds = turns.load(Ordering::SeqCst);
+            if let Some(goal) = self._goal_met() {
+                return StopReason::GoalMet { goal, rounds };
+            }
+            if self._accepted(&mut evaluated).await {
+                return StopReason::Accepted { rounds };
+            }
+            match status {
+                BrokerStatus::Idle { starved: true } => return StopReason::MaxRounds(max_rounds),
+                BrokerStatus::Idle { starved: false } => return StopReason::Quiescent { rounds },
+                BrokerStatus::Busy => {}
+            }
+            if changed.changed().await.is_err() {
+                return StopReason::Quiescent { rounds };
+            }
+        }
+    }
+
+    /// Evaluate the deliverables if messages were published since the `evaluated` count.
+    async fn _accepted(&self, evaluated: &mut Option<usize>) -> bool {
+        let Some(evaluator) = &self.acceptance else { return false };
+        let artifacts: Vec<Message> = self.memory.lock().unwrap().get(0).into_iter().cloned().collect();
+        if *evaluated == Some(artifacts.len()) {
+            return false;
         }
+        *evaluated = Some(artifacts.len());
+        let report = evaluator.evaluate(&artifacts, &self.workspace).await;
+        info!("acceptance: {}", report);
+        let accepted = report.accepted();
+        *self.report.lock().unwrap() = Some(report);
+        accepted
+    }
+
+    fn _goal_met(&self) -> Option<String> {
+        let goal = self.goal.as_ref()?;
+        let memory = self.memory.lock().unwrap();
+        (!memory.get_by_action(goal).is_empty()).then(|| goal.clone())
     }
 
     /// Get a specific role within the environment.
@@ -71,4 +390,150 @@ impl Default for Environment {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agent_provider::with_fake_llm;
+    use agent_roles::{ReActRole, REACT_ANSWER};
+
+    fn researcher() -> Box<dyn Role> {
+        let watch = HashSet::from(["BossRequirement".to_string()]);
+        Box::new(ReActRole::new("Rex", "Researcher", "", "", "", watch, vec![]))
+    }
+
+    fn requirement() -> Message {
+        Message { content: "question".into(), role: "BOSS".into(), cause_by: "BossRequirement".into(), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_run_until_quiescent() {
+        with_fake_llm(async {
+            let mut env = Environment::new();
+            assert_eq!(env.run(5).await, StopReason::Quiescent { rounds: 0 });
+
+            env.add_role(researcher());
+            env.publish_message(requirement());
+            assert_eq!(env.run(5).await, StopReason::Quiescent { rounds: 1 });
+            assert_eq!(env.memory.lock().unwrap().get_by_role("Researcher")[0].cause_by, REACT_ANSWER);
+            let transcript = env.transcript();
+            assert_eq!(transcript.entries.len(), 2);
+            assert_eq!((transcript.entries[0].round, transcript.entries[1].round), (0, 1));
+            assert_eq!(transcript.entries[1].message.round, 1);
+            assert!(env.history().starts_with("[BossRequirement]: question"));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_run_stops_on_goal_and_limit() {
+        with_fake_llm(async {
+            let mut env = Environment::new();
+            env.add_role(researcher());
+            env.set_goal(REACT_ANSWER);
+            env.publish_message(requirement());
+            assert_eq!(env.run(5).await, StopReason::GoalMet { goal: REACT_ANSWER.into(), rounds: 1 });
+
+            let mut env = Environment::new();
+            env.add_role(researcher());
+            env.publish_message(requirement());
+            assert_eq!(env.run(0).await, StopReason::MaxRounds(0));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_memory_persists_across_runs() {
+        with_fake_llm(async {
+            let backend: Arc<dyn MemoryBackend> = Arc::new(agent_memory::InMemoryBackend::default());
+            let mut env = Environment::new();
+            env.add_role(researcher());
+            env.set_memory_backend(backend.clone(), "snake").unwrap();
+            env.publish_message(requirement());
+            env.run(5).await;
+            let remembered = env.roles["Researcher"]._get_rc_memory().count();
+            assert!(remembered > 0);
+
+            let mut next_run = Environment::new();
+            next_run.set_memory_backend(backend, "snake").unwrap();
+            next_run.add_role(researcher());
+            assert_eq!(next_run.roles["Researcher"]._get_rc_memory().count(), remembered);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_next_requirement_of_a_persisted_project() {
+        with_fake_llm(async {
+            let backend: Arc<dyn MemoryBackend> = Arc::new(agent_memory::InMemoryBackend::default());
+            let workspace = tempfile::tempdir().unwrap();
+            for idea in ["a snake game", "a todo list"] {
+                let mut env = Environment::new();
+                env.set_workspace(workspace.path());
+                env.add_role(Box::new(agent_roles::ProductManager::default()));
+                env.set_memory_backend(backend.clone(), "games").unwrap();
+                env.publish_message(Message { content: idea.into(), ..requirement() });
+                env.run(1).await;
+                let transcript = env.transcript();
+                let prd = transcript.entries.iter().find(|entry| entry.message.cause_by == "WritePRD").unwrap();
+                assert!(prd.prompts[0].contains(idea));
+            }
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_run_stops_on_acceptance() {
+        with_fake_llm(async {
+            let published = |cause_by: &str| ChecklistItem {
+                requirement: format!("{} published", cause_by),
+                published: Some(cause_by.into()),
+                ..Default::default()
+            };
+            let mut env = Environment::new();
+            env.add_role(researcher());
+            env.set_acceptance(AcceptanceEvaluator::new(vec![published(REACT_ANSWER)], Box::new(LlmJudge::default())));
+            env.publish_message(requirement());
+            assert_eq!(env.run(5).await, StopReason::Accepted { rounds: 1 });
+
+            let mut env = Environment::new();
+            env.add_role(researcher());
+            env.set_acceptance(AcceptanceEvaluator::new(vec![published("WriteCode")], Box::new(LlmJudge::default())));
+            env.publish_message(requirement());
+            assert_eq!(env.run(5).await, StopReason::Quiescent { rounds: 1 });
+            assert_eq!(env.acceptance_report().unwrap().unmet()[0].reason, "no WriteCode was published");
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_checkpoint() {
+        with_fake_llm(async {
+            let run_dir = tempfile::tempdir().unwrap();
+            let mut env = Environment::new();
+            env.set_run_dir(run_dir.path());
+            env.add_role(researcher());
+            env.publish_message(requirement());
+            env.run(5).await;
+
+            let checkpoint = Checkpoint::load(run_dir.path()).unwrap();
+            assert_eq!(checkpoint.roles[0].turns, 1);
+            // the reply of a turn still running is not saved
+            let running = Message { role: "Researcher".into(), round: 2, ..Default::default() };
+            env.memory.lock().unwrap().add(running.clone());
+            env._record(2, Utc::now(), running, vec![]);
+            assert_eq!(env.checkpoint().memory, checkpoint.memory);
+            assert_eq!(env.checkpoint().transcript.entries.len(), 2);
+
+            // the restored researcher has nothing left to do
+            let mut resumed = Environment::new();
+            resumed.add_role(researcher());
+            resumed.restore(checkpoint);
+            assert_eq!(resumed.run(5).await, StopReason::Quiescent { rounds: 1 });
+            assert_eq!(resumed.memory.lock().unwrap().get_by_role("Researcher").len(), 1);
+            assert_eq!(resumed.transcript().entries.len(), 2);
+        })
+        .await;
+    }
+}
@@ -0,0 +1,437 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::watch;
+use tracing::warn;
+
+use agent_schema::Message;
+
+/// Default number of messages kept for subscribers that have not read them yet.
+pub const DEFAULT_BROKER_CAPACITY: usize = 64;
+
+/// What a subscriber receives: messages addressed to `profile` with `send_to`, or,
+/// without recipients, the ones whose `cause_by` is in `watch`. Never its own messages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Topic {
+    pub profile: String,
+    pub watch: HashSet<String>,
+}
+
+impl Topic {
+    pub fn new(profile: &str, watch: HashSet<String>) -> Self {
+        Self { profile: profile.to_string(), watch }
+    }
+
+    pub fn matches(&self, message: &Message) -> bool {
+        message.role != self.profile && message.is_delivered_to(&self.profile, &self.watch)
+    }
+}
+
+#[derive(Debug)]
+struct Cursor {
+    topic: Topic,
+    /// Sequence number of the next message to read.
+    position: usize,
+    /// Blocked in `recv` with nothing to read.
+    waiting: bool,
+    /// The message its role waits to publish, it reads nothing until that one is out.
+    publishing: Option<Message>,
+    /// Stopped reading, it no longer holds messages back.
+    closed: bool,
+    /// Closed while messages of its topic were unread, or got some since.
+    starved: bool,
+}
+
+#[derive(Debug, Default)]
+struct BrokerState {
+    /// Sequence number of `log[0]`.
+    offset: usize,
+    log: VecDeque<Message>,
+    cursors: HashMap<usize, Cursor>,
+    next_id: usize,
+}
+
+impl BrokerState {
+    fn end(&self) -> usize {
+        self.offset + self.log.len()
+    }
+
+    fn pending(&self, cursor: &Cursor) -> usize {
+        self.log.iter().skip(cursor.position - self.offset).filter(|msg| cursor.topic.matches(msg)).count()
+    }
+
+    /// The subscribers other than the author of `message` with `capacity` messages of their
+    /// topic to read. The author's own cursor never counts: it does not read while publishing,
+    /// and waiting for it would never end.
+    fn full<'a>(&'a self, message: &'a Message, capacity: usize) -> impl Iterator<Item = &'a Cursor> + 'a {
+        self.cursors
+            .values()
+            .filter(move |cursor| !cursor.closed && cursor.topic.profile != message.role)
+            .filter(move |cursor| self.pending(cursor) >= capacity)
+    }
+
+    fn is_full(&self, message: &Message, capacity: usize) -> bool {
+        self.full(message, capacity).next().is_some()
+    }
+
+    fn set_publishing(&mut self, profile: &str, publishing: Option<&Message>) {
+        for cursor in self.cursors.values_mut().filter(|cursor| cursor.topic.profile == profile) {
+            cursor.publishing = publishing.cloned();
+        }
+    }
+
+    /// Whether `cursor` will not read before its role publishes, which waits on subscribers
+    /// that are stuck as well or on one of the `waiting` roles, so would wait forever.
+    fn is_stuck(&self, cursor: &Cursor, capacity: usize, waiting: &mut HashSet<String>) -> bool {
+        let Some(message) = &cursor.publishing else { return false };
+        waiting.insert(cursor.topic.profile.clone());
+        let blockers: Vec<&Cursor> = self.full(message, capacity).collect();
+        !blockers.is_empty()
+            && blockers.into_iter().all(|blocker| waiting.contains(&blocker.topic.profile) || self.is_stuck(blocker, capacity, waiting))
+    }
+
+    /// Drop the messages every open subscriber has read. Without subscribers the log is
+    /// kept whole for the roles still to come.
+    fn trim(&mut self) {
+        let Some(oldest) = self.cursors.values().filter(|c| !c.closed).map(|c| c.position).min() else {
+            return;
+        };
+        while self.offset < oldest {
+            self.log.pop_front();
+            self.offset += 1;
+        }
+    }
+}
+
+/// Whether the subscribers still have work, see `MessageBroker::status`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BrokerStatus {
+    /// A subscriber is handling messages or has some to read.
+    Busy,
+    /// Every open subscriber waits for input, `starved` when a closed one left messages unread.
+    Idle { starved: bool },
+}
+
+/// Publish/subscribe bus between the roles of an environment.
+///
+/// Every subscriber reads the log from its own cursor, so each message is delivered once
+/// per matching subscriber. At most `capacity` messages of its topic wait for each open
+/// subscriber: past that, `publish` waits until it catches up. The publisher's own
+/// subscription is not waited for, nor those of roles waiting in `publish` on each other:
+/// two roles full of each other's messages would wait forever, the message goes over capacity.
+#[derive(Debug)]
+pub struct MessageBroker {
+    state: Mutex<BrokerState>,
+    /// Bumped on every change, wakes the tasks waiting on the broker.
+    version: watch::Sender<u64>,
+    capacity: usize,
+}
+
+impl Default for MessageBroker {
+    fn default() -> Self {
+        Self::new(DEFAULT_BROKER_CAPACITY)
+    }
+}
+
+impl MessageBroker {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(BrokerState::default()),
+            version: watch::channel(0).0,
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Subscribe to `topic`, starting from the oldest message still in the log.
+    pub fn subscribe(self: &Arc<Self>, topic: Topic) -> Subscription {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        let position = state.offset;
+        state.cursors.insert(id, Cursor { topic, position, waiting: false, publishing: None, closed: false, starved: false });
+        Subscription { id, broker: self.clone() }
+    }
+
+    /// Append a message, waiting while a subscriber has `capacity` messages to read, unless
+    /// all of those are stuck publishing themselves.
+    pub async fn publish(&self, message: Message) {
+        let mut registered = false;
+        loop {
+            let mut changed = {
+                let mut state = self.state.lock().unwrap();
+                let blockers: Vec<&Cursor> = state.full(&message, self.capacity).collect();
+                let mut waiting = HashSet::from([message.role.clone()]);
+                let deadlocked = !blockers.is_empty() && blockers.iter().all(|cursor| state.is_stuck(cursor, self.capacity, &mut waiting));
+                if deadlocked {
+                    warn!("the subscribers {} waits for are waiting to publish as well, going over capacity", message.role);
+                }
+                if blockers.is_empty() || deadlocked {
+                    state.set_publishing(&message.role, None);
+                    self.push(&mut state, message);
+                    return;
+                }
+                if !registered {
+                    registered = true;
+                    state.set_publishing(&message.role, Some(&message));
+                    // the roles waiting on this one may be stuck now
+                    self.notify();
+                }
+                self.version.subscribe()
+            };
+            let _ = changed.changed().await;
+        }
+    }
+
+    /// Append a message unless a subscriber has `capacity` messages to read, in which case
+    /// it is handed back.
+    pub fn try_publish(&self, message: Message) -> Result<(), Box<Message>> {
+        let mut state = self.state.lock().unwrap();
+        if state.is_full(&message, self.capacity) {
+            return Err(Box::new(message));
+        }
+        self.push(&mut state, message);
+        Ok(())
+    }
+
+    /// Append a message whatever the capacity, for input injected from outside the roles.
+    pub fn publish_now(&self, message: Message) {
+        let mut state = self.state.lock().unwrap();
+        self.push(&mut state, message);
+    }
+
+    /// The current status and a receiver notified of the next change, taken together so
+    /// that no change is missed in between.
+    pub fn status(&self) -> (BrokerStatus, watch::Receiver<u64>) {
+        let state = self.state.lock().unwrap();
+        let mut starved = false;
+        let mut busy = false;
+        for cursor in state.cursors.values() {
+            if cursor.closed {
+                starved |= cursor.starved;
+            } else if !cursor.waiting || state.pending(cursor) > 0 {
+                busy = true;
+            }
+        }
+        let status = if busy { BrokerStatus::Busy } else { BrokerStatus::Idle { starved } };
+        (status, self.version.subscribe())
+    }
+
+    fn push(&self, state: &mut BrokerState, message: Message) {
+        for cursor in state.cursors.values_mut().filter(|cursor| cursor.closed) {
+            cursor.starved |= cursor.topic.matches(&message);
+        }
+        state.log.push_back(message);
+        self.version.send_modify(|version| *version += 1);
+    }
+
+    fn notify(&self) {
+        self.version.send_modify(|version| *version += 1);
+    }
+}
+
+/// A subscriber's view of the broker, see `MessageBroker::subscribe`.
+#[derive(Debug)]
+pub struct Subscription {
+    id: usize,
+    broker: Arc<MessageBroker>,
+}
+
+impl Subscription {
+    /// Wait for messages of the topic and return all of them at once, `None` once closed.
+    pub async fn recv(&self) -> Option<Vec<Message>> {
+        loop {
+            let mut changed = {
+                let mut state = self.broker.state.lock().unwrap();
+                let logged = state.log.len();
+                let messages = self._drain(&mut state)?;
+                let cursor = state.cursors.get_mut(&self.id)?;
+                let was_waiting = std::mem::replace(&mut cursor.waiting, messages.is_empty());
+                // only wake the others when something they wait on changed: a new waiter for
+                // the supervisor, room in the log for the publishers
+                if !was_waiting || !messages.is_empty() || state.log.len() < logged {
+                    self.broker.notify();
+                }
+                if !messages.is_empty() {
+                    return Some(messages);
+                }
+                self.broker.version.subscribe()
+            };
+            let _ = changed.changed().await;
+        }
+    }
+
+    /// The messages of the topic published since the last read, without waiting.
+    pub fn try_recv(&self) -> Vec<Message> {
+        let mut state = self.broker.state.lock().unwrap();
+        let logged = state.log.len();
+        let messages = self._drain(&mut state).unwrap_or_default();
+        if !messages.is_empty() || state.log.len() < logged {
+            self.broker.notify();
+        }
+        messages
+    }
+
+    /// Number of messages of the topic waiting to be read.
+    pub fn pending(&self) -> usize {
+        let state = self.broker.state.lock().unwrap();
+        state.cursors.get(&self.id).filter(|cursor| !cursor.closed).map_or(0, |cursor| state.pending(cursor))
+    }
+
+    /// Stop reading: the subscription no longer holds messages back and `recv` returns `None`.
+    pub fn close(&self) {
+        let mut state = self.broker.state.lock().unwrap();
+        let end = state.end();
+        let starved = state.cursors.get(&self.id).filter(|cursor| !cursor.closed).map_or(0, |cursor| state.pending(cursor)) > 0;
+        if let Some(cursor) = state.cursors.get_mut(&self.id) {
+            cursor.starved |= starved;
+            cursor.closed = true;
+            cursor.waiting = false;
+            cursor.position = end;
+        }
+        state.trim();
+        self.broker.notify();
+    }
+
+    /// Read again after `close`, from the next published message.
+    pub fn reopen(&self) {
+        let mut state = self.broker.state.lock().unwrap();
+        let end = state.end();
+        if let Some(cursor) = state.cursors.get_mut(&self.id).filter(|cursor| cursor.closed) {
+            cursor.closed = false;
+            cursor.starved = false;
+            cursor.position = end;
+        }
+    }
+
+    /// Move the cursor to the end of the log and return the matching messages passed over.
+    fn _drain(&self, state: &mut BrokerState) -> Option<Vec<Message>> {
+        let end = state.end();
+        let offset = state.offset;
+        let cursor = state.cursors.get(&self.id).filter(|cursor| !cursor.closed)?;
+        let messages: Vec<Message> = state
+            .log
+            .iter()
+            .skip(cursor.position - offset)
+            .filter(|msg| cursor.topic.matches(msg))
+            .cloned()
+            .collect();
+        state.cursors.get_mut(&self.id)?.position = end;
+        state.trim();
+        Some(messages)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut state = self.broker.state.lock().unwrap();
+        state.cursors.remove(&self.id);
+        state.trim();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(cause_by: &str) -> Message {
+        Message { content: cause_by.into(), role: "BOSS".into(), cause_by: cause_by.into(), ..Default::default() }
+    }
+
+    /// A message of `role`, which watches the messages caused by the other role.
+    fn reply(role: &str) -> Message {
+        Message { content: role.into(), role: role.into(), cause_by: role.into(), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions() {
+        let broker = Arc::new(MessageBroker::new(2));
+        let pm = broker.subscribe(Topic::new("Product Manager", HashSet::from(["BossRequirement".to_string()])));
+        let architect = broker.subscribe(Topic::new("Architect", HashSet::from(["WritePRD".to_string()])));
+
+        broker.publish(message("WritePRD")).await;
+        broker.publish(message("BossRequirement")).await;
+        broker.publish(message("WritePRD")).await;
+        // the architect has two messages to read
+        assert!(broker.try_publish(message("WriteDesign")).is_err());
+
+        assert_eq!(pm.pending(), 1);
+        assert_eq!(pm.recv().await.unwrap()[0].cause_by, "BossRequirement");
+        assert!(pm.try_recv().is_empty());
+        assert_eq!(broker.status().0, BrokerStatus::Busy);
+        assert!(broker.try_publish(message("WriteDesign")).is_err());
+
+        assert_eq!(architect.try_recv().len(), 2);
+        assert!(broker.try_publish(message("WriteDesign")).is_ok());
+
+        architect.close();
+        assert!(architect.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_recv_waits_for_publish() {
+        let broker = Arc::new(MessageBroker::default());
+        let pm = broker.subscribe(Topic::new("Product Manager", HashSet::from(["BossRequirement".to_string()])));
+        let publisher = async {
+            tokio::task::yield_now().await;
+            assert_eq!(broker.status().0, BrokerStatus::Idle { starved: false });
+            broker.publish(message("BossRequirement")).await;
+        };
+        let (received, _) = tokio::join!(pm.recv(), publisher);
+        assert_eq!(received.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_roles_feeding_each_other() {
+        let broker = Arc::new(MessageBroker::new(1));
+        let ping = broker.subscribe(Topic::new("Ping", HashSet::from(["Pong".to_string()])));
+        let pong = broker.subscribe(Topic::new("Pong", HashSet::from(["Ping".to_string()])));
+        broker.publish_now(Message { role: "BOSS".into(), cause_by: "Pong".into(), ..Default::default() });
+
+        // every turn publishes two messages: the second one waits for the other role to
+        // read the first, never for the publisher's own cursor
+        let ping_turns = async {
+            ping.recv().await.unwrap();
+            broker.publish(reply("Ping")).await;
+            broker.publish(reply("Ping")).await;
+            ping.recv().await.unwrap()
+        };
+        let pong_turns = async {
+            pong.recv().await.unwrap();
+            broker.publish(reply("Pong")).await;
+            broker.publish(reply("Pong")).await;
+        };
+        let (received, ()) = tokio::time::timeout(std::time::Duration::from_secs(5), async { tokio::join!(ping_turns, pong_turns) })
+            .await
+            .expect("the roles waited for each other");
+        assert_eq!(received[0].role, "Pong");
+
+        // a full subscriber hands the message back
+        assert_eq!(broker.try_publish(reply("Pong")).unwrap_err().role, "Pong");
+    }
+
+    #[tokio::test]
+    async fn test_roles_full_of_each_other() {
+        let broker = Arc::new(MessageBroker::new(1));
+        let ping = broker.subscribe(Topic::new("Ping", HashSet::from(["Pong".to_string()])));
+        let pong = broker.subscribe(Topic::new("Pong", HashSet::from(["Ping".to_string()])));
+
+        // both publish twice before reading: each one's second message waits for the other,
+        // which waits to publish as well, so one of them goes over capacity
+        let ping_turn = async {
+            broker.publish(reply("Ping")).await;
+            tokio::task::yield_now().await;
+            broker.publish(reply("Ping")).await;
+            ping.recv().await.unwrap()
+        };
+        let pong_turn = async {
+            broker.publish(reply("Pong")).await;
+            tokio::task::yield_now().await;
+            broker.publish(reply("Pong")).await;
+            pong.recv().await.unwrap()
+        };
+        let (to_ping, to_pong) = tokio::time::timeout(std::time::Duration::from_secs(5), async { tokio::join!(ping_turn, pong_turn) })
+            .await
+            .expect("the roles waited for each other");
+        assert_eq!(to_ping.len() + to_pong.len() + ping.try_recv().len() + pong.try_recv().len(), 4);
+    }
+}
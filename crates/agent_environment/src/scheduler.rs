@@ -0,0 +1,25 @@
+use std::fmt;
+
+/// Why `Environment::run` returned. `rounds` is the most turns taken by a single role.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopReason {
+    /// No role had a new message to react to.
+    Quiescent { rounds: usize },
+    /// A message caused by the goal action was published.
+    GoalMet { goal: String, rounds: usize },
+    /// The deliverables passed the acceptance checklist.
+    Accepted { rounds: usize },
+    /// A role used all its turns while messages were still coming for it.
+    MaxRounds(usize),
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Quiescent { rounds } => write!(f, "no role has new input after {} rounds", rounds),
+            StopReason::GoalMet { goal, rounds } => write!(f, "goal {} met after {} rounds", goal, rounds),
+            StopReason::Accepted { rounds } => write!(f, "deliverables accepted after {} rounds", rounds),
+            StopReason::MaxRounds(rounds) => write!(f, "stopped at the limit of {} rounds with messages still pending", rounds),
+        }
+    }
+}
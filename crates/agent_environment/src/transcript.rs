@@ -0,0 +1,149 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use agent_schema::Message;
+
+/// A published message with when, in which turn and from which prompts it was produced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    /// Turn of the publishing role, 0 for messages published from outside the roles.
+    pub round: usize,
+    pub started_at: DateTime<Utc>,
+    pub published_at: DateTime<Utc>,
+    pub message: Message,
+    /// Prompts sent to the LLM during the turn, in order.
+    pub prompts: Vec<String>,
+}
+
+/// The files written by `Transcript::export`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptFiles {
+    pub jsonl: PathBuf,
+    pub markdown: PathBuf,
+    pub html: PathBuf,
+}
+
+/// Every message published during a run, in publishing order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Transcript {
+    pub entries: Vec<TranscriptEntry>,
+}
+
+impl Transcript {
+    /// One JSON entry per line.
+    pub fn to_jsonl(&self) -> String {
+        self.entries
+            .iter()
+            .filter_map(|entry| serde_json::to_string(entry).ok())
+            .map(|line| line + "\n")
+            .collect()
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::from("# Run transcript\n");
+        for (idx, entry) in self.entries.iter().enumerate() {
+            let msg = &entry.message;
+            md.push_str(&format!("\n## {}. {} · {}\n\n", idx + 1, msg.role, msg.cause_by));
+            md.push_str(&format!("- round: {}\n", entry.round));
+            md.push_str(&format!("- published: {}\n", entry.published_at.to_rfc3339()));
+            md.push_str(&format!("- duration: {:.1}s\n", seconds(entry)));
+            if !msg.send_to.is_empty() {
+                md.push_str(&format!("- to: {}\n", msg.send_to.join(", ")));
+            }
+            md.push_str(&format!("\n{}\n", msg.content.trim()));
+            for (n, prompt) in entry.prompts.iter().enumerate() {
+                md.push_str(&format!("\n<details><summary>Prompt {}</summary>\n\n````\n{}\n````\n</details>\n", n + 1, prompt.trim()));
+            }
+        }
+        md
+    }
+
+    /// A single HTML page with no external resources, prompts folded by default.
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Run transcript</title>\n<style>\n\
+body { font-family: sans-serif; max-width: 960px; margin: 2em auto; color: #222; }\n\
+section { border: 1px solid #ddd; border-radius: 6px; padding: 0.5em 1em; margin: 1em 0; }\n\
+.meta { color: #777; font-size: 0.9em; }\n\
+pre { white-space: pre-wrap; background: #f6f8fa; padding: 0.8em; border-radius: 4px; }\n\
+summary { cursor: pointer; color: #0366d6; }\n\
+</style>\n</head>\n<body>\n<h1>Run transcript</h1>\n",
+        );
+        for (idx, entry) in self.entries.iter().enumerate() {
+            let msg = &entry.message;
+            html.push_str("<section>\n");
+            html.push_str(&format!("<h2>{}. {} · {}</h2>\n", idx + 1, escape(&msg.role), escape(&msg.cause_by)));
+            html.push_str(&format!(
+                "<p class=\"meta\">round {} · published {} · {:.1}s",
+                entry.round,
+                entry.published_at.to_rfc3339(),
+                seconds(entry)
+            ));
+            if !msg.send_to.is_empty() {
+                html.push_str(&format!(" · to {}", escape(&msg.send_to.join(", "))));
+            }
+            html.push_str("</p>\n");
+            html.push_str(&format!("<pre>{}</pre>\n", escape(msg.content.trim())));
+            for (n, prompt) in entry.prompts.iter().enumerate() {
+                html.push_str(&format!("<details><summary>Prompt {}</summary><pre>{}</pre></details>\n", n + 1, escape(prompt.trim())));
+            }
+            html.push_str("</section>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    /// Write `transcript.jsonl`, `transcript.md` and `transcript.html` to `dir`.
+    pub fn export(&self, dir: &Path) -> anyhow::Result<TranscriptFiles> {
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        let files = TranscriptFiles {
+            jsonl: dir.join("transcript.jsonl"),
+            markdown: dir.join("transcript.md"),
+            html: dir.join("transcript.html"),
+        };
+        fs::write(&files.jsonl, self.to_jsonl())?;
+        fs::write(&files.markdown, self.to_markdown())?;
+        fs::write(&files.html, self.to_html())?;
+        Ok(files)
+    }
+}
+
+fn seconds(entry: &TranscriptEntry) -> f64 {
+    (entry.published_at - entry.started_at).num_milliseconds() as f64 / 1000.0
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcript_formats() {
+        let now = Utc::now();
+        let transcript = Transcript {
+            entries: vec![TranscriptEntry {
+                round: 1,
+                started_at: now,
+                published_at: now,
+                message: Message { content: "<b>PRD</b>".into(), role: "Product Manager".into(), cause_by: "WritePRD".into(), ..Default::default() },
+                prompts: vec!["write a PRD".into()],
+            }],
+        };
+        let jsonl = transcript.to_jsonl();
+        assert_eq!(jsonl.lines().count(), 1);
+        let entry: TranscriptEntry = serde_json::from_str(jsonl.trim()).unwrap();
+        assert_eq!(entry, transcript.entries[0]);
+
+        assert!(transcript.to_markdown().contains("## 1. Product Manager · WritePRD"));
+        let html = transcript.to_html();
+        assert!(html.contains("&lt;b&gt;PRD&lt;/b&gt;"));
+        assert!(html.contains("<details><summary>Prompt 1</summary><pre>write a PRD</pre></details>"));
+    }
+}
@@ -30,7 +30,16 @@ fn impl_action_macro(ast: &syn::DeriveInput) -> TokenStream {
             fn get_prefix(&self) -> &str{
                 &self.prefix
             }
+            fn set_llm(&mut self, llm: Box<dyn LLMBase>) {
+                self._llm = llm;
+            }
+            fn set_workspace(&mut self, workspace: &std::path::Path) {
+                self.workspace = workspace.to_path_buf();
+            }
             async fn aask(&self, prompt: &str) -> String{
+                if agent_provider::is_llm_fake() {
+                    return String::new();
+                }
                 self._llm.aask(prompt.into()).await
             }
             /// 这里接收的是 所有信息，但是不是所有行为都会用到
@@ -42,7 +51,9 @@ fn impl_action_macro(ast: &syn::DeriveInput) -> TokenStream {
                 debug!("{:?}", self);
                 info!("【{} Prompt】: \n {}", stringify!(#name), &prompt);
                 // 测试数据
-                if std::env::var("LLM_FAKE").is_ok() && std::env::var("LLM_FAKE").unwrap() == "true"  {
+                if agent_provider::is_llm_fake() {
+                    // the LLM is skipped, record the prompt it would have received
+                    agent_provider::record_prompt(&prompt);
                     // return PROMPT_TEMPLATE_RESPONSE_SAMPLE_FULL.into();
                     return self._post_processing(msgs, PROMPT_TEMPLATE_RESPONSE_SAMPLE_FULL.into()).await;
                 }
@@ -74,6 +85,11 @@ fn impl_role_macro(ast: &syn::DeriveInput) -> TokenStream {
             fn set_env_global_memory(&mut self, memory: Arc<Mutex<Memory>>) {
                 self._rc.env_memory = memory
             }
+
+            fn set_ask_human(&mut self, ask_human: Arc<dyn crate::AskHuman>, actions: HashSet<String>) {
+                self._rc.ask_human = Some(ask_human);
+                self._rc.human_gates = actions;
+            }
             
             fn _reset(&mut self) {
                 self._states = vec![];
@@ -82,8 +98,9 @@ fn impl_role_macro(ast: &syn::DeriveInput) -> TokenStream {
 
             fn _init_actions(&mut self, actions: Vec<Box<dyn Action>>) {
                 self._reset();
-                for mut action in actions {
+                for (idx, mut action) in actions.into_iter().enumerate() {
                     action.set_prefix(&self._setting.get_prefix(), &self._setting.profile);
+                    self._states.push(format!("{}. {}", idx, action.name()));
                     self._actions.push(action);
                 }
             }
@@ -92,8 +109,19 @@ fn impl_role_macro(ast: &syn::DeriveInput) -> TokenStream {
 
             }
             fn _set_state(&mut self, state: i32) {
-                // let mut _rc = self._get_role_context();
-                // _rc.state = state;
+                self._rc.set_state(state);
+            }
+
+            async fn _aask(&self, prompt: &str) -> String {
+                if agent_provider::is_llm_fake() {
+                    return String::new();
+                }
+                // clone the client so the lock is not held across the request
+                let llm = { self._llm.lock().unwrap().clone() };
+                llm.aask(prompt).await.unwrap_or_else(|e| {
+                    tracing::warn!("【{}】LLM request failed: {}", self._setting.profile, e);
+                    String::new()
+                })
             }
             fn _get_profile(&self) -> &str {
                 &self._setting.profile
@@ -109,6 +137,17 @@ fn impl_role_macro(ast: &syn::DeriveInput) -> TokenStream {
             fn _get_rc(&self) -> RoleContext {
                 self._rc.clone()
             }
+            fn _get_setting(&self) -> &RoleSetting {
+                &self._setting
+            }
+            fn set_reflection(&mut self, max_rounds: usize) {
+                self._rc.reflection_rounds = max_rounds;
+            }
+            fn set_workspace(&mut self, workspace: &std::path::Path) {
+                for action in self._actions.iter_mut() {
+                    action.set_workspace(workspace);
+                }
+            }
             fn _get_rc_env_memory(&self) -> MutexGuard<'_, Memory> {
                 // 获取可变引用并锁定 Mutex
                 debug!("_get_rc_env_memory, have {:?} messages", self._rc.env_memory.lock().unwrap().count());
@@ -118,8 +157,7 @@ fn impl_role_macro(ast: &syn::DeriveInput) -> TokenStream {
                 self._rc.role_memory.lock().unwrap()
             }
             fn _get_action_by_state(&self, state: usize) -> Option<&Box<dyn Action>> {
-                let Some(action) = self._actions.get(state) else { return None };
-                Some(action)
+                self._actions.get(state)
             }
 
             fn _get_action_count(&self) -> usize {
@@ -0,0 +1,310 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::Context;
+use rusqlite::{params, Connection};
+use tracing::warn;
+
+use agent_schema::Message;
+
+use crate::storage::{KVStorage, LocalJsonStorage};
+
+/// Whose messages are stored: one project, one role of it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MemoryNamespace {
+    pub project: String,
+    pub role: String,
+}
+
+impl MemoryNamespace {
+    pub fn new(project: &str, role: &str) -> Self {
+        Self { project: project.to_string(), role: role.to_string() }
+    }
+
+    /// `project/role`, the key of the namespace in the backends.
+    pub fn key(&self) -> String {
+        format!("{}/{}", self.project, self.role)
+    }
+}
+
+impl fmt::Display for MemoryNamespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.key())
+    }
+}
+
+/// Where a `Memory` keeps its messages beyond the process, see `Memory::attach`.
+pub trait MemoryBackend: fmt::Debug + Send + Sync {
+    /// The messages of `namespace`, oldest first.
+    fn load(&self, namespace: &MemoryNamespace) -> anyhow::Result<Vec<Message>>;
+    /// Store messages after the others, skipping the ids already stored.
+    fn append(&self, namespace: &MemoryNamespace, messages: &[Message]) -> anyhow::Result<()>;
+    fn remove(&self, namespace: &MemoryNamespace, id: &str) -> anyhow::Result<()>;
+    fn clear(&self, namespace: &MemoryNamespace) -> anyhow::Result<()>;
+    /// Write the messages the backend buffers, see `JsonMemoryBackend`.
+    fn flush(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Keeps the messages for the life of the process, e.g. to share them between runs
+/// of the same company or in tests.
+#[derive(Debug, Default)]
+pub struct InMemoryBackend {
+    namespaces: Mutex<HashMap<MemoryNamespace, Vec<Message>>>,
+}
+
+impl MemoryBackend for InMemoryBackend {
+    fn load(&self, namespace: &MemoryNamespace) -> anyhow::Result<Vec<Message>> {
+        Ok(self.namespaces.lock().unwrap().get(namespace).cloned().unwrap_or_default())
+    }
+
+    fn append(&self, namespace: &MemoryNamespace, messages: &[Message]) -> anyhow::Result<()> {
+        let mut namespaces = self.namespaces.lock().unwrap();
+        let stored = namespaces.entry(namespace.clone()).or_default();
+        for message in messages {
+            if !stored.iter().any(|m| m.id == message.id) {
+                stored.push(message.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn remove(&self, namespace: &MemoryNamespace, id: &str) -> anyhow::Result<()> {
+        if let Some(stored) = self.namespaces.lock().unwrap().get_mut(namespace) {
+            stored.retain(|m| m.id != id);
+        }
+        Ok(())
+    }
+
+    fn clear(&self, namespace: &MemoryNamespace) -> anyhow::Result<()> {
+        self.namespaces.lock().unwrap().remove(namespace);
+        Ok(())
+    }
+}
+
+/// Messages `JsonMemoryBackend` buffers before writing them.
+const JSON_BATCH_SIZE: usize = 64;
+
+/// One JSON file holding every namespace through `LocalJsonStorage`, the project being
+/// the storage namespace and the role the key.
+///
+/// Every write rewrites the whole file, so appended messages are buffered and written
+/// together: every `JSON_BATCH_SIZE` messages, on `flush`, before any other access and
+/// when the backend is dropped.
+#[derive(Debug)]
+pub struct JsonMemoryBackend {
+    storage: Mutex<LocalJsonStorage>,
+    /// Appended messages not written yet, locked after `storage`.
+    pending: Mutex<HashMap<MemoryNamespace, Vec<Message>>>,
+}
+
+impl JsonMemoryBackend {
+    /// Open the file at `path`, creating it when missing.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let storage = if path.exists() {
+            LocalJsonStorage::new(path.to_path_buf())
+        } else {
+            LocalJsonStorage::create(path.to_path_buf(), None, false)
+        }
+        .with_context(|| format!("failed to open the memory file {}", path.display()))?;
+        Ok(Self { storage: Mutex::new(storage), pending: Mutex::new(HashMap::new()) })
+    }
+
+    /// The messages stored when the file was last read.
+    fn _stored(storage: &mut LocalJsonStorage, namespace: &MemoryNamespace) -> anyhow::Result<Vec<Message>> {
+        match storage.namespace(&namespace.project).query(&namespace.role)? {
+            Some(value) => serde_json::from_value(value).with_context(|| format!("invalid memory of {}", namespace)),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// The stored messages, as written by any process.
+    fn _messages(storage: &mut LocalJsonStorage, namespace: &MemoryNamespace) -> anyhow::Result<Vec<Message>> {
+        storage.reload()?;
+        Self::_stored(storage, namespace)
+    }
+
+    fn _store(storage: &mut LocalJsonStorage, namespace: &MemoryNamespace, messages: &[Message]) -> anyhow::Result<()> {
+        storage.namespace(&namespace.project).update(&namespace.role, serde_json::to_value(messages)?)
+    }
+
+    /// Add the pending messages after the stored ones in a single write.
+    fn _write_pending(storage: &mut LocalJsonStorage, pending: &HashMap<MemoryNamespace, Vec<Message>>) -> anyhow::Result<()> {
+        if pending.is_empty() {
+            return Ok(());
+        }
+        storage.reload()?;
+        let mut values = vec![];
+        for (namespace, messages) in pending {
+            let mut stored = Self::_stored(storage, namespace)?;
+            let mut ids: HashSet<String> = stored.iter().map(|m| m.id.clone()).collect();
+            stored.extend(messages.iter().filter(|m| ids.insert(m.id.clone())).cloned());
+            values.push((namespace, serde_json::to_value(stored)?));
+        }
+        let mut transaction = storage.transaction();
+        for (namespace, value) in values {
+            transaction.put(&namespace.project, &namespace.role, value);
+        }
+        transaction.commit()
+    }
+}
+
+impl MemoryBackend for JsonMemoryBackend {
+    fn load(&self, namespace: &MemoryNamespace) -> anyhow::Result<Vec<Message>> {
+        self.flush()?;
+        Self::_messages(&mut self.storage.lock().unwrap(), namespace)
+    }
+
+    fn append(&self, namespace: &MemoryNamespace, messages: &[Message]) -> anyhow::Result<()> {
+        let full = {
+            let mut pending = self.pending.lock().unwrap();
+            pending.entry(namespace.clone()).or_default().extend_from_slice(messages);
+            pending.values().map(Vec::len).sum::<usize>() >= JSON_BATCH_SIZE
+        };
+        if full {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, namespace: &MemoryNamespace, id: &str) -> anyhow::Result<()> {
+        self.flush()?;
+        let mut storage = self.storage.lock().unwrap();
+        let mut stored = Self::_messages(&mut storage, namespace)?;
+        stored.retain(|m| m.id != id);
+        Self::_store(&mut storage, namespace, &stored)
+    }
+
+    fn clear(&self, namespace: &MemoryNamespace) -> anyhow::Result<()> {
+        let mut storage = self.storage.lock().unwrap();
+        self.pending.lock().unwrap().remove(namespace);
+        storage.namespace(&namespace.project).remove(&namespace.role)
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        let mut storage = self.storage.lock().unwrap();
+        let mut pending = self.pending.lock().unwrap();
+        Self::_write_pending(&mut storage, &pending)?;
+        pending.clear();
+        Ok(())
+    }
+}
+
+impl Drop for JsonMemoryBackend {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            warn!("failed to write the memory file: {:#}", e);
+        }
+    }
+}
+
+/// A SQLite database with one row per message.
+#[derive(Debug)]
+pub struct SqliteMemoryBackend {
+    connection: Mutex<Connection>,
+    path: PathBuf,
+}
+
+impl SqliteMemoryBackend {
+    /// Open the database at `path`, creating it and its table when missing.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let connection = Connection::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS messages (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                namespace TEXT NOT NULL,
+                id TEXT NOT NULL,
+                message TEXT NOT NULL,
+                UNIQUE (namespace, id)
+            );",
+        )?;
+        Ok(Self { connection: Mutex::new(connection), path: path.to_path_buf() })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl MemoryBackend for SqliteMemoryBackend {
+    fn load(&self, namespace: &MemoryNamespace) -> anyhow::Result<Vec<Message>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare("SELECT message FROM messages WHERE namespace = ?1 ORDER BY seq")?;
+        let rows = statement.query_map(params![namespace.key()], |row| row.get::<_, String>(0))?;
+        rows.map(|json| Ok(serde_json::from_str(&json?)?)).collect()
+    }
+
+    fn append(&self, namespace: &MemoryNamespace, messages: &[Message]) -> anyhow::Result<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        for message in messages {
+            transaction.execute(
+                "INSERT OR IGNORE INTO messages (namespace, id, message) VALUES (?1, ?2, ?3)",
+                params![namespace.key(), message.id, serde_json::to_string(message)?],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn remove(&self, namespace: &MemoryNamespace, id: &str) -> anyhow::Result<()> {
+        let connection = self.connection.lock().unwrap();
+        connection.execute("DELETE FROM messages WHERE namespace = ?1 AND id = ?2", params![namespace.key(), id])?;
+        Ok(())
+    }
+
+    fn clear(&self, namespace: &MemoryNamespace) -> anyhow::Result<()> {
+        let connection = self.connection.lock().unwrap();
+        connection.execute("DELETE FROM messages WHERE namespace = ?1", params![namespace.key()])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_backend(backend: &dyn MemoryBackend) {
+        let architect = MemoryNamespace::new("snake", "Architect");
+        let engineer = MemoryNamespace::new("snake", "Engineer");
+        let prd = Message { content: "PRD".into(), cause_by: "WritePRD".into(), ..Default::default() };
+        let design = Message { content: "design".into(), cause_by: "WriteDesign".into(), ..Default::default() };
+
+        backend.append(&architect, &[prd.clone(), design.clone()]).unwrap();
+        backend.append(&architect, &[prd.clone()]).unwrap();
+        backend.append(&engineer, &[design.clone()]).unwrap();
+        assert_eq!(backend.load(&architect).unwrap(), vec![prd.clone(), design.clone()]);
+
+        backend.remove(&architect, &prd.id).unwrap();
+        assert_eq!(backend.load(&architect).unwrap(), vec![design.clone()]);
+        backend.clear(&architect).unwrap();
+        assert!(backend.load(&architect).unwrap().is_empty());
+        assert_eq!(backend.load(&engineer).unwrap(), vec![design]);
+    }
+
+    #[test]
+    fn test_memory_backends() {
+        let dir = tempfile::tempdir().unwrap();
+        check_backend(&InMemoryBackend::default());
+        check_backend(&JsonMemoryBackend::open(&dir.path().join("memory.json")).unwrap());
+
+        // the JSON file is written once for the whole batch
+        let path = dir.path().join("batch.json");
+        let backend = JsonMemoryBackend::open(&path).unwrap();
+        let namespace = MemoryNamespace::new("snake", "Engineer");
+        let messages: Vec<Message> = (0..3).map(|idx| Message { content: idx.to_string(), ..Default::default() }).collect();
+        for message in &messages {
+            backend.append(&namespace, std::slice::from_ref(message)).unwrap();
+        }
+        assert!(JsonMemoryBackend::open(&path).unwrap().load(&namespace).unwrap().is_empty());
+        backend.flush().unwrap();
+        assert_eq!(JsonMemoryBackend::open(&path).unwrap().load(&namespace).unwrap(), messages);
+        check_backend(&SqliteMemoryBackend::open(&dir.path().join("memory.db")).unwrap());
+    }
+}
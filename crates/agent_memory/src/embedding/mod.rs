@@ -0,0 +1,3 @@
+mod openai;
+
+pub use openai::{OpenAIEmbedder, DEFAULT_EMBEDDING_MODEL};
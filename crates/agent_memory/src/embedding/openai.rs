@@ -0,0 +1,59 @@
+use async_openai::config::OpenAIConfig;
+use async_openai::types::CreateEmbeddingRequestArgs;
+use async_openai::Client;
+use async_trait::async_trait;
+use agent_provider::{is_llm_fake, openai_config};
+
+use crate::semantic::{Embedder, HashEmbedder};
+
+/// Model of `OpenAIEmbedder::default`.
+pub const DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-ada-002";
+
+/// Embedder calling the embeddings endpoint of the OpenAI compatible provider, see
+/// `agent_provider::set_provider`, so that texts about the same things match without sharing words.
+/// With the LLM faked, see `agent_provider::is_llm_fake`, the texts are hashed locally like
+/// `HashEmbedder` does.
+#[derive(Debug, Clone)]
+pub struct OpenAIEmbedder {
+    client: Client<OpenAIConfig>,
+    model: String,
+    dimensions: usize,
+}
+
+impl OpenAIEmbedder {
+    pub fn new(model: &str) -> Self {
+        Self { client: Client::with_config(openai_config()), model: model.to_string(), dimensions: dimensions(model) }
+    }
+}
+
+impl Default for OpenAIEmbedder {
+    fn default() -> Self {
+        Self::new(DEFAULT_EMBEDDING_MODEL)
+    }
+}
+
+/// Dimensions of the vectors of `model`: those of ada and `text-embedding-3-small` but for
+/// `text-embedding-3-large`.
+fn dimensions(model: &str) -> usize {
+    match model {
+        "text-embedding-3-large" => 3072,
+        _ => 1536,
+    }
+}
+
+#[async_trait]
+impl Embedder for OpenAIEmbedder {
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    async fn embed(&self, text: &str) -> anyhow::Result<Vec<f32>> {
+        if is_llm_fake() {
+            return HashEmbedder::new(self.dimensions).embed(text).await;
+        }
+        let request = CreateEmbeddingRequestArgs::default().model(&self.model).input(text).build()?;
+        let response = self.client.embeddings().create(request).await?;
+        let embedding = response.data.into_iter().next().ok_or_else(|| anyhow::anyhow!("{} returned no embedding", self.model))?;
+        Ok(embedding.embedding)
+    }
+}
@@ -1,44 +1,97 @@
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
 
 use agent_schema::Message;
+use chrono::{DateTime, Utc};
+use tracing::warn;
 
+mod backend;
+mod embedding;
 mod memory_provider;
+mod semantic;
+pub mod storage;
 
-// TODO 需要优化储存方式
-#[derive(Debug)]
-pub struct Memory {
-    pub storage: Vec<Message>,
-    index: HashMap<String, Vec<Message>>, 
-}
+pub use backend::{InMemoryBackend, JsonMemoryBackend, MemoryBackend, MemoryNamespace, SqliteMemoryBackend};
+pub use embedding::{OpenAIEmbedder, DEFAULT_EMBEDDING_MODEL};
+#[cfg(feature = "qdrant")]
+pub use semantic::QdrantVectorIndex;
+pub use semantic::{
+    Embedder, HashEmbedder, LocalVectorIndex, MemoryFilter, SemanticIndex, VectorIndex, DEFAULT_EMBEDDING_DIMENSIONS,
+};
 
-impl Default for Memory {
-    fn default() -> Self {
-        Self::new()
-    }
+/// Position in a `Memory`, see `Memory::cursor` and `Memory::since`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MemoryCursor(usize);
+
+/// Messages in insertion order, indexed by id, role, `cause_by` and timestamp.
+///
+/// Every message keeps the sequence number it was added with. Deleted messages leave
+/// an empty slot, so cursors taken before a deletion stay valid.
+///
+/// Attached to a `MemoryBackend`, every change is written through to it. With a
+/// `SemanticIndex`, messages are embedded as they are added and `remember` ranks them by
+/// relevance to a query.
+#[derive(Debug, Default)]
+pub struct Memory {
+    storage: Vec<Option<Message>>,
+    by_id: HashMap<String, usize>,
+    by_role: HashMap<String, Vec<usize>>,
+    by_action: HashMap<String, Vec<usize>>,
+    by_time: BTreeSet<(DateTime<Utc>, usize)>,
+    backend: Option<(Arc<dyn MemoryBackend>, MemoryNamespace)>,
+    semantic: Option<SemanticIndex>,
 }
 
 impl Memory {
     pub fn new() -> Self {
-        Self {
-            storage: Vec::new(),
-            index: HashMap::new(),
+        Self::default()
+    }
+
+    /// Persist the memory in `namespace` of `backend`: the messages stored there by
+    /// earlier runs come first, followed by the ones already in memory.
+    pub fn attach(&mut self, backend: Arc<dyn MemoryBackend>, namespace: MemoryNamespace) -> anyhow::Result<()> {
+        let stored = backend.load(&namespace)?;
+        let current: Vec<Message> = self.iter().cloned().collect();
+        *self = Self { semantic: self.semantic.take(), ..Self::default() };
+        self.add_batch(stored);
+        self.backend = Some((backend, namespace));
+        self.add_batch(current);
+        Ok(())
+    }
+
+    /// Embed the messages in `semantic` from now on, starting with the ones already stored.
+    pub fn set_semantic_index(&mut self, semantic: SemanticIndex) {
+        for message in self.iter() {
+            semantic.insert(message);
         }
+        self.semantic = Some(semantic);
     }
 
+    /// The semantic index, to search it without holding the memory, see `remember`.
+    pub fn semantic_index(&self) -> Option<SemanticIndex> {
+        self.semantic.clone()
+    }
+
+    /// Add a message unless one with the same id, or an equal one, is already stored.
     pub fn add(&mut self, message: Message) {
-        // println!("memory add message{:?}", message);
-        if self.storage.contains(&message) {
+        if self.by_id.contains_key(&message.id) || self._contains_equal(&message) {
             return;
         }
-        self.storage.push(message.clone());
-        // self.index.insert(message.cause_by, message.clone());
-        // self.index.get(message.cause_by);
-        // if let Some(cause_by) =  {
-        self.index
-                .entry(message.cause_by.clone())
-                .or_insert_with(Vec::new)
-                .push(message);
+        if let Some((backend, namespace)) = &self.backend {
+            if let Err(e) = backend.append(namespace, std::slice::from_ref(&message)) {
+                warn!("failed to persist a message of {}: {:#}", namespace, e);
+            }
+        }
+        if let Some(semantic) = &self.semantic {
+            semantic.insert(&message);
+        }
+        let seq = self.storage.len();
+        self.by_id.insert(message.id.clone(), seq);
+        self.by_role.entry(message.role.clone()).or_default().push(seq);
+        self.by_action.entry(message.cause_by.clone()).or_default().push(seq);
+        self.by_time.insert((message.timestamp, seq));
+        self.storage.push(Some(message));
     }
 
     pub fn add_batch(&mut self, messages: Vec<Message>) {
@@ -47,84 +100,145 @@ impl Memory {
         }
     }
 
+    pub fn get_by_id(&self, id: &str) -> Option<&Message> {
+        self.by_id.get(id).and_then(|seq| self.storage[*seq].as_ref())
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.by_id.contains_key(id)
+    }
+
     pub fn get_by_role(&self, role: &str) -> Vec<&Message> {
-        self.storage
-            .iter()
-            .filter(|message| message.role == role)
-            .collect()
+        self._resolve(self.by_role.get(role).into_iter().flatten().copied())
+    }
+
+    pub fn get_by_action(&self, action: &str) -> Vec<&Message> {
+        self._resolve(self.by_action.get(action).into_iter().flatten().copied())
+    }
+
+    /// Messages caused by any of `actions`, in insertion order.
+    pub fn get_by_actions(&self, actions: HashSet<String>) -> Vec<&Message> {
+        let mut seqs: Vec<usize> = actions.iter().filter_map(|action| self.by_action.get(action)).flatten().copied().collect();
+        seqs.sort_unstable();
+        self._resolve(seqs.into_iter())
+    }
+
+    fn _contains_equal(&self, message: &Message) -> bool {
+        self.get_by_action(&message.cause_by).into_iter().any(|stored| stored == message)
     }
 
     pub fn get_by_content(&self, content: &str) -> Vec<&Message> {
-        self.storage
-            .iter()
-            .filter(|message| message.content.contains(content))
-            .collect()
+        self.iter().filter(|message| message.content.contains(content)).collect()
+    }
+
+    /// The cursor after the last message, to read what is added from now on with `since`.
+    pub fn cursor(&self) -> MemoryCursor {
+        MemoryCursor(self.storage.len())
+    }
+
+    /// Messages added after `cursor` was taken.
+    pub fn since(&self, cursor: MemoryCursor) -> Vec<&Message> {
+        self.storage.iter().skip(cursor.0).flatten().collect()
+    }
+
+    /// Messages timestamped in `from..to`, oldest first.
+    pub fn between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<&Message> {
+        if from >= to {
+            return vec![];
+        }
+        self._resolve(self.by_time.range((from, 0)..(to, 0)).map(|(_, seq)| *seq))
     }
 
     pub fn delete(&mut self, message: &Message) {
-        if let Some(index) = self.storage.iter().position(|m| m == message) {
-            self.storage.remove(index);
+        let Some(seq) = self.by_id.remove(&message.id) else { return };
+        if let Some((backend, namespace)) = &self.backend {
+            if let Err(e) = backend.remove(namespace, &message.id) {
+                warn!("failed to delete a message of {}: {:#}", namespace, e);
+            }
         }
-        // if let Some(cause_by) = &message.cause_by {
-        if let Some(index) = self.index.get_mut(&message.cause_by).and_then(|v| v.iter().position(|m| m == message)) {
-            self.index.get_mut(&message.cause_by).unwrap().remove(index);
+        if let Some(semantic) = &self.semantic {
+            semantic.remove(&message.id);
+        }
+        if let Some(message) = self.storage[seq].take() {
+            self.by_time.remove(&(message.timestamp, seq));
+            if let Some(seqs) = self.by_role.get_mut(&message.role) {
+                seqs.retain(|s| *s != seq);
+            }
+            if let Some(seqs) = self.by_action.get_mut(&message.cause_by) {
+                seqs.retain(|s| *s != seq);
+            }
         }
-        // }
     }
 
+    /// Forget every message, in the backend and the semantic index too.
     pub fn clear(&mut self) {
-        self.storage.clear();
-        self.index.clear();
+        let backend = self.backend.take();
+        if let Some((backend, namespace)) = &backend {
+            if let Err(e) = backend.clear(namespace) {
+                warn!("failed to clear the memory of {}: {:#}", namespace, e);
+            }
+        }
+        let semantic = self.semantic.take();
+        if let Some(semantic) = &semantic {
+            semantic.clear();
+        }
+        *self = Self { backend, semantic, ..Self::default() };
     }
 
     pub fn count(&self) -> usize {
-        self.storage.len()
+        self.by_id.len()
+    }
+
+    /// All the messages in insertion order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Message> {
+        self.storage.iter().flatten()
     }
 
     pub fn try_remember(&self, keyword: &str) -> Vec<&Message> {
-        self.storage
-            .iter()
-            .filter(|message| message.content.contains(keyword))
+        self.get_by_content(keyword)
+    }
+
+    /// The `k` messages passing `filter` most relevant to `query`, best first. Without a
+    /// semantic index, or when it fails, the most recent ones containing `query`.
+    ///
+    /// A memory shared behind a lock is better searched with a clone of `semantic_index`,
+    /// the query being embedded meanwhile.
+    pub async fn remember(&self, query: &str, k: usize, filter: &MemoryFilter) -> Vec<&Message> {
+        if let Some(semantic) = &self.semantic {
+            match semantic.search(query, k, filter).await {
+                Ok(hits) => return hits.iter().filter_map(|(id, _)| self.get_by_id(id)).collect(),
+                Err(e) => warn!("semantic search failed, matching the content instead: {:#}", e),
+            }
+        }
+        self.iter()
+            .rev()
+            .filter(|message| filter.matches(&message.role, &message.cause_by) && message.content.contains(query))
+            .take(k)
             .collect()
     }
 
+    /// All the messages with `k` 0, otherwise the `k` most recent ones, newest first.
     pub fn get(&self, k: usize) -> Vec<&Message> {
         if k == 0 {
-            self.storage.iter().collect()
+            self.iter().collect()
         } else {
-            self.storage.iter().rev().take(k).collect()
+            self.iter().rev().take(k).collect()
         }
     }
 
+    /// Whether the message is stored, among the `k` most recent ones unless `k` is 0.
     pub fn has_message(&self, message: &Message, k: usize) -> bool {
-        let messages = self.get(k);
-        messages.contains(&message)
+        if k == 0 {
+            return self.contains(&message.id);
+        }
+        self.iter().rev().take(k).any(|m| m.id == message.id)
     }
 
-    // pub fn remember(&self, observed: Vec<&Message>, k: usize) -> Vec<&Message> {
-    //     let already_observed = self.get(k);
-    //     observed
-    //         .into_iter()
-    //         .filter(|i| !already_observed.contains(i))
-    //         .collect()
-    // }
-
-    // pub fn get_by_action(&self, action: &str) -> Vec<&Message> {
-    //     self.index.get(action).unwrap_or(&Vec::new()).clone()
-    // }
-
-    pub fn get_by_actions(&self, actions: HashSet<String>) -> Vec<&Message> {
-        let mut result = Vec::new();
-        for action in actions {
-            if let Some(messages) = self.index.get(&action) {
-                result.extend(messages);
-            }
-        }
-        result
+    fn _resolve(&self, seqs: impl Iterator<Item = usize>) -> Vec<&Message> {
+        seqs.filter_map(|seq| self.storage[seq].as_ref()).collect()
     }
 }
 
-
 // pub fn add(left: usize, right: usize) -> usize {
 //     left + right
 // }
@@ -139,3 +253,88 @@ impl Memory {
 //         assert_eq!(result, 4);
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn message(role: &str, cause_by: &str) -> Message {
+        Message { content: format!("{} by {}", cause_by, role), role: role.into(), cause_by: cause_by.into(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_memory_indexes() {
+        let mut memory = Memory::new();
+        let prd = message("Product Manager", "WritePRD");
+        memory.add(prd.clone());
+        memory.add(prd.clone());
+        // the same message under another id
+        memory.add(message("Product Manager", "WritePRD"));
+        let cursor = memory.cursor();
+        let design = message("Architect", "WriteDesign");
+        memory.add(design.clone());
+        memory.add(message("Architect", "WriteApiSpec"));
+
+        assert_eq!(memory.count(), 3);
+        assert_eq!(memory.get_by_id(&design.id), Some(&design));
+        assert_eq!(memory.get_by_role("Architect").len(), 2);
+        assert_eq!(memory.get_by_action("WritePRD"), vec![&prd]);
+        let actions = HashSet::from(["WriteApiSpec".to_string(), "WritePRD".to_string()]);
+        assert_eq!(memory.get_by_actions(actions)[0], &prd);
+        assert_eq!(memory.since(cursor).len(), 2);
+        assert_eq!(memory.between(prd.timestamp, design.timestamp), vec![&prd]);
+        assert_eq!(memory.between(prd.timestamp, Utc::now() + Duration::seconds(1)).len(), 3);
+
+        memory.delete(&design);
+        assert!(!memory.has_message(&design, 0));
+        assert_eq!(memory.since(cursor).len(), 1);
+        assert_eq!(memory.get_by_role("Architect").len(), 1);
+        assert_eq!(memory.get(1)[0].cause_by, "WriteApiSpec");
+    }
+
+    #[test]
+    fn test_memory_survives_runs() {
+        let backend: Arc<dyn MemoryBackend> = Arc::new(InMemoryBackend::default());
+        let namespace = MemoryNamespace::new("snake", "Architect");
+        let prd = message("Product Manager", "WritePRD");
+
+        let mut memory = Memory::new();
+        memory.add(prd.clone());
+        memory.attach(backend.clone(), namespace.clone()).unwrap();
+        memory.add(message("Architect", "WriteDesign"));
+
+        let mut next_run = Memory::new();
+        next_run.attach(backend.clone(), namespace.clone()).unwrap();
+        assert_eq!(next_run.count(), 2);
+        assert_eq!(next_run.get(0)[0], &prd);
+        next_run.clear();
+        assert!(backend.load(&namespace).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_memory_remember() {
+        let text = |role: &str, cause_by: &str, content: &str| Message {
+            content: content.into(),
+            role: role.into(),
+            cause_by: cause_by.into(),
+            ..Default::default()
+        };
+        let mut memory = Memory::new();
+        memory.add(text("Product Manager", "WritePRD", "The snake grows when it eats food"));
+        memory.set_semantic_index(SemanticIndex::local());
+        let score = text("Architect", "WriteDesign", "A scoreboard shows the high score of the player");
+        memory.add(score.clone());
+        memory.add(text("Engineer", "WriteCode", "def move(snake): the snake moves on the grid"));
+
+        let best = memory.remember("player high score", 1, &MemoryFilter::default()).await;
+        assert_eq!(best, vec![&score]);
+        let snake = memory.remember("snake", 3, &MemoryFilter::default().role("Engineer")).await;
+        assert_eq!(snake.len(), 1);
+        assert_eq!(snake[0].cause_by, "WriteCode");
+        assert_eq!(memory.remember("snake eats food", 2, &MemoryFilter::default()).await[0].cause_by, "WritePRD");
+
+        memory.delete(&score);
+        assert!(memory.remember("score", 3, &MemoryFilter::default().cause_by("WriteDesign")).await.is_empty());
+    }
+}
@@ -0,0 +1,402 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use agent_provider::{is_llm_fake, with_fake_llm};
+use agent_schema::Message;
+use async_trait::async_trait;
+use tracing::warn;
+
+/// Dimensions of `HashEmbedder::default`.
+pub const DEFAULT_EMBEDDING_DIMENSIONS: usize = 512;
+
+/// Turns text into a vector, texts about the same things being close by cosine.
+#[async_trait]
+pub trait Embedder: fmt::Debug + Send + Sync {
+    fn dimensions(&self) -> usize;
+    async fn embed(&self, text: &str) -> anyhow::Result<Vec<f32>>;
+}
+
+/// Local embedder hashing the words and word pairs of the text into a fixed number of
+/// dimensions. No model nor network is needed, relevance is by shared vocabulary.
+#[derive(Debug, Clone)]
+pub struct HashEmbedder {
+    dimensions: usize,
+}
+
+impl HashEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        Self { dimensions: dimensions.max(1) }
+    }
+
+    fn _bucket(&self, feature: &str) -> (usize, f32) {
+        let mut hasher = Fnv1a::default();
+        feature.hash(&mut hasher);
+        let hash = hasher.finish();
+        // the sign bit keeps colliding features from only adding up
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        ((hash % self.dimensions as u64) as usize, sign)
+    }
+}
+
+impl Default for HashEmbedder {
+    fn default() -> Self {
+        Self::new(DEFAULT_EMBEDDING_DIMENSIONS)
+    }
+}
+
+#[async_trait]
+impl Embedder for HashEmbedder {
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    async fn embed(&self, text: &str) -> anyhow::Result<Vec<f32>> {
+        let words: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.chars().count() > 1)
+            .map(str::to_lowercase)
+            .collect();
+        let mut vector = vec![0.0; self.dimensions];
+        for word in &words {
+            let (idx, sign) = self._bucket(word);
+            vector[idx] += sign;
+        }
+        for pair in words.windows(2) {
+            let (idx, sign) = self._bucket(&format!("{} {}", pair[0], pair[1]));
+            vector[idx] += 0.5 * sign;
+        }
+        normalize(&mut vector);
+        Ok(vector)
+    }
+}
+
+/// FNV-1a, stable across processes unlike the std hasher, so stored vectors stay valid.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norms = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+/// Restricts a search to the messages of a role and/or caused by an action.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryFilter {
+    pub role: Option<String>,
+    pub cause_by: Option<String>,
+}
+
+impl MemoryFilter {
+    pub fn role(mut self, role: &str) -> Self {
+        self.role = Some(role.to_string());
+        self
+    }
+
+    pub fn cause_by(mut self, cause_by: &str) -> Self {
+        self.cause_by = Some(cause_by.to_string());
+        self
+    }
+
+    pub fn matches(&self, role: &str, cause_by: &str) -> bool {
+        self.role.as_deref().map_or(true, |r| r == role) && self.cause_by.as_deref().map_or(true, |c| c == cause_by)
+    }
+}
+
+/// Vectors of messages with the metadata they are filtered on.
+#[async_trait]
+pub trait VectorIndex: fmt::Debug + Send + Sync {
+    /// Store the vector of `message`, replacing the one with the same id.
+    async fn upsert(&self, message: &Message, vector: Vec<f32>) -> anyhow::Result<()>;
+    /// Ids and scores of the `k` vectors closest to `vector` passing `filter`, best first.
+    async fn search(&self, vector: &[f32], k: usize, filter: &MemoryFilter) -> anyhow::Result<Vec<(String, f32)>>;
+    async fn remove(&self, id: &str) -> anyhow::Result<()>;
+    async fn clear(&self) -> anyhow::Result<()>;
+}
+
+#[derive(Debug)]
+struct IndexEntry {
+    role: String,
+    cause_by: String,
+    vector: Vec<f32>,
+}
+
+/// In-process index searched exhaustively, plenty for the messages of a run.
+#[derive(Debug, Default)]
+pub struct LocalVectorIndex {
+    entries: Mutex<HashMap<String, IndexEntry>>,
+}
+
+#[async_trait]
+impl VectorIndex for LocalVectorIndex {
+    async fn upsert(&self, message: &Message, vector: Vec<f32>) -> anyhow::Result<()> {
+        let entry = IndexEntry { role: message.role.clone(), cause_by: message.cause_by.clone(), vector };
+        self.entries.lock().unwrap().insert(message.id.clone(), entry);
+        Ok(())
+    }
+
+    async fn search(&self, vector: &[f32], k: usize, filter: &MemoryFilter) -> anyhow::Result<Vec<(String, f32)>> {
+        let entries = self.entries.lock().unwrap();
+        let mut scored: Vec<(String, f32)> = entries
+            .iter()
+            .filter(|(_, entry)| filter.matches(&entry.role, &entry.cause_by))
+            .map(|(id, entry)| (id.clone(), cosine(vector, &entry.vector)))
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        scored.truncate(k);
+        Ok(scored)
+    }
+
+    async fn remove(&self, id: &str) -> anyhow::Result<()> {
+        self.entries.lock().unwrap().remove(id);
+        Ok(())
+    }
+
+    async fn clear(&self) -> anyhow::Result<()> {
+        self.entries.lock().unwrap().clear();
+        Ok(())
+    }
+}
+
+/// A change of the memory that the index has not caught up with yet.
+#[derive(Debug)]
+enum IndexChange {
+    Upsert(Message),
+    Remove(String),
+    Clear,
+}
+
+/// What `Memory` embeds its messages with and where it indexes them, see
+/// `Memory::set_semantic_index`.
+///
+/// `Memory` only queues its changes, so that no embedding runs while it is locked. They
+/// are applied in order by a task spawned on the current runtime, if any, and before
+/// every search.
+#[derive(Debug, Clone)]
+pub struct SemanticIndex {
+    pub embedder: Arc<dyn Embedder>,
+    pub index: Arc<dyn VectorIndex>,
+    pending: Arc<Mutex<Vec<IndexChange>>>,
+    applying: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl SemanticIndex {
+    pub fn new(embedder: Arc<dyn Embedder>, index: Arc<dyn VectorIndex>) -> Self {
+        Self { embedder, index, pending: Arc::default(), applying: Arc::default() }
+    }
+
+    /// `HashEmbedder` and `LocalVectorIndex`, all in process.
+    pub fn local() -> Self {
+        Self::new(Arc::new(HashEmbedder::default()), Arc::new(LocalVectorIndex::default()))
+    }
+
+    pub(crate) fn insert(&self, message: &Message) {
+        self._queue(IndexChange::Upsert(message.clone()));
+    }
+
+    pub(crate) fn remove(&self, id: &str) {
+        self._queue(IndexChange::Remove(id.to_string()));
+    }
+
+    pub(crate) fn clear(&self) {
+        self._queue(IndexChange::Clear);
+    }
+
+    fn _queue(&self, change: IndexChange) {
+        self.pending.lock().unwrap().push(change);
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            // the task does not inherit the caller's `with_fake_llm`
+            let (semantic, fake) = (self.clone(), is_llm_fake());
+            handle.spawn(async move {
+                if fake {
+                    with_fake_llm(semantic.catch_up()).await
+                } else {
+                    semantic.catch_up().await
+                }
+            });
+        }
+    }
+
+    /// Apply the queued changes. A change failing is logged and dropped.
+    pub async fn catch_up(&self) {
+        let _applying = self.applying.lock().await;
+        let changes = std::mem::take(&mut *self.pending.lock().unwrap());
+        for change in changes {
+            match change {
+                IndexChange::Upsert(message) => {
+                    let indexed = match self.embedder.embed(&message.content).await {
+                        Ok(vector) => self.index.upsert(&message, vector).await,
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = indexed {
+                        warn!("failed to index message {}: {:#}", message.id, e);
+                    }
+                }
+                IndexChange::Remove(id) => {
+                    if let Err(e) = self.index.remove(&id).await {
+                        warn!("failed to unindex message {}: {:#}", id, e);
+                    }
+                }
+                IndexChange::Clear => {
+                    if let Err(e) = self.index.clear().await {
+                        warn!("failed to clear the semantic index: {:#}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Ids and scores of the `k` messages passing `filter` most relevant to `query`, best
+    /// first, once the queued changes are applied.
+    pub async fn search(&self, query: &str, k: usize, filter: &MemoryFilter) -> anyhow::Result<Vec<(String, f32)>> {
+        self.catch_up().await;
+        let vector = self.embedder.embed(query).await?;
+        self.index.search(&vector, k, filter).await
+    }
+}
+
+#[cfg(feature = "qdrant")]
+pub use self::qdrant::QdrantVectorIndex;
+
+#[cfg(feature = "qdrant")]
+mod qdrant {
+    use std::collections::HashMap;
+    use std::fmt;
+
+    use anyhow::Context;
+    use async_trait::async_trait;
+    use qdrant_client::prelude::*;
+    use qdrant_client::qdrant::point_id::PointIdOptions;
+    use qdrant_client::qdrant::vectors_config::Config;
+    use qdrant_client::qdrant::{
+        Condition, CreateCollection, Filter, PointId, PointsSelector, SearchPoints, VectorParams,
+        VectorsConfig,
+    };
+
+    use agent_schema::Message;
+
+    use super::{MemoryFilter, VectorIndex};
+
+    /// A Qdrant collection, the message id being the point id.
+    pub struct QdrantVectorIndex {
+        client: QdrantClient,
+        collection: String,
+    }
+
+    impl fmt::Debug for QdrantVectorIndex {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("QdrantVectorIndex").field("collection", &self.collection).finish()
+        }
+    }
+
+    impl QdrantVectorIndex {
+        /// Connect to the server at `url`, creating `collection` with `dimensions` when missing.
+        pub async fn connect(url: &str, collection: &str, dimensions: usize) -> anyhow::Result<Self> {
+            let client = QdrantClient::from_url(url).build()?;
+            async {
+                if !client.has_collection(collection).await? {
+                    client
+                        .create_collection(&CreateCollection {
+                            collection_name: collection.to_string(),
+                            vectors_config: Some(VectorsConfig {
+                                config: Some(Config::Params(VectorParams {
+                                    size: dimensions as u64,
+                                    distance: Distance::Cosine.into(),
+                                    ..Default::default()
+                                })),
+                            }),
+                            ..Default::default()
+                        })
+                        .await?;
+                }
+                anyhow::Ok(())
+            }
+            .await
+            .with_context(|| format!("failed to open the Qdrant collection {} at {}", collection, url))?;
+            Ok(Self { client, collection: collection.to_string() })
+        }
+    }
+
+    #[async_trait]
+    impl VectorIndex for QdrantVectorIndex {
+        async fn upsert(&self, message: &Message, vector: Vec<f32>) -> anyhow::Result<()> {
+            let payload: Payload = HashMap::from([
+                ("role", Value::from(message.role.clone())),
+                ("cause_by", Value::from(message.cause_by.clone())),
+            ])
+            .into();
+            let point = PointStruct::new(message.id.clone(), vector, payload);
+            self.client.upsert_points_blocking(&self.collection, vec![point], None).await?;
+            Ok(())
+        }
+
+        async fn search(&self, vector: &[f32], k: usize, filter: &MemoryFilter) -> anyhow::Result<Vec<(String, f32)>> {
+            let mut conditions = vec![];
+            if let Some(role) = &filter.role {
+                conditions.push(Condition::matches("role", role.clone()));
+            }
+            if let Some(cause_by) = &filter.cause_by {
+                conditions.push(Condition::matches("cause_by", cause_by.clone()));
+            }
+            let search = SearchPoints {
+                collection_name: self.collection.clone(),
+                vector: vector.to_vec(),
+                filter: if conditions.is_empty() { None } else { Some(Filter::must(conditions)) },
+                limit: k as u64,
+                ..Default::default()
+            };
+            let response = self.client.search_points(&search).await?;
+            Ok(response
+                .result
+                .into_iter()
+                .filter_map(|point| match point.id?.point_id_options? {
+                    PointIdOptions::Uuid(id) => Some((id, point.score)),
+                    PointIdOptions::Num(id) => Some((id.to_string(), point.score)),
+                })
+                .collect())
+        }
+
+        async fn remove(&self, id: &str) -> anyhow::Result<()> {
+            let selector = PointsSelector::from(vec![PointId::from(id.to_string())]);
+            self.client.delete_points_blocking(&self.collection, &selector, None).await?;
+            Ok(())
+        }
+
+        async fn clear(&self) -> anyhow::Result<()> {
+            let selector = PointsSelector::from(Filter::default());
+            self.client.delete_points_blocking(&self.collection, &selector, None).await?;
+            Ok(())
+        }
+    }
+}
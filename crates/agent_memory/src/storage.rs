@@ -1,11 +1,27 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
 use serde_json::value::Value;
-use std::collections::HashMap;
-use std::io::{Read, Write};
-use std::path::PathBuf;
+
+/// Version of the file layout written by `LocalJsonStorage`. Files without one, or without
+/// its `namespaces` object, are the flat `key -> value` maps of earlier versions and are read
+/// into `DEFAULT_NAMESPACE`.
+pub const STORAGE_VERSION: u64 = 1;
+
+/// Namespace of the `KVStorage` methods of `LocalJsonStorage` itself.
+pub const DEFAULT_NAMESPACE: &str = "default";
 
 // KVStorage trait
 pub trait KVStorage {
     fn add(&mut self, key: &str, value: Value) -> anyhow::Result<()>;
+    /// Add a value that `query` stops returning once `ttl` has elapsed.
+    fn add_with_ttl(&mut self, key: &str, value: Value, ttl: Duration) -> anyhow::Result<()>;
     fn remove(&mut self, key: &str) -> anyhow::Result<()>;
     fn query(&self, key: &str) -> anyhow::Result<Option<Value>>;
     fn update(&mut self, key: &str, value: Value) -> anyhow::Result<()>;
@@ -13,79 +29,369 @@ pub trait KVStorage {
     fn batch_update(&mut self, data: HashMap<String, Value>) -> anyhow::Result<()>;
 }
 
-// LocalJsonStorage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    value: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl Entry {
+    fn is_live(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.map_or(true, |expires_at| expires_at > now)
+    }
+}
+
+type Namespaces = BTreeMap<String, BTreeMap<String, Entry>>;
+
+/// The file as written, see `STORAGE_VERSION`.
+#[derive(Debug, Serialize, Deserialize)]
+struct StorageFile {
+    version: u64,
+    namespaces: Namespaces,
+}
+
+/// Bring a file of any version to the current layout. Only a numeric `version` next to a
+/// `namespaces` object marks a versioned file, a flat map may well have a `version` key.
+fn migrate(value: Value) -> anyhow::Result<Namespaces> {
+    let Value::Object(map) = value else { anyhow::bail!("the storage is not a JSON object") };
+    let versioned = map.get("namespaces").is_some_and(Value::is_object);
+    let version = map.get("version").and_then(Value::as_u64).filter(|_| versioned);
+    match version {
+        Some(version) if version > STORAGE_VERSION => {
+            anyhow::bail!("the storage has version {}, newer than the supported {}", version, STORAGE_VERSION)
+        }
+        Some(_) => Ok(serde_json::from_value::<StorageFile>(Value::Object(map))?.namespaces),
+        None => {
+            let entries = map.into_iter().map(|(key, value)| (key, Entry { value, expires_at: None })).collect();
+            Ok(BTreeMap::from([(DEFAULT_NAMESPACE.to_string(), entries)]))
+        }
+    }
+}
+
+/// A change applied by `LocalJsonStorage::_commit`.
+#[derive(Debug, Clone)]
+enum Op {
+    Put { namespace: String, key: String, entry: Entry },
+    Remove { namespace: String, key: String },
+    ClearNamespace { namespace: String },
+}
+
+/// A JSON file of namespaced keys, safe against crashes and concurrent processes.
+///
+/// Every write takes an advisory lock on `<file>.lock`, reads the file again to keep the
+/// changes of other processes, applies its own and replaces the file with a fully written
+/// temporary one. Reads are served from the values loaded by the last write or `reload`.
+#[derive(Debug)]
 pub struct LocalJsonStorage {
     path: PathBuf,
-    pub data: HashMap<String, Value>,
+    namespaces: Namespaces,
 }
 
 impl LocalJsonStorage {
     pub fn new(path: PathBuf) -> anyhow::Result<Self> {
         // if the path is not existing, error
-        let mut file = std::fs::File::open(path.clone())?;
-        let mut content = String::new();
-        file.read_to_string(&mut content)?;
-        let data: HashMap<String, Value> = serde_json::from_str(&content)?;
-        Ok(Self { path, data })
+        let namespaces = Self::_read(&path)?.ok_or_else(|| anyhow::anyhow!("{} does not exist", path.display()))?;
+        Ok(Self { path, namespaces })
     }
 
+    /// Create the file, with `content` as a flat JSON object of the default namespace.
     pub fn create(path: PathBuf, content: Option<String>, force: bool) -> anyhow::Result<Self> {
         // if the path is existing, error
         if !force && path.exists() {
             return Err(anyhow::anyhow!("path is existing"));
         }
-        let mut data: HashMap<String, Value> = HashMap::new();
-        if let Some(content) = content {
-            data = serde_json::from_str(&content)?;
-        }
+        let namespaces = match content {
+            Some(content) => migrate(serde_json::from_str(&content)?)?,
+            None => Namespaces::new(),
+        };
         // if parent directory is not existing, create it
-        if let Some(parent) = path.parent() {
-            if !parent.exists() {
-                std::fs::create_dir_all(parent)?;
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut storage = Self { path, namespaces: Namespaces::new() };
+        let lock = storage._lock()?;
+        Self::_write(&storage.path, &namespaces)?;
+        drop(lock);
+        storage.namespaces = namespaces;
+        Ok(storage)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read the changes other processes wrote since the last write.
+    pub fn reload(&mut self) -> anyhow::Result<()> {
+        let lock = self._lock()?;
+        self.namespaces = Self::_read(&self.path)?.unwrap_or_default();
+        drop(lock);
+        Ok(())
+    }
+
+    /// The keys of `namespace` whose values have not expired.
+    pub fn keys(&self, namespace: &str) -> Vec<String> {
+        let now = Utc::now();
+        self.namespaces
+            .get(namespace)
+            .map(|entries| entries.iter().filter(|(_, entry)| entry.is_live(now)).map(|(key, _)| key.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// A `KVStorage` over the keys of `namespace` only.
+    pub fn namespace(&mut self, namespace: &str) -> NamespacedStorage<'_> {
+        NamespacedStorage { storage: self, namespace: namespace.to_string() }
+    }
+
+    /// Remove every key of `namespace`.
+    pub fn clear_namespace(&mut self, namespace: &str) -> anyhow::Result<()> {
+        self._commit(vec![Op::ClearNamespace { namespace: namespace.to_string() }])
+    }
+
+    /// Changes written together, or not at all when the transaction is dropped uncommitted.
+    pub fn transaction(&mut self) -> Transaction<'_> {
+        Transaction { storage: self, ops: vec![] }
+    }
+
+    fn _get(&self, namespace: &str, key: &str) -> Option<Value> {
+        let entry = self.namespaces.get(namespace)?.get(key)?;
+        entry.is_live(Utc::now()).then(|| entry.value.clone())
+    }
+
+    fn _lock(&self) -> anyhow::Result<File> {
+        let path = sibling(&self.path, "lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("failed to open the lock {}", path.display()))?;
+        file.lock_exclusive().with_context(|| format!("failed to lock {}", path.display()))?;
+        // unlocked when the file is closed
+        Ok(file)
+    }
+
+    /// Apply `ops` to the file as it is on disk, dropping the expired keys.
+    fn _commit(&mut self, ops: Vec<Op>) -> anyhow::Result<()> {
+        let lock = self._lock()?;
+        let mut namespaces = Self::_read(&self.path)?.unwrap_or_default();
+        for op in ops {
+            match op {
+                Op::Put { namespace, key, entry } => {
+                    namespaces.entry(namespace).or_default().insert(key, entry);
+                }
+                Op::Remove { namespace, key } => {
+                    if let Some(entries) = namespaces.get_mut(&namespace) {
+                        entries.remove(&key);
+                    }
+                }
+                Op::ClearNamespace { namespace } => {
+                    namespaces.remove(&namespace);
+                }
             }
         }
-        // force create the file, and write the content
-        let mut file = std::fs::File::create(path.clone())?;
-        let content = serde_json::to_string_pretty(&data)?;
-        file.write_all(content.as_bytes())?;
-        Ok(Self { path, data })
+        let now = Utc::now();
+        for entries in namespaces.values_mut() {
+            entries.retain(|_, entry| entry.is_live(now));
+        }
+        namespaces.retain(|_, entries| !entries.is_empty());
+        Self::_write(&self.path, &namespaces)?;
+        drop(lock);
+        self.namespaces = namespaces;
+        Ok(())
+    }
+
+    /// `None` when the file does not exist.
+    fn _read(path: &Path) -> anyhow::Result<Option<Namespaces>> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+        };
+        let value: Value = serde_json::from_str(&content).with_context(|| format!("{} is not valid JSON", path.display()))?;
+        migrate(value).with_context(|| format!("failed to read {}", path.display())).map(Some)
+    }
+
+    /// Write a temporary file next to `path` and rename it over `path`, so the file is
+    /// always either the old or the new version.
+    fn _write(path: &Path, namespaces: &Namespaces) -> anyhow::Result<()> {
+        let file = StorageFile { version: STORAGE_VERSION, namespaces: namespaces.clone() };
+        let content = serde_json::to_string_pretty(&serde_json::to_value(file)?)?;
+        let tmp = sibling(path, "tmp");
+        let mut out = File::create(&tmp).with_context(|| format!("failed to create {}", tmp.display()))?;
+        out.write_all(content.as_bytes())?;
+        out.sync_all()?;
+        std::fs::rename(&tmp, path).with_context(|| format!("failed to replace {}", path.display()))?;
+        Ok(())
     }
 }
 
+/// `path` with `extension` appended, e.g. `memory.json.lock`.
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(extension);
+    path.with_file_name(name)
+}
+
+fn entry(value: Value, ttl: Option<Duration>) -> Entry {
+    Entry { value, expires_at: ttl.map(|ttl| Utc::now() + ttl) }
+}
+
 impl KVStorage for LocalJsonStorage {
     fn add(&mut self, key: &str, value: Value) -> anyhow::Result<()> {
-        self.data.insert(key.to_string(), value);
-        self.save()
+        self.namespace(DEFAULT_NAMESPACE).add(key, value)
+    }
+
+    fn add_with_ttl(&mut self, key: &str, value: Value, ttl: Duration) -> anyhow::Result<()> {
+        self.namespace(DEFAULT_NAMESPACE).add_with_ttl(key, value, ttl)
     }
 
     fn remove(&mut self, key: &str) -> anyhow::Result<()> {
-        self.data.remove(key);
-        self.save()
+        self.namespace(DEFAULT_NAMESPACE).remove(key)
     }
 
     fn query(&self, key: &str) -> anyhow::Result<Option<Value>> {
-        Ok(self.data.get(key).cloned())
+        Ok(self._get(DEFAULT_NAMESPACE, key))
     }
 
     fn update(&mut self, key: &str, value: Value) -> anyhow::Result<()> {
-        self.data.insert(key.to_string(), value);
-        self.save()
+        self.namespace(DEFAULT_NAMESPACE).update(key, value)
     }
 
     fn save(&mut self) -> anyhow::Result<()> {
-        let mut file = std::fs::File::create(self.path.clone())?;
-        let content = serde_json::to_string_pretty(&self.data)?;
-        file.write_all(content.as_bytes())?;
-        Ok(())
+        self._commit(vec![])
     }
 
     fn batch_update(&mut self, data: HashMap<String, Value>) -> anyhow::Result<()> {
+        self.namespace(DEFAULT_NAMESPACE).batch_update(data)
+    }
+}
+
+/// The keys of one namespace of a `LocalJsonStorage`, see `LocalJsonStorage::namespace`.
+#[derive(Debug)]
+pub struct NamespacedStorage<'a> {
+    storage: &'a mut LocalJsonStorage,
+    namespace: String,
+}
+
+impl NamespacedStorage<'_> {
+    fn _put(&mut self, key: &str, value: Value, ttl: Option<Duration>) -> anyhow::Result<()> {
+        let op = Op::Put { namespace: self.namespace.clone(), key: key.to_string(), entry: entry(value, ttl) };
+        self.storage._commit(vec![op])
+    }
+}
+
+impl KVStorage for NamespacedStorage<'_> {
+    fn add(&mut self, key: &str, value: Value) -> anyhow::Result<()> {
+        self._put(key, value, None)
+    }
+
+    fn add_with_ttl(&mut self, key: &str, value: Value, ttl: Duration) -> anyhow::Result<()> {
+        self._put(key, value, Some(ttl))
+    }
+
+    fn remove(&mut self, key: &str) -> anyhow::Result<()> {
+        self.storage._commit(vec![Op::Remove { namespace: self.namespace.clone(), key: key.to_string() }])
+    }
+
+    fn query(&self, key: &str) -> anyhow::Result<Option<Value>> {
+        Ok(self.storage._get(&self.namespace, key))
+    }
+
+    fn update(&mut self, key: &str, value: Value) -> anyhow::Result<()> {
+        self._put(key, value, None)
+    }
+
+    fn save(&mut self) -> anyhow::Result<()> {
+        self.storage.save()
+    }
+
+    fn batch_update(&mut self, data: HashMap<String, Value>) -> anyhow::Result<()> {
+        let mut transaction = self.storage.transaction();
         for (key, value) in data {
-            self.data.insert(key, value);
+            transaction.put(&self.namespace, &key, value);
         }
-        let mut file = std::fs::File::create(self.path.clone())?;
-        let content = serde_json::to_string_pretty(&self.data)?;
-        file.write_all(content.as_bytes())?;
-        Ok(())
+        transaction.commit()
+    }
+}
+
+/// Changes staged by `LocalJsonStorage::transaction`.
+#[derive(Debug)]
+pub struct Transaction<'a> {
+    storage: &'a mut LocalJsonStorage,
+    ops: Vec<Op>,
+}
+
+impl Transaction<'_> {
+    pub fn put(&mut self, namespace: &str, key: &str, value: Value) -> &mut Self {
+        self.ops.push(Op::Put { namespace: namespace.to_string(), key: key.to_string(), entry: entry(value, None) });
+        self
+    }
+
+    pub fn put_with_ttl(&mut self, namespace: &str, key: &str, value: Value, ttl: Duration) -> &mut Self {
+        self.ops.push(Op::Put { namespace: namespace.to_string(), key: key.to_string(), entry: entry(value, Some(ttl)) });
+        self
+    }
+
+    pub fn remove(&mut self, namespace: &str, key: &str) -> &mut Self {
+        self.ops.push(Op::Remove { namespace: namespace.to_string(), key: key.to_string() });
+        self
+    }
+
+    pub fn clear_namespace(&mut self, namespace: &str) -> &mut Self {
+        self.ops.push(Op::ClearNamespace { namespace: namespace.to_string() });
+        self
     }
-}
\ No newline at end of file
+
+    /// Write the staged changes at once.
+    pub fn commit(self) -> anyhow::Result<()> {
+        self.storage._commit(self.ops)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_local_json_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.json");
+        let mut storage = LocalJsonStorage::create(path.clone(), Some(r#"{"legacy": 1}"#.into()), false).unwrap();
+        assert_eq!(storage.query("legacy").unwrap(), Some(json!(1)));
+
+        // another process writing the same file keeps our keys
+        let mut other = LocalJsonStorage::new(path.clone()).unwrap();
+        other.namespace("snake").add("design", json!({"files": ["main.py"]})).unwrap();
+        storage.add_with_ttl("session", json!("token"), Duration::seconds(-1)).unwrap();
+        assert_eq!(storage.namespace("snake").query("design").unwrap(), Some(json!({"files": ["main.py"]})));
+        assert_eq!(storage.query("session").unwrap(), None);
+
+        let mut transaction = storage.transaction();
+        transaction.put("snake", "prd", json!("PRD")).remove(DEFAULT_NAMESPACE, "legacy");
+        drop(transaction);
+        assert_eq!(storage.keys("snake"), vec!["design"]);
+        let mut transaction = storage.transaction();
+        transaction.put("snake", "prd", json!("PRD")).remove(DEFAULT_NAMESPACE, "legacy");
+        transaction.commit().unwrap();
+
+        let reopened = LocalJsonStorage::new(path.clone()).unwrap();
+        assert_eq!(reopened.keys("snake"), vec!["design", "prd"]);
+        assert!(reopened.keys(DEFAULT_NAMESPACE).is_empty());
+        let raw: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(raw["version"], json!(STORAGE_VERSION));
+
+        // a legacy flat map with a `version` key of its own
+        std::fs::write(&path, r#"{"version": 2, "namespaces": ["snake"]}"#).unwrap();
+        let legacy = LocalJsonStorage::new(path.clone()).unwrap();
+        assert_eq!(legacy.query("version").unwrap(), Some(json!(2)));
+        assert_eq!(legacy.query("namespaces").unwrap(), Some(json!(["snake"])));
+        std::fs::write(&path, r#"{"version": "1.0"}"#).unwrap();
+        assert_eq!(LocalJsonStorage::new(path.clone()).unwrap().query("version").unwrap(), Some(json!("1.0")));
+
+        std::fs::write(&path, r#"{"version": 99, "namespaces": {}}"#).unwrap();
+        assert!(LocalJsonStorage::new(path).is_err());
+    }
+}
@@ -1,8 +1,10 @@
 mod prompt_processing;
+mod prompt_registry;
 // mod auto_agent_instructions;
 mod agent_prompt;
 
 pub use prompt_processing::PromptTemplate;
+pub use prompt_registry::{PromptRegistry, PROJECT_PROMPT_DIR, PROMPT_PATH_ENV};
 pub use agent_prompt::AgentPrompt;
 // pub use auto_agent_instructions::{AutoAgentInstructions};
 
@@ -28,10 +28,24 @@ impl PromptTemplate {
         }
     }
 
-    /// ```
+    /// Names of the placeholders used in the template, without braces and duplicates.
+    pub fn placeholders(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = vec![];
+        for (start, end) in self.matches.iter() {
+            let name = &self.src[start + 2..end - 2];
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
     /// # Examples
     ///
-    /// let template = Template::new("Hi, my name is {{name}} and I'm a {{lang}} developer.");
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use agent_prompts::PromptTemplate;
+    /// let template = PromptTemplate::new("Hi, my name is {{name}} and I'm a {{lang}} developer.");
     ///
     /// let mut args = HashMap::new();
     /// args.insert("name", "Michael");
@@ -106,7 +120,8 @@ impl PromptTemplate {
     }
 
     /// ```
-    /// let template = Template::new("Hi, my name is {{}} and I'm a {{}} developer.");
+    /// # use agent_prompts::PromptTemplate;
+    /// let template = PromptTemplate::new("Hi, my name is {{}} and I'm a {{}} developer.");
     ///
     /// let args = vec!["Michael", "Rust"];
     /// let s = template.render_positional(&args);
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::prompt_processing::PromptTemplate;
+
+/// Directory, relative to the project root, whose files override the built-in templates.
+pub const PROJECT_PROMPT_DIR: &str = "prompts";
+/// Env var holding a list of directories (`:` separated on unix) overriding everything else.
+pub const PROMPT_PATH_ENV: &str = "AGENTX_PROMPTS_PATH";
+
+#[derive(Debug, Clone)]
+struct RegisteredPrompt {
+    text: String,
+    /// Where the current text comes from, `builtin` or a file path.
+    source: String,
+    /// Placeholders the caller fills in when rendering.
+    placeholders: Vec<String>,
+}
+
+/// Named prompt templates, layered as: built-in defaults, then project-level files
+/// in `<project root>/prompts`, then the directories listed in `AGENTX_PROMPTS_PATH`.
+/// A template named `write_prd` is overridden by a `write_prd.md` file.
+#[derive(Debug, Default, Clone)]
+pub struct PromptRegistry {
+    prompts: HashMap<String, RegisteredPrompt>,
+}
+
+impl PromptRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the built-in template for `name` and the placeholders the caller renders it with.
+    pub fn register(&mut self, name: &str, default: &str, placeholders: &[&str]) {
+        self.prompts.insert(
+            name.to_string(),
+            RegisteredPrompt {
+                text: default.to_string(),
+                source: "builtin".to_string(),
+                placeholders: placeholders.iter().map(|p| p.to_string()).collect(),
+            },
+        );
+    }
+
+    /// Override registered templates with the `<name>.md` files found in `dir`.
+    /// Returns the number of overridden templates.
+    pub fn load_dir(&mut self, dir: &Path) -> std::io::Result<usize> {
+        let mut loaded = 0;
+        if !dir.is_dir() {
+            return Ok(loaded);
+        }
+        for (name, prompt) in self.prompts.iter_mut() {
+            let path = dir.join(format!("{}.md", name));
+            if path.is_file() {
+                prompt.text = std::fs::read_to_string(&path)?;
+                prompt.source = path.display().to_string();
+                loaded += 1;
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Apply the overrides of `project_root`, if any, and the env-specified ones on top of the
+    /// defaults. Each directory is loaded on its own, one failing does not skip the others.
+    /// Returns the number of overridden templates and a message per failed directory.
+    pub fn load_overrides(&mut self, project_root: Option<&Path>) -> (usize, Vec<String>) {
+        let mut dirs: Vec<PathBuf> = project_root.map(|root| root.join(PROJECT_PROMPT_DIR)).into_iter().collect();
+        if let Some(paths) = std::env::var_os(PROMPT_PATH_ENV) {
+            dirs.extend(std::env::split_paths(&paths));
+        }
+        self.load_dirs(&dirs)
+    }
+
+    fn load_dirs(&mut self, dirs: &[PathBuf]) -> (usize, Vec<String>) {
+        let mut loaded = 0;
+        let mut errors = Vec::new();
+        for dir in dirs {
+            match self.load_dir(dir) {
+                Ok(n) => loaded += n,
+                Err(e) => errors.push(format!("{}: {}", dir.display(), e)),
+            }
+        }
+        (loaded, errors)
+    }
+
+    pub fn template(&self, name: &str) -> Option<PromptTemplate> {
+        self.prompts.get(name).map(|p| PromptTemplate::new(&p.text))
+    }
+
+    pub fn text(&self, name: &str) -> Option<&str> {
+        self.prompts.get(name).map(|p| p.text.as_str())
+    }
+
+    pub fn source(&self, name: &str) -> Option<&str> {
+        self.prompts.get(name).map(|p| p.source.as_str())
+    }
+
+    /// Check every template uses exactly the registered placeholders: a missing one would
+    /// silently drop context, an unknown one would be sent to the LLM verbatim.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        let mut names: Vec<&String> = self.prompts.keys().collect();
+        names.sort();
+        for name in names {
+            let prompt = &self.prompts[name];
+            let template = PromptTemplate::new(&prompt.text);
+            let used = template.placeholders();
+            for expected in &prompt.placeholders {
+                if !used.contains(&expected.as_str()) {
+                    errors.push(format!("{} ({}): missing placeholder {{{{{}}}}}", name, prompt.source, expected));
+                }
+            }
+            for placeholder in used {
+                if !prompt.placeholders.iter().any(|p| p == placeholder) {
+                    errors.push(format!("{} ({}): unknown placeholder {{{{{}}}}}", name, prompt.source, placeholder));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_placeholders() {
+        let mut registry = PromptRegistry::new();
+        registry.register("ok", "# Context\n{{context}}", &["context"]);
+        assert!(registry.validate().is_ok());
+
+        registry.register("broken", "# Context\n{{contxt}}", &["context"]);
+        let errors = registry.validate().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("missing placeholder {{context}}"));
+        assert!(errors[1].contains("unknown placeholder {{contxt}}"));
+    }
+
+    #[test]
+    fn test_load_overrides() {
+        let root = tempfile::tempdir().unwrap();
+        let project = root.path().join(PROJECT_PROMPT_DIR);
+        let (broken, extra) = (root.path().join("broken"), root.path().join("extra"));
+        for dir in [&project, &broken, &extra] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(project.join("write_prd.md"), "project {{context}}").unwrap();
+        std::fs::write(broken.join("write_prd.md"), [0xff, 0xfe]).unwrap();
+        std::fs::write(extra.join("write_code.md"), "extra {{context}}").unwrap();
+
+        let mut registry = PromptRegistry::new();
+        registry.register("write_prd", "{{context}}", &["context"]);
+        registry.register("write_code", "{{context}}", &["context"]);
+        // a directory failing to load does not skip the next ones
+        let (loaded, errors) = registry.load_dirs(&[project, broken.clone(), extra]);
+        assert_eq!(loaded, 2);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with(&broken.display().to_string()));
+        assert_eq!(registry.text("write_prd"), Some("project {{context}}"));
+        assert_eq!(registry.text("write_code"), Some("extra {{context}}"));
+    }
+}
@@ -0,0 +1,34 @@
+use std::future::Future;
+
+tokio::task_local! {
+    static FAKE_LLM: bool;
+}
+
+/// Run `future` without the LLM: the actions answer with their samples and the roles with
+/// nothing, e.g. to test a run offline. Futures polled concurrently next to it keep asking
+/// the LLM.
+pub async fn with_fake_llm<F: Future>(future: F) -> F::Output {
+    FAKE_LLM.scope(true, future).await
+}
+
+/// Whether the LLM is faked, within `with_fake_llm` or else with `LLM_FAKE=true`.
+pub fn is_llm_fake() -> bool {
+    FAKE_LLM
+        .try_with(|fake| *fake)
+        .unwrap_or_else(|_| std::env::var("LLM_FAKE").is_ok_and(|fake| fake == "true"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_with_fake_llm() {
+        let (faked, asked) = futures::join!(with_fake_llm(async { is_llm_fake() }), async {
+            tokio::task::yield_now().await;
+            is_llm_fake()
+        });
+        assert!(faked);
+        assert_eq!(asked, std::env::var("LLM_FAKE").is_ok_and(|fake| fake == "true"));
+    }
+}
@@ -1,6 +1,12 @@
+mod fake_llm;
 mod llmbase;
 mod openai;
+mod prompt_log;
+mod tokens;
 
 
+pub use fake_llm::{is_llm_fake, with_fake_llm};
 pub use llmbase::LLMBase;
-pub use openai::OpenAIGPTAPI as LLM;
\ No newline at end of file
+pub use openai::{chat_request_message, openai_config, set_provider, OpenAIGPTAPI as LLM, LLMSettings, ProviderSettings};
+pub use prompt_log::{capture_prompts, record_prompt};
+pub use tokens::{context_window, count_tokens, DEFAULT_COMPLETION_TOKENS, DEFAULT_CONTEXT_WINDOW};
\ No newline at end of file
@@ -1,10 +1,12 @@
 #![warn(unused_variables)]
 use std::error::Error;
+use std::sync::OnceLock;
 
 use tracing::{debug, info, warn, error};
 
 use async_trait::async_trait;
 use futures::StreamExt;
+use serde::{Deserialize, Serialize};
 
 // use async_openai::config::OpenA/IConfig;
 use async_openai::{
@@ -16,12 +18,119 @@ use async_openai::{
     Client,
 };
 
-// use agent_schema::Message;
+use agent_schema::{ChatMessage, ChatRole};
 use crate::llmbase::LLMBase;
+use crate::prompt_log::record_prompt;
+use crate::tokens::{context_window, DEFAULT_COMPLETION_TOKENS};
 
-#[derive(Debug)]
+/// Per-role model settings, unset fields fall back to the defaults of `set_provider`, then to
+/// the API defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LLMSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u16>,
+}
+
+impl LLMSettings {
+    pub fn model(&self) -> String {
+        self.model
+            .clone()
+            .or_else(|| provider().defaults.model.clone())
+            .unwrap_or_else(|| "gpt-3.5-turbo".to_string())
+    }
+
+    pub fn temperature(&self) -> Option<f32> {
+        self.temperature.or(provider().defaults.temperature)
+    }
+
+    pub fn max_tokens(&self) -> Option<u16> {
+        self.max_tokens.or(provider().defaults.max_tokens)
+    }
+
+    /// Tokens of the model's context window, see `context_window`.
+    pub fn context_window(&self) -> usize {
+        context_window(&self.model())
+    }
+
+    /// Tokens of the window kept for the answer.
+    pub fn completion_tokens(&self) -> usize {
+        self.max_tokens().map_or(DEFAULT_COMPLETION_TOKENS, usize::from)
+    }
+}
+
+/// The endpoint every client talks to, and the model settings of the roles without their own.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProviderSettings {
+    pub api_key: Option<String>,
+    /// OpenAI compatible endpoint, e.g. a local Ollama.
+    pub api_base: Option<String>,
+    pub defaults: LLMSettings,
+}
+
+impl ProviderSettings {
+    /// `OPENAI_API_KEY`, `OPENAI_API_BASE`, `OPENAI_API_MODEL`, `TEMPERATURE` and `MAX_TOKENS`,
+    /// for the programs that do not configure the provider.
+    fn from_env() -> Self {
+        let var = |key: &str| std::env::var(key).ok().filter(|value| !value.is_empty());
+        Self {
+            api_key: var("OPENAI_API_KEY"),
+            api_base: var("OPENAI_API_BASE"),
+            defaults: LLMSettings {
+                model: var("OPENAI_API_MODEL"),
+                temperature: var("TEMPERATURE").and_then(|value| value.parse().ok()),
+                max_tokens: var("MAX_TOKENS").and_then(|value| value.parse().ok()),
+            },
+        }
+    }
+}
+
+/// Set by `set_provider`, or read from the environment when a client needs it first.
+static PROVIDER: OnceLock<ProviderSettings> = OnceLock::new();
+
+/// Use `settings` for every client, to be called at startup with the resolved configuration
+/// before any client is created.
+pub fn set_provider(settings: ProviderSettings) {
+    if PROVIDER.set(settings).is_err() {
+        warn!("the provider was already configured, ignoring the new settings");
+    }
+}
+
+fn provider() -> &'static ProviderSettings {
+    PROVIDER.get_or_init(ProviderSettings::from_env)
+}
+
+/// The client configuration of the provider, for the chat and the embeddings clients.
+pub fn openai_config() -> OpenAIConfig {
+    let provider = provider();
+    let mut config = OpenAIConfig::new();
+    if let Some(api_key) = &provider.api_key {
+        config = config.with_api_key(api_key);
+    }
+    if let Some(api_base) = &provider.api_base {
+        info!("[OLLAMA DEBUG] Configuring client with custom base URL: {}", api_base);
+        config = config.with_api_base(api_base);
+    }
+    config
+}
+
+/// The request message of a `ChatMessage`, e.g. `chat_request_message(SystemMessage::new(..).into())`.
+pub fn chat_request_message(message: ChatMessage) -> ChatCompletionRequestMessage {
+    let role = match message.role {
+        ChatRole::System => Role::System,
+        ChatRole::User => Role::User,
+        ChatRole::Assistant => Role::Assistant,
+    };
+    ChatCompletionRequestMessage { role, content: Some(message.content), name: None, function_call: None }
+}
+
+#[derive(Debug, Clone)]
 pub struct OpenAIGPTAPI {
     client: Client<OpenAIConfig>,
+    settings: LLMSettings,
 }
 #[async_trait]
 impl LLMBase for OpenAIGPTAPI {
@@ -47,33 +156,39 @@ impl Default for OpenAIGPTAPI {
 
 impl OpenAIGPTAPI {
     pub fn new() -> Self {
-        // Configure client to use custom base URL if OPENAI_API_BASE is set
-        let config = if let Ok(base_url) = std::env::var("OPENAI_API_BASE") {
-            if !base_url.is_empty() {
-                info!("[OLLAMA DEBUG] Configuring client with custom base URL: {}", base_url);
-                OpenAIConfig::new().with_api_base(base_url)
-            } else {
-                info!("[OLLAMA DEBUG] Using default OpenAI configuration");
-                OpenAIConfig::new()
-            }
-        } else {
-            info!("[OLLAMA DEBUG] OPENAI_API_BASE not set, using default OpenAI configuration");
-            OpenAIConfig::new()
-        };
-        
-        let client = Client::with_config(config);
-        Self { client }
+        Self { client: Client::with_config(openai_config()), settings: LLMSettings::default() }
+    }
+
+    pub fn with_settings(settings: LLMSettings) -> Self {
+        Self { settings, ..Self::new() }
+    }
+
+    fn _request_args(&self) -> CreateChatCompletionRequestArgs {
+        let mut args = CreateChatCompletionRequestArgs::default();
+        if let Some(temperature) = self.settings.temperature() {
+            args.temperature(temperature);
+        }
+        if let Some(max_tokens) = self.settings.max_tokens() {
+            args.max_tokens(max_tokens);
+        }
+        args
     }
 
     pub async fn aask_with_role(
         &self,
         messages: Vec<ChatCompletionRequestMessage>,
     ) -> Result<String, Box<dyn Error>> {
-        let model = std::env::var("OPENAI_API_MODEL").unwrap_or("gpt-3.5-turbo".to_string());
+        let prompt = messages
+            .iter()
+            .map(|msg| format!("{:?}: {}", msg.role, msg.content.as_deref().unwrap_or_default()))
+            .collect::<Vec<String>>()
+            .join("\n\n");
+        record_prompt(&prompt);
+        let model = self.settings.model();
         info!("[OLLAMA DEBUG] Using model: {}", model);
-        info!("[OLLAMA DEBUG] API Base: {:?}", std::env::var("OPENAI_API_BASE"));
+        info!("[OLLAMA DEBUG] API Base: {:?}", provider().api_base);
         
-        let request = CreateChatCompletionRequestArgs::default()
+        let request = self._request_args()
             .model(model)
             // .max_tokens(4096u16)
             .messages(messages)
@@ -124,12 +239,18 @@ impl OpenAIGPTAPI {
         Ok(rsp)
     }
 
+    /// Ask with a conversation, e.g. a `SystemMessage` then a `UserMessage`.
+    pub async fn aask_chat(&self, messages: Vec<ChatMessage>) -> Result<String, Box<dyn Error>> {
+        self.aask_with_role(messages.into_iter().map(chat_request_message).collect()).await
+    }
+
     pub async fn aask(&self, content: &str) -> Result<String, Box<dyn Error>> {
-        let model = std::env::var("OPENAI_API_MODEL").unwrap_or("gpt-3.5-turbo".to_string());
+        record_prompt(content);
+        let model = self.settings.model();
         info!("[OLLAMA DEBUG] aask - Using model: {}", model);
         info!("[OLLAMA DEBUG] aask - Prompt length: {} chars", content.len());
         
-        let request = CreateChatCompletionRequestArgs::default()
+        let request = self._request_args()
             .model(model)
             // .max_tokens(4096u16)
             .messages([ChatCompletionRequestMessageArgs::default()
@@ -183,3 +304,23 @@ impl OpenAIGPTAPI {
         Ok(rsp)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agent_schema::{AIMessage, SystemMessage, UserMessage};
+
+    #[test]
+    fn test_chat_request_message() {
+        let messages: Vec<ChatCompletionRequestMessage> = vec![
+            SystemMessage::new("You are an engineer").into(),
+            UserMessage::new("Write snake.py").into(),
+            AIMessage::new("Done").into(),
+        ]
+        .into_iter()
+        .map(chat_request_message)
+        .collect();
+        assert_eq!(messages.iter().map(|msg| msg.role.clone()).collect::<Vec<Role>>(), vec![Role::System, Role::User, Role::Assistant]);
+        assert_eq!(messages[1].content.as_deref(), Some("Write snake.py"));
+    }
+}
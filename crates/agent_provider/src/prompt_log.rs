@@ -0,0 +1,46 @@
+use std::cell::RefCell;
+use std::future::Future;
+
+tokio::task_local! {
+    static PROMPTS: RefCell<Vec<String>>;
+}
+
+/// Run `future` and collect the prompts sent to the LLM while it runs, e.g. to record
+/// which prompts produced a role's message. Futures polled concurrently next to it,
+/// even on the same task, keep their own prompts.
+pub async fn capture_prompts<F: Future>(future: F) -> (F::Output, Vec<String>) {
+    PROMPTS
+        .scope(RefCell::new(vec![]), async move {
+            let output = future.await;
+            let prompts = PROMPTS.with(|prompts| prompts.take());
+            (output, prompts)
+        })
+        .await
+}
+
+/// Record a prompt in the enclosing `capture_prompts`, if any.
+pub fn record_prompt(prompt: &str) {
+    let _ = PROMPTS.try_with(|prompts| prompts.borrow_mut().push(prompt.to_string()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_capture_prompts() {
+        record_prompt("outside any capture");
+        let ((), first) = capture_prompts(async { record_prompt("a") }).await;
+        let (((), second), ((), third)) = futures::join!(
+            capture_prompts(async {
+                record_prompt("b");
+                tokio::task::yield_now().await;
+                record_prompt("c");
+            }),
+            capture_prompts(async { record_prompt("d") }),
+        );
+        assert_eq!(first, vec!["a"]);
+        assert_eq!(second, vec!["b", "c"]);
+        assert_eq!(third, vec!["d"]);
+    }
+}
@@ -0,0 +1,75 @@
+/// Context window of models the name does not tell, the smallest of the OpenAI chat models.
+pub const DEFAULT_CONTEXT_WINDOW: usize = 4096;
+
+/// Tokens kept for the answer when `max_tokens` is not set.
+pub const DEFAULT_COMPLETION_TOKENS: usize = 1024;
+
+/// Tokens of the context window of `model`, prompt and completion together.
+pub fn context_window(model: &str) -> usize {
+    let model = model.to_lowercase();
+    // the most specific names first, e.g. gpt-4-32k before gpt-4
+    const WINDOWS: &[(&str, usize)] = &[
+        ("gpt-4o", 128_000),
+        ("gpt-4-turbo", 128_000),
+        ("gpt-4-1106", 128_000),
+        ("gpt-4-0125", 128_000),
+        ("gpt-4-32k", 32_768),
+        ("gpt-4", 8_192),
+        ("gpt-3.5-turbo-16k", 16_385),
+        ("gpt-3.5-turbo-1106", 16_385),
+        ("gpt-3.5-turbo-0125", 16_385),
+        ("gpt-3.5-turbo", 4_096),
+        ("claude", 200_000),
+        ("llama3", 8_192),
+        ("llama2", 4_096),
+        ("mistral", 32_768),
+        ("mixtral", 32_768),
+        ("qwen", 32_768),
+    ];
+    WINDOWS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, window)| *window)
+        .unwrap_or(DEFAULT_CONTEXT_WINDOW)
+}
+
+/// Rough token count of `text`: a token is about four characters of English or code,
+/// and each word or symbol run is at least one. Chinese, Japanese and Korean characters
+/// are a token each.
+pub fn count_tokens(text: &str) -> usize {
+    let cjk = text.chars().filter(|c| is_cjk(*c)).count();
+    let chars = (text.chars().count() - cjk + 3) / 4;
+    let words = text.split_whitespace().filter(|word| !word.chars().all(is_cjk)).count();
+    cjk + chars.max(words)
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3000}'..='\u{30ff}' // punctuation, hiragana, katakana
+            | '\u{3400}'..='\u{4dbf}'
+            | '\u{4e00}'..='\u{9fff}'
+            | '\u{ac00}'..='\u{d7af}' // hangul
+            | '\u{f900}'..='\u{faff}'
+            | '\u{ff00}'..='\u{ffef}' // full width forms
+            | '\u{20000}'..='\u{2ffff}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_window() {
+        assert_eq!(context_window("gpt-4-32k-0613"), 32_768);
+        assert_eq!(context_window("gpt-4"), 8_192);
+        assert_eq!(context_window("GPT-4o-mini"), 128_000);
+        assert_eq!(context_window("my-local-model"), DEFAULT_CONTEXT_WINDOW);
+        assert_eq!(count_tokens(""), 0);
+        assert_eq!(count_tokens("a b c"), 3);
+        assert_eq!(count_tokens("snake_game_controller"), 6);
+        assert_eq!(count_tokens("贪吃蛇游戏"), 5);
+        assert_eq!(count_tokens("写一个 snake 游戏。"), 8);
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::HashSet;
+use std::sync::{Mutex, Arc, MutexGuard};
+
+use agent_macro::RoleMacro;
+use agent_schema::Message;
+use async_trait::async_trait;
+use tracing::debug;
+
+use agent_memory::Memory;
+use agent_actions::{Action, WriteApiSpec};
+use agent_provider::LLM;
+
+use crate::role::{Role, RoleContext, RoleSetting};
+
+/// Turns the architect's design into an OpenAPI contract for the engineer and QA.
+#[derive(RoleMacro)]
+pub struct ApiDesigner {
+    _llm: Arc<Mutex<LLM>>,
+    _setting:  RoleSetting,
+    _states: Vec<String>,
+    _actions: Vec<Box<dyn Action>>,
+    _rc: RoleContext,
+}
+
+impl ApiDesigner {
+    pub fn new(name: &str, profile: &str, goal: &str,  constraints: &str, desc: &str) -> Self {
+        let setting = RoleSetting::new(name, profile, goal, constraints, desc);
+
+        let llm = Arc::new(Mutex::new(LLM::new()));
+
+        let mut action = WriteApiSpec::new(name, profile, &setting.get_prefix(), profile, llm.clone());
+        action.set_prefix(&setting.get_prefix(), profile);
+        Self {
+            _llm: llm,
+            _setting: setting,
+            _states: vec![],
+            _actions: vec![Box::new(action)],
+            _rc: RoleContext::new(HashSet::from(["WriteDesign".to_string()])),
+        }
+    }
+
+    pub fn default() -> Self {
+        let name = "Carol";
+        let profile = "API Designer";
+        let goal = "Write a precise, valid OpenAPI contract that matches the system design";
+        let desc = "";
+        let constraints = "Only describe endpoints and schemas that exist in the design";
+        ApiDesigner::new(name, profile, goal, constraints, desc)
+    }
+
+    fn _before_action(&self, env_msgs: &Vec<Message>,  _role_msgs: &Vec<Message>) {
+        debug!(" {:?}", env_msgs);
+    }
+
+    fn _after_action(&self, message: Message) -> Message {
+        message
+    }
+}
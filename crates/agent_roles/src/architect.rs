@@ -41,7 +41,8 @@ impl Architect {
             _setting: setting,
             _states: vec![],
             _actions: vec![Box::new(action)],
-            _rc: RoleContext::new(HashSet::from(["WritePRD".to_string()])),
+            _rc: RoleContext::new(HashSet::from(["WritePRD".to_string()]))
+                .with_context(HashSet::from(["RepositorySummary".to_string()])),
         }
     }
 
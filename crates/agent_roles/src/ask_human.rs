@@ -0,0 +1,93 @@
+use std::io::{self, BufRead, Write};
+
+use async_trait::async_trait;
+
+/// The decision a human takes on an artifact produced by a gated action.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HumanFeedback {
+    /// Keep the artifact as it is.
+    Approve,
+    /// Replace the artifact with the edited content.
+    Edit(String),
+    /// Run the action again, taking the feedback into account.
+    Reject(String),
+    /// Nobody answered, e.g. stdin was closed: the artifact is held back.
+    Unanswered,
+}
+
+/// Approval gate between pipeline stages: the role pauses until `review` returns.
+#[async_trait]
+pub trait AskHuman: Send + Sync {
+    async fn review(&self, profile: &str, action: &str, artifact: &str) -> HumanFeedback;
+}
+
+/// Ask for approval on the terminal.
+#[derive(Debug, Default)]
+pub struct StdinAskHuman;
+
+impl StdinAskHuman {
+    /// The next line, `None` once stdin is closed or unreadable.
+    fn read_line(stdin: &mut impl BufRead) -> Option<String> {
+        let mut line = String::new();
+        match stdin.read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim().to_string()),
+        }
+    }
+
+    /// Read lines until a line containing only `EOF`.
+    fn read_block(stdin: &mut impl BufRead) -> String {
+        let mut block = Vec::new();
+        for line in stdin.lines() {
+            let Ok(line) = line else { break };
+            if line.trim() == "EOF" {
+                break;
+            }
+            block.push(line);
+        }
+        block.join("\n")
+    }
+}
+
+impl StdinAskHuman {
+    /// Ask on the terminal until an answer is read or stdin is closed.
+    fn ask(profile: &str, action: &str, artifact: &str) -> HumanFeedback {
+        let mut stdin = io::stdin().lock();
+        println!("\n==================== 【{}】{} ====================", profile, action);
+        println!("{}", artifact);
+        println!("====================================================");
+        loop {
+            print!("[a]pprove, [e]dit or [r]eject with feedback? ");
+            let _ = io::stdout().flush();
+            // nobody is left to approve once stdin is closed
+            let Some(answer) = Self::read_line(&mut stdin) else { return HumanFeedback::Unanswered };
+            match answer.to_lowercase().as_str() {
+                "a" | "approve" => return HumanFeedback::Approve,
+                "e" | "edit" => {
+                    println!("Enter the new content, finish with a line containing only EOF:");
+                    return HumanFeedback::Edit(Self::read_block(&mut stdin));
+                }
+                "r" | "reject" => {
+                    print!("Feedback: ");
+                    let _ = io::stdout().flush();
+                    return match Self::read_line(&mut stdin) {
+                        Some(feedback) => HumanFeedback::Reject(feedback),
+                        None => HumanFeedback::Unanswered,
+                    };
+                }
+                _ => continue,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl AskHuman for StdinAskHuman {
+    async fn review(&self, profile: &str, action: &str, artifact: &str) -> HumanFeedback {
+        // reading stdin blocks, keep it off the runtime's workers running the other roles
+        let (profile, action, artifact) = (profile.to_string(), action.to_string(), artifact.to_string());
+        tokio::task::spawn_blocking(move || Self::ask(&profile, &action, &artifact))
+            .await
+            .unwrap_or(HumanFeedback::Unanswered)
+    }
+}
@@ -0,0 +1,223 @@
+use agent_provider::{count_tokens, LLMSettings};
+use agent_schema::Message;
+
+use crate::template::summary_template;
+
+/// `cause_by` of the rolling summaries a role keeps in its memory. Their
+/// `instruct_content` is the id of the last message they cover.
+pub const CONTEXT_SUMMARY: &str = "ContextSummary";
+
+/// `cause_by` of the requirement, the latest one is never summarized.
+const REQUIREMENT: &str = "BossRequirement";
+
+/// Tokens counted for the role and separators of each message.
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// Characters of each message kept when the LLM gives no summary.
+const EXCERPT_CHARS: usize = 200;
+
+fn message_tokens(message: &Message) -> usize {
+    count_tokens(&message.content) + MESSAGE_OVERHEAD
+}
+
+/// Cut `text` to about `tokens` tokens.
+fn truncate_tokens(text: &str, tokens: usize) -> String {
+    if count_tokens(text) <= tokens {
+        return text.to_string();
+    }
+    match text.char_indices().nth(tokens.saturating_sub(4) * 4) {
+        Some((end, _)) => format!("{}\n[... truncated]", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// How many tokens of memory a role puts in a prompt. Messages that do not fit are
+/// folded into a rolling summary, see `Role::_compact_memory`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextWindow {
+    pub budget: usize,
+}
+
+impl ContextWindow {
+    pub fn new(budget: usize) -> Self {
+        Self { budget }
+    }
+
+    /// Half of what the model's window leaves after the answer, the other half being for
+    /// the action's own prompt.
+    pub fn for_llm(settings: &LLMSettings) -> Self {
+        Self::new(settings.context_window().saturating_sub(settings.completion_tokens()) / 2)
+    }
+
+    /// Tokens a summary may take.
+    fn summary_budget(&self) -> usize {
+        self.budget / 4
+    }
+
+    /// Split the memory into the latest requirement, the recent messages kept verbatim and
+    /// the older ones, covered by the latest summary or still to be folded into it.
+    /// Everything is kept when it fits. The messages remembered from earlier runs come
+    /// before the current requirement in memory, they are kept behind it.
+    pub fn compact(&self, memory: &[Message]) -> Compaction {
+        let summary = memory.iter().rev().find(|msg| msg.cause_by == CONTEXT_SUMMARY).cloned();
+        let mut messages: Vec<&Message> = memory.iter().filter(|msg| msg.cause_by != CONTEXT_SUMMARY).collect();
+        let requirement = messages.iter().rposition(|msg| msg.cause_by == REQUIREMENT).map(|idx| messages.remove(idx).clone());
+        let tokens = requirement.as_ref().map_or(0, message_tokens) + messages.iter().map(|msg| message_tokens(msg)).sum::<usize>();
+        if tokens <= self.budget {
+            return Compaction { requirement, recent: messages.into_iter().cloned().collect(), ..Default::default() };
+        }
+
+        let requirement = requirement.map(|mut message| {
+            message.content = truncate_tokens(&message.content, self.summary_budget());
+            message
+        });
+        let mut available = self
+            .budget
+            .saturating_sub(requirement.as_ref().map_or(0, message_tokens))
+            .saturating_sub(self.summary_budget());
+
+        // the newest messages verbatim, at least the last one even if it must be cut
+        let mut recent = vec![];
+        let mut boundary = messages.len();
+        for (idx, message) in messages.iter().enumerate().rev() {
+            let tokens = message_tokens(message);
+            if tokens > available {
+                if recent.is_empty() {
+                    let mut message = (*message).clone();
+                    message.content = truncate_tokens(&message.content, available.saturating_sub(MESSAGE_OVERHEAD));
+                    recent.push(message);
+                    boundary = idx;
+                }
+                break;
+            }
+            available -= tokens;
+            recent.push((*message).clone());
+            boundary = idx;
+        }
+        recent.reverse();
+
+        let covered = summary
+            .as_ref()
+            .and_then(|summary| summary.instruct_content.as_deref())
+            .and_then(|id| messages.iter().position(|msg| msg.id == id));
+        let folded = messages[..boundary]
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| covered.map_or(true, |covered| *idx > covered))
+            .map(|(_, msg)| (*msg).clone())
+            .collect();
+        Compaction { requirement, summary, folded, recent, summary_budget: self.summary_budget() }
+    }
+}
+
+/// The memory of a role fitted into its `ContextWindow`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Compaction {
+    /// The latest requirement.
+    pub requirement: Option<Message>,
+    /// The latest summary.
+    pub summary: Option<Message>,
+    /// Older messages the summary does not cover yet.
+    pub folded: Vec<Message>,
+    /// The newest messages, verbatim.
+    pub recent: Vec<Message>,
+    summary_budget: usize,
+}
+
+impl Compaction {
+    /// The prompt asking for a summary covering the folded messages, `None` when the
+    /// latest summary is up to date.
+    pub fn summary_prompt(&self, prefix: &str) -> Option<String> {
+        if self.folded.is_empty() {
+            return None;
+        }
+        let summary = self.summary.as_ref().map_or("(none)", |summary| summary.content.as_str());
+        let messages = self
+            .folded
+            .iter()
+            .map(|msg| format!("{} ({}): {}", msg.role, msg.cause_by, msg.content))
+            .collect::<Vec<String>>()
+            .join("\n\n");
+        // about three quarters of a word per token
+        Some(summary_template(prefix, summary, &messages, self.summary_budget * 3 / 4))
+    }
+
+    /// The summary covering the folded messages, from the LLM's `answer` or, when it is
+    /// empty, from excerpts of the messages.
+    pub fn summarize(&self, profile: &str, answer: &str) -> Message {
+        let content = if answer.trim().is_empty() {
+            let mut excerpts: Vec<String> = self.summary.iter().map(|summary| summary.content.clone()).collect();
+            excerpts.extend(self.folded.iter().map(|msg| {
+                let excerpt: String = msg.content.chars().take(EXCERPT_CHARS).collect();
+                format!("{} ({}): {}", msg.role, msg.cause_by, excerpt)
+            }));
+            excerpts.join("\n")
+        } else {
+            answer.trim().to_string()
+        };
+        Message {
+            content: truncate_tokens(&content, self.summary_budget),
+            role: profile.to_string(),
+            cause_by: CONTEXT_SUMMARY.to_string(),
+            instruct_content: self.folded.last().map(|msg| msg.id.clone()),
+            ..Default::default()
+        }
+    }
+
+    /// The messages to act on: the requirement, the summary, then the recent messages.
+    pub fn into_messages(self, summary: Option<Message>) -> Vec<Message> {
+        self.requirement.into_iter().chain(summary.or(self.summary)).chain(self.recent).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `words` tokens of content.
+    fn message(cause_by: &str, words: usize) -> Message {
+        Message { content: vec!["ab"; words].join(" "), role: "Boss".into(), cause_by: cause_by.into(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_compaction() {
+        let window = ContextWindow::new(400);
+        let mut memory = vec![message("BossRequirement", 20), message("WritePRD", 100), message("WriteDesign", 100)];
+        memory.push(message("WriteTasks", 100));
+        let compaction = window.compact(&memory);
+        assert!(compaction.summary_prompt("").is_none());
+        assert_eq!(compaction.into_messages(None), memory);
+
+        memory.push(message("WriteCode", 100));
+        let compaction = window.compact(&memory);
+        assert_eq!(compaction.requirement.as_ref(), Some(&memory[0]));
+        assert_eq!(compaction.folded, memory[1..3].to_vec());
+        assert_eq!(compaction.recent, memory[3..].to_vec());
+        assert!(compaction.summary_prompt("").unwrap().contains("Boss (WritePRD): ab ab"));
+        let summary = compaction.summarize("Engineer", "");
+        assert!(summary.content.starts_with("Boss (WritePRD)"));
+        assert_eq!(summary.instruct_content.as_deref(), Some(memory[2].id.as_str()));
+
+        // the summary is kept in memory and only the newly folded messages are added to it
+        memory.push(summary);
+        memory.push(message("WriteCode", 100));
+        let compaction = window.compact(&memory);
+        assert_eq!(compaction.folded, vec![memory[3].clone()]);
+        let next = compaction.summarize("Engineer", "The PRD and the design are done");
+        let messages = compaction.into_messages(Some(next.clone()));
+        assert_eq!(messages, vec![memory[0].clone(), next, memory[4].clone(), memory[6].clone()]);
+    }
+
+    #[test]
+    fn test_latest_requirement_first() {
+        // an earlier run of the project remembered ahead of the current requirement
+        let memory = vec![message("BossRequirement", 40), message("WritePRD", 60), message("BossRequirement", 20)];
+        let messages = ContextWindow::new(400).compact(&memory).into_messages(None);
+        assert_eq!(messages, vec![memory[2].clone(), memory[0].clone(), memory[1].clone()]);
+
+        let compaction = ContextWindow::new(120).compact(&memory);
+        assert_eq!(compaction.requirement.as_ref(), Some(&memory[2]));
+        assert_eq!(compaction.folded, vec![memory[0].clone()]);
+        assert_eq!(compaction.recent, vec![memory[1].clone()]);
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::{Mutex, Arc, MutexGuard};
+
+use anyhow::{anyhow, bail, Context};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use agent_actions::{create_action, Action, ACTION_NAMES};
+use agent_macro::RoleMacro;
+use agent_memory::Memory;
+use agent_provider::{LLMSettings, LLM};
+use agent_schema::Message;
+
+use crate::context_window::ContextWindow;
+use crate::role::{Role, RoleContext, RoleSetting};
+
+/// A role as written in YAML:
+///
+/// ```yaml
+/// roles:
+///   - name: Alice
+///     profile: Product Manager
+///     goal: Efficiently create a successful product
+///     actions: [WritePRD]
+///     watch: [BossRequirement]
+///     context: [RepositorySummary]
+///     reflection: 2
+///     llm:
+///       model: gpt-4
+///       temperature: 0.2
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoleDefinition {
+    pub name: String,
+    pub profile: String,
+    #[serde(default)]
+    pub goal: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub constraints: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub desc: String,
+    /// Registered action names, see `agent_actions::ACTION_NAMES`.
+    pub actions: Vec<String>,
+    /// `cause_by` values that trigger the role.
+    pub watch: Vec<String>,
+    /// `cause_by` values read as background context only.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub llm: LLMSettings,
+    /// Critique and revise rounds after each action, 0 disables reflection.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub reflection: usize,
+}
+
+#[derive(Deserialize)]
+struct RoleDefinitions {
+    roles: Vec<RoleDefinition>,
+}
+
+impl RoleDefinition {
+    /// Parse the `roles:` list of a YAML document.
+    pub fn from_yaml(yaml: &str) -> anyhow::Result<Vec<RoleDefinition>> {
+        let definitions: RoleDefinitions = serde_yaml::from_str(yaml)?;
+        for definition in &definitions.roles {
+            definition.validate()?;
+        }
+        Ok(definitions.roles)
+    }
+
+    pub fn from_file(path: &Path) -> anyhow::Result<Vec<RoleDefinition>> {
+        let yaml = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Self::from_yaml(&yaml).with_context(|| format!("invalid role definitions in {}", path.display()))
+    }
+
+    pub(crate) fn validate(&self) -> anyhow::Result<()> {
+        if self.actions.is_empty() {
+            bail!("role {} has no actions", self.name);
+        }
+        if self.watch.is_empty() {
+            bail!("role {} watches nothing and would never run", self.name);
+        }
+        for action in &self.actions {
+            if !ACTION_NAMES.contains(&action.as_str()) {
+                bail!("role {}: unknown action {}, expected one of {:?}", self.name, action, ACTION_NAMES);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+/// A role instantiated from a `RoleDefinition` instead of a dedicated struct.
+#[derive(RoleMacro)]
+pub struct DeclarativeRole {
+    _llm: Arc<Mutex<LLM>>,
+    _setting:  RoleSetting,
+    _states: Vec<String>,
+    _actions: Vec<Box<dyn Action>>,
+    _rc: RoleContext,
+}
+
+impl DeclarativeRole {
+    pub fn new(definition: &RoleDefinition) -> anyhow::Result<Self> {
+        definition.validate()?;
+        let setting = RoleSetting::new(&definition.name, &definition.profile, &definition.goal, &definition.constraints, &definition.desc);
+        let llm = Arc::new(Mutex::new(LLM::with_settings(definition.llm.clone())));
+
+        let mut actions = vec![];
+        for name in &definition.actions {
+            let mut action = create_action(name, &setting.name, &setting.profile, &setting.get_prefix(), llm.clone())
+                .ok_or_else(|| anyhow!("unknown action {}", name))?;
+            action.set_prefix(&setting.get_prefix(), &setting.profile);
+            action.set_llm(Box::new(LLM::with_settings(definition.llm.clone())));
+            actions.push(action);
+        }
+        Ok(Self {
+            _llm: llm,
+            _setting: setting,
+            _states: definition.actions.iter().enumerate().map(|(idx, name)| format!("{}. {}", idx, name)).collect(),
+            _actions: actions,
+            _rc: RoleContext::new(definition.watch.iter().cloned().collect())
+                .with_context(definition.context.iter().cloned().collect())
+                .with_reflection(definition.reflection)
+                .with_context_window(ContextWindow::for_llm(&definition.llm)),
+        })
+    }
+
+    fn _before_action(&self, _env_msgs: &Vec<Message>,  _role_msgs: &Vec<Message>) -> String {
+        String::new()
+    }
+    fn _after_action(&self, message: Message) -> Message {
+        message
+    }
+}
+
+/// Load every role of a YAML file, ready for `SoftwareCompany::hire`.
+pub fn load_roles(path: &Path) -> anyhow::Result<Vec<Box<dyn Role>>> {
+    RoleDefinition::from_file(path)?
+        .iter()
+        .map(|definition| DeclarativeRole::new(definition).map(|role| Box::new(role) as Box<dyn Role>))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROLES: &str = r#"
+roles:
+  - name: Alice
+    profile: Product Manager
+    goal: Efficiently create a successful product
+    actions: [WritePRD]
+    watch: [BossRequirement]
+    llm:
+      model: gpt-4
+      temperature: 0.2
+  - name: Bob
+    profile: Architect
+    actions: [WriteDesign]
+    watch: [WritePRD]
+    context: [RepositorySummary]
+"#;
+
+    #[test]
+    fn test_role_definitions_from_yaml() {
+        let definitions = RoleDefinition::from_yaml(ROLES).unwrap();
+        assert_eq!(definitions.len(), 2);
+        assert_eq!(definitions[0].llm.model.as_deref(), Some("gpt-4"));
+        assert_eq!(definitions[1].context, vec!["RepositorySummary"]);
+
+        let role = DeclarativeRole::new(&definitions[1]).unwrap();
+        assert_eq!(role._get_profile(), "Architect");
+        assert_eq!(role._get_action_by_state(0).unwrap().name(), "WriteDesign");
+    }
+
+    #[test]
+    fn test_example_roles() {
+        let definitions = RoleDefinition::from_yaml(include_str!("../../../examples/roles.yaml")).unwrap();
+        assert_eq!(definitions.len(), 5);
+    }
+
+    #[test]
+    fn test_unknown_action() {
+        let yaml = ROLES.replace("[WriteDesign]", "[WriteNovel]");
+        let error = RoleDefinition::from_yaml(&yaml).unwrap_err();
+        assert!(error.to_string().contains("unknown action WriteNovel"));
+    }
+}
@@ -41,7 +41,8 @@ impl Engineer {
             _setting: setting,
             _states: vec![],
             _actions: vec![Box::new(action)],
-            _rc: RoleContext::new(HashSet::from(["WriteTasks".to_string()])),
+            _rc: RoleContext::new(HashSet::from(["WriteTasks".to_string()]))
+                .with_context(HashSet::from(["RepositorySummary".to_string(), "WriteApiSpec".to_string(), "WriteDesign".to_string()])),
         }
     }
 
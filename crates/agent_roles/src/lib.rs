@@ -1,21 +1,33 @@
 
 mod role;
+mod ask_human;
+mod context_window;
 mod template;
 mod product_manager;
 mod architect;
+mod api_designer;
 mod project_manager;
 mod engineer;
 mod qa_engineer;
 mod searcher;
 mod role_builder;
 mod research_agent;
+mod declarative_role;
+mod react_role;
+mod team_plan;
 
 pub use role::{Role, RoleContext, RoleSetting};
+pub use ask_human::{AskHuman, HumanFeedback, StdinAskHuman};
+pub use context_window::{Compaction, ContextWindow, CONTEXT_SUMMARY};
 pub use product_manager::ProductManager;
 pub use architect::Architect;
+pub use api_designer::ApiDesigner;
 pub use project_manager::ProjectManager;
 pub use engineer::Engineer;
 pub use qa_engineer::QaEngineer;
 pub use searcher::Searcher;
 pub use role_builder::AgentRoleBuilder;
-pub use research_agent::ResearchAgent;
\ No newline at end of file
+pub use research_agent::{PageFetcher, ResearchAgent, WebFetcher, RESEARCH_REPORT};
+pub use declarative_role::{DeclarativeRole, RoleDefinition, load_roles};
+pub use react_role::{ReActRole, REACT_ANSWER, REACT_STEP};
+pub use team_plan::{TeamPlan, builtin_catalog, COMPANY_MESSAGES, MAX_TEAM_ROUNDS};
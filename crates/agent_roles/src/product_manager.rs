@@ -36,7 +36,8 @@ impl ProductManager {
             _setting: setting,
             _states: vec![],
             _actions: vec![Box::new(action)],
-            _rc: RoleContext::new(HashSet::from(["BossRequirement".to_string()])),
+            _rc: RoleContext::new(HashSet::from(["BossRequirement".to_string()]))
+                .with_context(HashSet::from(["RepositorySummary".to_string()])),
         }
     }
     pub fn default() -> Self {
@@ -37,7 +37,8 @@ impl ProjectManager {
             _setting: setting,
             _states: vec![],
             _actions: vec![Box::new(action)],
-            _rc: RoleContext::new(HashSet::from(["WriteDesign".to_string()])),
+            _rc: RoleContext::new(HashSet::from(["WriteDesign".to_string()]))
+                .with_context(HashSet::from(["RepositorySummary".to_string()])),
         }
     }
 
@@ -6,12 +6,13 @@ use async_trait::async_trait;
 use tracing::{debug};
 
 use agent_memory::Memory;
-use agent_actions::{Action, WritePRD};
+use agent_actions::{Action, WriteTests};
 use agent_provider::LLM;
 use agent_macro::RoleMacro;
 
 use crate::role::{Role, RoleContext, RoleSetting};
 
+/// Writes tests against the API contract of the `ApiDesigner`.
 #[derive(RoleMacro)]
 pub struct QaEngineer {
     _llm: Arc<Mutex<LLM>>,
@@ -25,15 +26,15 @@ impl QaEngineer {
     pub fn new(name: &str, profile: &str, goal: &str,  constraints: &str, desc: &str) -> Self {
         let setting = RoleSetting::new(name, profile, goal, constraints, desc);
         let llm = Arc::new(Mutex::new(LLM::new()));
-        let mut action = WritePRD::new(name, profile,&setting.get_prefix(), profile, llm.clone());
+        let mut action = WriteTests::new(name, profile, &setting.get_prefix(), profile, llm.clone());
         action.set_prefix(&setting.get_prefix(), profile);
-        // self.new(name, profile, goal, constraints)
         Self {
             _llm: llm,
             _setting: setting,
             _states: vec![],
             _actions: vec![Box::new(action)],
-            _rc: RoleContext::new(HashSet::from(["BossRequirement".to_string()])),
+            _rc: RoleContext::new(HashSet::from(["WriteApiSpec".to_string()]))
+                .with_context(HashSet::from(["WriteDesign".to_string(), "RepositorySummary".to_string()])),
         }
     }
     pub fn default() -> Self {
@@ -0,0 +1,298 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::{Mutex, Arc, MutexGuard};
+
+use async_trait::async_trait;
+use tracing::{debug, info, warn};
+
+use agent_actions::{Action, DEFAULT_WORKSPACE};
+use agent_memory::Memory;
+use agent_provider::LLM;
+use agent_schema::Message;
+use agent_tools::{search_engine, FetchTool, FileOperation, FileTool, SearchTool, Tool};
+
+use crate::ask_human::AskHuman;
+use crate::role::{Role, RoleContext, RoleSetting};
+use crate::template::react_template;
+
+/// `cause_by` of the thought / tool call / observation messages kept in the role's memory.
+pub const REACT_STEP: &str = "ReActStep";
+/// `cause_by` of the final answer published to the environment.
+pub const REACT_ANSWER: &str = "ReActAnswer";
+
+const DEFAULT_MAX_STEPS: usize = 8;
+
+/// One parsed LLM answer of the ReAct loop.
+#[derive(Debug, PartialEq)]
+enum ReActStep {
+    Call { tool: String, input: String },
+    Final(String),
+}
+
+/// A role that, instead of running one fixed action per turn, loops
+/// thought -> tool call -> observation over its tools until it gives a final answer
+/// or reaches `max_steps`.
+pub struct ReActRole {
+    _llm: Arc<Mutex<LLM>>,
+    _setting: RoleSetting,
+    _states: Vec<String>,
+    _actions: Vec<Box<dyn Action>>,
+    _rc: RoleContext,
+    tools: Vec<Box<dyn Tool>>,
+    max_steps: usize,
+}
+
+impl ReActRole {
+    pub fn new(name: &str, profile: &str, goal: &str, constraints: &str, desc: &str, watch: HashSet<String>, tools: Vec<Box<dyn Tool>>) -> Self {
+        Self {
+            _llm: Arc::new(Mutex::new(LLM::new())),
+            _setting: RoleSetting::new(name, profile, goal, constraints, desc),
+            _states: vec![],
+            _actions: vec![],
+            _rc: RoleContext::new(watch),
+            tools,
+            max_steps: DEFAULT_MAX_STEPS,
+        }
+    }
+
+    /// A researcher answering the boss requirement with web search, page fetching and
+    /// the files of the workspace.
+    pub fn default() -> Self {
+        let name = "Rex";
+        let profile = "Researcher";
+        let goal = "Answer the question accurately using the tools";
+        let desc = "";
+        let constraints = "Cite the pages you used";
+        let tools: Vec<Box<dyn Tool>> = vec![
+            Box::new(SearchTool::new(search_engine())),
+            Box::new(FetchTool),
+            Box::new(FileTool::new(DEFAULT_WORKSPACE, FileOperation::Read)),
+            Box::new(FileTool::new(DEFAULT_WORKSPACE, FileOperation::Write)),
+            Box::new(FileTool::new(DEFAULT_WORKSPACE, FileOperation::List)),
+        ];
+        ReActRole::new(name, profile, goal, constraints, desc, HashSet::from(["BossRequirement".to_string()]), tools)
+    }
+
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    fn _tool_descriptions(&self) -> String {
+        self.tools
+            .iter()
+            .map(|tool| format!("- {}: {}", tool.name(), tool.description()))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    async fn _call_tool(&self, name: &str, input: &str) -> String {
+        let Some(tool) = self.tools.iter().find(|tool| tool.name() == name) else {
+            let names: Vec<&str> = self.tools.iter().map(|tool| tool.name()).collect();
+            return format!("Unknown tool {}, use one of {:?}", name, names);
+        };
+        match tool.call(input).await {
+            Ok(observation) => observation,
+            Err(e) => format!("{} failed: {}", name, e),
+        }
+    }
+
+    /// Store an intermediate step in the role's memory only.
+    fn _record_step(&self, content: String) -> Message {
+        let msg = Message {
+            content,
+            role: self._setting.profile.clone(),
+            cause_by: REACT_STEP.to_string(),
+            round: self._rc.round(),
+            ..Default::default()
+        };
+        self._get_rc_memory().add(msg.clone());
+        msg
+    }
+}
+
+#[async_trait]
+impl Role for ReActRole {
+    fn set_env_global_memory(&mut self, memory: Arc<Mutex<Memory>>) {
+        self._rc.env_memory = memory
+    }
+
+    fn set_ask_human(&mut self, ask_human: Arc<dyn AskHuman>, actions: HashSet<String>) {
+        self._rc.ask_human = Some(ask_human);
+        self._rc.human_gates = actions;
+    }
+
+    fn _reset(&mut self) {
+        self._states = vec![];
+        self._actions = vec![];
+    }
+
+    fn _init_actions(&mut self, actions: Vec<Box<dyn Action>>) {
+        self._reset();
+        self._actions = actions;
+    }
+
+    fn _watch(&mut self, _actions: Vec<Box<dyn Action>>) {}
+
+    fn _set_state(&mut self, state: i32) {
+        self._rc.set_state(state);
+    }
+
+    async fn _aask(&self, prompt: &str) -> String {
+        if agent_provider::is_llm_fake() {
+            return "Thought: no LLM in fake mode\nFinal Answer: ".to_string();
+        }
+        let llm = { self._llm.lock().unwrap().clone() };
+        llm.aask(prompt).await.unwrap_or_else(|e| {
+            warn!("【{}】LLM request failed: {}", self._setting.profile, e);
+            String::new()
+        })
+    }
+
+    fn _get_profile(&self) -> &str {
+        &self._setting.profile
+    }
+
+    fn _get_prefix(&self) -> String {
+        self._setting.get_prefix()
+    }
+
+    fn _get_states(&self) -> Vec<String> {
+        self._states.clone()
+    }
+
+    fn _get_rc(&self) -> RoleContext {
+        self._rc.clone()
+    }
+
+    fn _get_setting(&self) -> &RoleSetting {
+        &self._setting
+    }
+
+    fn set_reflection(&mut self, max_rounds: usize) {
+        self._rc.reflection_rounds = max_rounds;
+    }
+
+    fn set_workspace(&mut self, workspace: &Path) {
+        for action in self._actions.iter_mut() {
+            action.set_workspace(workspace);
+        }
+        for tool in self.tools.iter_mut() {
+            tool.set_workspace(workspace);
+        }
+    }
+
+    fn _get_rc_env_memory(&self) -> MutexGuard<'_, Memory> {
+        self._rc.env_memory.lock().unwrap()
+    }
+
+    fn _get_rc_memory(&self) -> MutexGuard<'_, Memory> {
+        self._rc.role_memory.lock().unwrap()
+    }
+
+    fn _get_action_by_state(&self, state: usize) -> Option<&Box<dyn Action>> {
+        self._actions.get(state)
+    }
+
+    fn _get_action_count(&self) -> usize {
+        self._actions.len()
+    }
+
+    fn _before_action(&self, _env_msgs: &Vec<Message>, _role_msgs: &Vec<Message>) -> String {
+        String::new()
+    }
+
+    fn _after_action(&self, message: Message) -> Message {
+        message
+    }
+
+    /// Thought -> tool call -> observation until a final answer or the step limit.
+    async fn _react(&self) -> Option<Message> {
+        let rc = self._get_rc();
+        let trigger = rc.trigger().unwrap_or_else(|| {
+            rc.important_memory().into_iter().rev().find(|msg| msg.cause_by != REACT_STEP && msg.cause_by != REACT_ANSWER).unwrap_or_default()
+        });
+        let task = trigger.content.clone();
+        let tools = self._tool_descriptions();
+        let mut scratchpad = String::new();
+        let mut answer = None;
+
+        for step in 0..self.max_steps {
+            self._rc.set_state(step as i32);
+            let prompt = react_template(&self._get_prefix(), &tools, &task, &scratchpad);
+            let response = self._aask(&prompt).await;
+            debug!("【{}】step {}:\n{}", self._get_profile(), step, response);
+            self._record_step(response.trim().to_string());
+
+            let observation = match parse_react_step(&response) {
+                Some(ReActStep::Final(final_answer)) => {
+                    answer = Some(final_answer);
+                    break;
+                }
+                Some(ReActStep::Call { tool, input }) => {
+                    info!("【{}】calling tool {} with {:?}", self._get_profile(), tool, input);
+                    self._call_tool(&tool, &input).await
+                }
+                None => "Invalid format: answer with `Action:` and `Action Input:`, or `Final Answer:`.".to_string(),
+            };
+            self._record_step(format!("Observation: {}", observation));
+            scratchpad.push_str(&format!("{}\nObservation: {}\n\n", response.trim(), observation));
+        }
+
+        let content = answer.unwrap_or_else(|| {
+            warn!("【{}】no final answer after {} steps", self._get_profile(), self.max_steps);
+            format!("No final answer after {} steps. Last steps:\n{}", self.max_steps, scratchpad)
+        });
+        let msg = if trigger.is_direct() {
+            trigger.reply(&content, self._get_profile(), REACT_ANSWER)
+        } else {
+            Message {
+                content,
+                role: self._get_profile().to_string(),
+                cause_by: REACT_ANSWER.to_string(),
+                ..Default::default()
+            }
+        }
+        .with_round(self._rc.round());
+        self._get_rc_memory().add(msg.clone());
+        Some(msg)
+    }
+}
+
+/// Parse `Final Answer: ...` or `Action: <tool>` followed by `Action Input: ...`.
+/// Anything from an `Observation:` line on was made up by the LLM and is ignored.
+fn parse_react_step(response: &str) -> Option<ReActStep> {
+    let response = response.split("\nObservation:").next().unwrap_or(response);
+    if let Some((_, answer)) = response.split_once("Final Answer:") {
+        return Some(ReActStep::Final(answer.trim().to_string()));
+    }
+    let (_, rest) = response.split_once("Action:")?;
+    let (tool, input) = rest.split_once("Action Input:")?;
+    let tool = tool.trim().trim_matches('`').to_string();
+    if tool.is_empty() {
+        return None;
+    }
+    Some(ReActStep::Call { tool, input: input.trim().to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_react_step() {
+        assert_eq!(
+            parse_react_step("Thought: look it up\nAction: search\nAction Input: rust async traits"),
+            Some(ReActStep::Call { tool: "search".into(), input: "rust async traits".into() })
+        );
+        assert_eq!(
+            parse_react_step("Thought: done\nFinal Answer: 42"),
+            Some(ReActStep::Final("42".into()))
+        );
+        assert_eq!(
+            parse_react_step("Action: search\nAction Input: rust\nObservation: made up\nFinal Answer: 42"),
+            Some(ReActStep::Call { tool: "search".into(), input: "rust".into() })
+        );
+        assert_eq!(parse_react_step("I think the answer is 42"), None);
+    }
+}
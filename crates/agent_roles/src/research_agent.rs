@@ -1,28 +1,58 @@
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 
 // use lazy_static::lazy_static;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use async_openai::types::ChatCompletionRequestMessage;
+use async_trait::async_trait;
+use futures::stream::{FuturesOrdered, StreamExt};
 use uuid::Uuid;
 use serde_json::json;
 
 use agent_schema::Message;
+use agent_memory::Memory;
 use agent_provider::LLM;
 use agent_tools::types::SearchResult;
 use agent_actions::{Action, GoogleSearch};
 use agent_utils::{
     file_ops::{read_txt_files, write_to_file},
-    html_ops
+    fetch_page, PdfDocument, WebPage,
 };
 // use agent_macro::RoleMacro;
 
-use crate::role::{Role, RoleSetting};
+use crate::ask_human::AskHuman;
+use crate::role::{Role, RoleContext, RoleSetting};
+
+/// `cause_by` of the report the agent publishes.
+pub const RESEARCH_REPORT: &str = "ResearchReport";
+
+const DEFAULT_MAX_CONCURRENCY: usize = 4;
+const DEFAULT_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+/// PDFs longer than this are summarized chunk by chunk before going into the research summary.
+const PDF_CHUNK_CHARS: usize = 8000;
 
 
 // type ReportPrompt = fn(&str, &str) -> String;
 
+/// Downloads the search results the agent browses.
+#[async_trait]
+pub trait PageFetcher: Send + Sync {
+    async fn fetch(&self, url: &str) -> anyhow::Result<WebPage>;
+}
+
+/// Fetches the pages on the web with `fetch_page`.
+#[derive(Debug, Default)]
+pub struct WebFetcher;
+
+#[async_trait]
+impl PageFetcher for WebFetcher {
+    async fn fetch(&self, url: &str) -> anyhow::Result<WebPage> {
+        fetch_page(url).await
+    }
+}
+
 // lazy_static! {
 //     static ref FUNCTION_MAP: HashMap<&'static str, ReportPrompt> = {
 //         let mut map = HashMap::new();
@@ -34,18 +64,26 @@ use crate::role::{Role, RoleSetting};
 //     };
 // }
 
-#[derive(Default)]
+/// Researches the question it receives on the web and publishes a report as a
+/// `ResearchReport` message, so it can be hired next to the other roles.
 pub struct ResearchAgent {
     _llm: Arc<Mutex<LLM>>,
     _setting: RoleSetting,
     _states: Vec<String>,
     _actions: Vec<Box<dyn Action>>,
+    _rc: RoleContext,
     dir_path: PathBuf,
     research_summary: String,
     agent_role_prompt: String,
     question: String,
     directory_name: String,
-    search_num_urls: usize
+    search_num_urls: usize,
+    /// How many pages are fetched at the same time.
+    max_concurrency: usize,
+    /// A page not fetched within this delay is skipped.
+    fetch_timeout: Duration,
+    fetcher: Box<dyn PageFetcher>,
+    report_type: String,
 }
 
 impl ResearchAgent {
@@ -64,18 +102,60 @@ impl ResearchAgent {
             _setting: setting,
             _states: vec![],
             _actions: vec![Box::new(action)],
+            _rc: RoleContext::new(HashSet::from(["BossRequirement".to_string()])),
+            dir_path: PathBuf::new(),
+            research_summary: String::new(),
+            agent_role_prompt: goal.to_string(),
+            question: String::new(),
             directory_name,
             search_num_urls: 2,
-            ..Default::default()
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            fetch_timeout: DEFAULT_FETCH_TIMEOUT,
+            fetcher: Box::new(WebFetcher),
+            report_type: "research_report".to_string(),
         }
     }
     pub fn default() -> Self {
-        let name = "Alice";
-        let profile = "Product Manager";
-        let goal = "Efficiently create a successful product";
-        let desc = "desc";
+        let name = "Ada";
+        let profile = "Research Agent";
+        let goal = generate_agent_role_prompt("Default Agent");
+        let desc = "";
         let constraints = "";
-        ResearchAgent::new(name, profile, goal, constraints, desc)
+        ResearchAgent::new(name, profile, &goal, constraints, desc)
+    }
+
+    /// Research the `cause_by` values in `watch` instead of `BossRequirement`.
+    pub fn with_watch(mut self, watch: HashSet<String>) -> Self {
+        self._rc = RoleContext::new(watch);
+        self
+    }
+
+    /// Number of search results browsed per query.
+    pub fn with_search_num_urls(mut self, search_num_urls: usize) -> Self {
+        self.search_num_urls = search_num_urls;
+        self
+    }
+
+    pub fn with_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency.max(1);
+        self
+    }
+
+    pub fn with_fetch_timeout(mut self, fetch_timeout: Duration) -> Self {
+        self.fetch_timeout = fetch_timeout;
+        self
+    }
+
+    /// Download the search results with `fetcher` instead of `WebFetcher`.
+    pub fn with_fetcher(mut self, fetcher: Box<dyn PageFetcher>) -> Self {
+        self.fetcher = fetcher;
+        self
+    }
+
+    /// `research_report`, `resource_report` or `outline_report`.
+    pub fn with_report_type(mut self, report_type: &str) -> Self {
+        self.report_type = report_type.to_string();
+        self
     }
 
     /// Determines what agent should be used
@@ -95,17 +175,21 @@ impl ResearchAgent {
         let msgs: Vec<ChatCompletionRequestMessage> =
             serde_json::from_value(serde_json::Value::Array(messages)).unwrap();
         debug!("call_agent {:?}", msgs);
-        let llm = { self._llm.lock().unwrap() };
+        // clone the client so the lock is not held across the request
+        let llm = { self._llm.lock().unwrap().clone() };
 
-        let response = llm.aask_with_role(msgs).await.unwrap();
+        let response = llm.aask_with_role(msgs).await.unwrap_or_else(|e| {
+            warn!("call_agent failed: {}", e);
+            String::new()
+        });
         info!("call_agent:\n {}", response);
         response
     }
 
-    async fn create_search_queries(&self) -> Vec<String> {
+    async fn create_search_queries(&self, question: &str) -> Vec<String> {
         let result = self
             .call_agent(
-                generate_search_queries_prompt(&self.question).as_str(),
+                generate_search_queries_prompt(question).as_str(),
                 false,
             )
             .await;
@@ -115,29 +199,62 @@ impl ResearchAgent {
         );
         // self.websocket.send_json(&json!({"type": "logs", "output": output})).await.unwrap();
         info!("{}", output);
-        serde_json::from_str(&result).unwrap()
+        serde_json::from_str(&result).unwrap_or_else(|_| {
+            warn!("search queries are not a JSON list, searching the question itself");
+            vec![question.to_string()]
+        })
     }
 
-    async fn async_browse(&self, url: &str) -> String {
+    async fn async_browse(&self, url: &str, query: &str) -> Option<String> {
         info!("async_browse {}", url);
-        if url.ends_with(".pdf") || url.ends_with(".PDF") {
-            let collection: Vec<&str> = url.split("/").collect();
-            let name = collection[collection.len() - 1];
-            info!("async_browse pdf:\n {}", name);
-            // let _ = download_pdf(url, name).await;
-            info!("✅ pdf: {}", name);
-            return "".to_string();
+        match tokio::time::timeout(self.fetch_timeout, self.fetcher.fetch(url)).await {
+            Ok(Ok(WebPage::Html(text))) => {
+                info!("✅ {}", url);
+                Some(text)
+            }
+            Ok(Ok(WebPage::Pdf(document))) if document.pages.iter().all(|page| page.is_empty()) => {
+                warn!("no text in {}, it may be a scanned document", url);
+                None
+            }
+            Ok(Ok(WebPage::Pdf(document))) => {
+                info!("✅ pdf: {} ({} pages)", url, document.pages.len());
+                Some(self.read_pdf(url, query, &document).await)
+            }
+            Ok(Err(e)) => {
+                warn!("failed to browse {}: {}", url, e);
+                None
+            }
+            Err(_) => {
+                warn!("browsing {} timed out after {:?}", url, self.fetch_timeout);
+                None
+            }
         }
+    }
 
-        let content;
-        if let Ok(res) = html_ops::scrape(&url).await {
-            content = res.text;
-            info!("✅ {}", url);
-        } else {
-            content = "".to_string();
+    /// The text of a PDF with its title and author, long documents are summarized
+    /// chunk by chunk with respect to `query`.
+    async fn read_pdf(&self, url: &str, query: &str, document: &PdfDocument) -> String {
+        let mut text = format!("Source: {}\n", url);
+        if let Some(title) = &document.title {
+            text.push_str(&format!("Title: {}\n", title));
+        }
+        if let Some(author) = &document.author {
+            text.push_str(&format!("Author: {}\n", author));
+        }
+        text.push('\n');
+
+        let full_text = document.to_text();
+        if full_text.chars().count() <= PDF_CHUNK_CHARS {
+            text.push_str(&full_text);
+            return text;
+        }
+        let chunks = document.chunks(PDF_CHUNK_CHARS);
+        info!("📄 summarizing {} in {} chunks", url, chunks.len());
+        for chunk in chunks {
+            let summary = self.call_agent(&generate_chunk_summary_prompt(query, &chunk), false).await;
+            text.push_str(&format!("{}\n\n", summary.trim()));
         }
-        debug!("📖 {}", content);
-        content
+        text
     }
 
     async fn async_search(&self, query: &str) -> Vec<String> {
@@ -145,16 +262,20 @@ impl ResearchAgent {
             content: query.to_string(),
             role: "ResearchAgent".to_string(),
             cause_by: "ResearchAgent".to_string(),
-            instruct_content: None,
-            send_to: None,
+            ..Default::default()
         };
         let response = self._actions[0].run(vec![&msg]).await;
         // response
-        let search_results: Vec<SearchResult> =
-            serde_json::from_str(&response).expect("failed to parse the search results. ");
+        let search_results: Vec<SearchResult> = serde_json::from_str(&response).unwrap_or_else(|e| {
+            warn!("failed to parse the search results: {}", e);
+            vec![]
+        });
 
-        let new_search_urls: Vec<String> =
-            search_results.clone().into_iter().map(|x| x.url).collect();
+        let new_search_urls: Vec<String> = search_results
+            .into_iter()
+            .take(self.search_num_urls)
+            .map(|x| x.url)
+            .collect();
 
         let output = format!(
             "🌐 Browsing the following sites for relevant information: {:?}...",
@@ -162,15 +283,28 @@ impl ResearchAgent {
         );
         info!("{}", output);
         // self.websocket.send_json(&json!({"type": "logs", "output": output})).await.unwrap();
-        // TODO multi-threaded
-        let mut tasks = Vec::new();
-        for url in &search_results[0..self.search_num_urls] {
-            let task = self.async_browse(&url.url).await;
-            tasks.push(task);
-        }
+        self.browse(&new_search_urls, query).await
+    }
 
-        // futures::future::join_all(tasks).await;
-        tasks
+    /// The text of the pages at `urls`, at most `max_concurrency` downloaded at the same
+    /// time. Pages that fail or time out are skipped, the others are kept in `urls` order.
+    async fn browse(&self, urls: &[String], query: &str) -> Vec<String> {
+        let mut urls = urls.iter();
+        let mut in_flight = FuturesOrdered::new();
+        let mut pages = Vec::new();
+        loop {
+            while in_flight.len() < self.max_concurrency {
+                match urls.next() {
+                    Some(url) => in_flight.push_back(self.async_browse(url, query)),
+                    None => break,
+                }
+            }
+            match in_flight.next().await {
+                Some(page) => pages.extend(page),
+                None => break,
+            }
+        }
+        pages
     }
 
     async fn run_search_summary(&self, query: &str) -> String {
@@ -184,13 +318,36 @@ impl ResearchAgent {
         let dir = format!("./outputs/{}/research-{}.txt", self.directory_name, query);
         let dir_path = std::path::Path::new(&dir);
         let parent_dir = dir_path.parent().unwrap();
-        std::fs::create_dir_all(parent_dir).unwrap();
-
-        let _ = write_to_file(&dir, &result);
+        let _ = std::fs::create_dir_all(parent_dir).and_then(|_| write_to_file(&dir, &result));
 
         result
     }
 
+    /// Search and browse for every query generated from the question.
+    async fn research(&self, question: &str) -> String {
+        let mut research_summary = String::new();
+        for query in self.create_search_queries(question).await {
+            let research_result = self.run_search_summary(&query).await;
+            research_summary.push_str(&format!("{}\n\n", research_result));
+        }
+        research_summary
+    }
+
+    async fn report(&self, question: &str, research_summary: &str, report_type: &str) -> String {
+        let prompt = get_report_by_type(report_type)(question, research_summary);
+        let output = format!("✍️ Writing {} for research task: {}...", report_type, question);
+        info!("{}", &prompt);
+        info!("{}", &output);
+        let report = self.call_agent(&prompt, false).await;
+
+        let dir = format!("./outputs/{}/research_report.md", self.directory_name);
+        let dir_path = std::path::Path::new(&dir);
+        let parent_dir = dir_path.parent().unwrap();
+        let _ = std::fs::create_dir_all(parent_dir).and_then(|_| write_to_file(&dir, &report));
+
+        report
+    }
+
     pub async fn conduct_research(&mut self, task: &str) -> String {
         self.question = task.to_string();
         self.research_summary = if self.dir_path.is_dir() {
@@ -200,13 +357,7 @@ impl ResearchAgent {
         };
 
         if self.research_summary.is_empty() {
-            let search_queries = self.create_search_queries().await;
-
-            for query in search_queries {
-                let research_result = self.run_search_summary(&query).await;
-                self.research_summary
-                    .push_str(&format!("{}\n\n", research_result));
-            }
+            self.research_summary = self.research(task).await;
         }
 
         let total_words = self.research_summary.split_whitespace().count();
@@ -218,21 +369,154 @@ impl ResearchAgent {
     }
 
     pub async fn write_report(&self, report_type: &str) -> String { 
-        let prompt = get_report_by_type(report_type)(&self.question, &self.research_summary);
-        let output = format!("✍️ Writing {} for research task: {}...", report_type, self.question);
-        info!("{}", &prompt);
-        info!("{}", &output);
-        let report = self.call_agent(&prompt, false).await;
+        self.report(&self.question, &self.research_summary, report_type).await
+    } 
+}
 
-        let dir = format!("./outputs/{}/research_report.md", self.directory_name);
-        let dir_path = std::path::Path::new(&dir);
-        let parent_dir = dir_path.parent().unwrap();
-        std::fs::create_dir_all(parent_dir).unwrap();
+#[async_trait]
+impl Role for ResearchAgent {
+    fn set_env_global_memory(&mut self, memory: Arc<Mutex<Memory>>) {
+        self._rc.env_memory = memory
+    }
 
-        let _ = write_to_file(&dir, &report);
+    fn set_ask_human(&mut self, ask_human: Arc<dyn AskHuman>, actions: HashSet<String>) {
+        self._rc.ask_human = Some(ask_human);
+        self._rc.human_gates = actions;
+    }
 
-        prompt
-    } 
+    fn _reset(&mut self) {
+        self._states = vec![];
+        self._actions = vec![];
+    }
+
+    fn _init_actions(&mut self, actions: Vec<Box<dyn Action>>) {
+        self._reset();
+        self._actions = actions;
+    }
+
+    fn _watch(&mut self, _actions: Vec<Box<dyn Action>>) {}
+
+    fn _set_state(&mut self, state: i32) {
+        self._rc.set_state(state);
+    }
+
+    async fn _aask(&self, prompt: &str) -> String {
+        self.call_agent(prompt, false).await
+    }
+
+    fn _get_profile(&self) -> &str {
+        &self._setting.profile
+    }
+
+    fn _get_prefix(&self) -> String {
+        self._setting.get_prefix()
+    }
+
+    fn _get_states(&self) -> Vec<String> {
+        self._states.clone()
+    }
+
+    fn _get_rc(&self) -> RoleContext {
+        self._rc.clone()
+    }
+
+    fn _get_setting(&self) -> &RoleSetting {
+        &self._setting
+    }
+
+    fn set_reflection(&mut self, max_rounds: usize) {
+        self._rc.reflection_rounds = max_rounds;
+    }
+
+    fn set_workspace(&mut self, workspace: &Path) {
+        for action in self._actions.iter_mut() {
+            action.set_workspace(workspace);
+        }
+    }
+
+    fn _get_rc_env_memory(&self) -> MutexGuard<'_, Memory> {
+        self._rc.env_memory.lock().unwrap()
+    }
+
+    fn _get_rc_memory(&self) -> MutexGuard<'_, Memory> {
+        self._rc.role_memory.lock().unwrap()
+    }
+
+    fn _get_action_by_state(&self, state: usize) -> Option<&Box<dyn Action>> {
+        self._actions.get(state)
+    }
+
+    fn _get_action_count(&self) -> usize {
+        self._actions.len()
+    }
+
+    fn _before_action(&self, _env_msgs: &Vec<Message>, _role_msgs: &Vec<Message>) -> String {
+        String::new()
+    }
+
+    fn _after_action(&self, message: Message) -> Message {
+        message
+    }
+
+    /// Research the latest question and publish the report, unless a human holds it back.
+    async fn _react(&self) -> Option<Message> {
+        let rc = self._get_rc();
+        let trigger = rc.trigger().unwrap_or_else(|| {
+            rc.important_memory().into_iter().rev().find(|msg| msg.role != self._get_profile()).unwrap_or_default()
+        });
+        let research_summary = self.research(&trigger.content).await;
+        info!("Total research words: {}", research_summary.split_whitespace().count());
+        // the report goes through reflection and the approval gate like any action output
+        let action = WriteReport { agent: self, research_summary: &research_summary };
+        let msgs = vec![trigger.clone()];
+        let report = action.run(msgs.iter().collect()).await;
+        let (report, critique) = self._reflect(&action, &msgs, report).await;
+        let report = self._ask_human(&action, &msgs, report).await?;
+
+        let mut msg = if trigger.is_direct() {
+            trigger.reply(&report, self._get_profile(), RESEARCH_REPORT)
+        } else {
+            Message {
+                content: report,
+                role: self._get_profile().to_string(),
+                cause_by: RESEARCH_REPORT.to_string(),
+                ..Default::default()
+            }
+        }
+        .with_round(self._rc.round());
+        msg.instruct_content = critique;
+        self._get_rc_memory().add(msg.clone());
+        Some(msg)
+    }
+}
+
+/// Writing the report as an `Action`, for `Role::_reflect` and `Role::_ask_human`. A revision
+/// writes it again with the draft and the feedback appended to the question.
+struct WriteReport<'a> {
+    agent: &'a ResearchAgent,
+    research_summary: &'a str,
+}
+
+#[async_trait]
+impl Action for WriteReport<'_> {
+    fn name(&self) -> &str {
+        RESEARCH_REPORT
+    }
+
+    fn set_prefix(&mut self, _prefix: &str, _profile: &str) {}
+
+    fn get_prefix(&self) -> &str {
+        ""
+    }
+
+    async fn aask(&self, prompt: &str) -> String {
+        self.agent.call_agent(prompt, false).await
+    }
+
+    async fn run(&self, msgs: Vec<&Message>) -> String {
+        let question = msgs.first().map_or("", |msg| msg.content.as_str());
+        self.agent.report(question, self.research_summary, &self.agent.report_type).await
+    }
 }
 
 fn generate_agent_role_prompt(agent: &str) -> String {
@@ -279,6 +563,13 @@ fn generate_report_prompt(question: &str, research_summary: &str) -> String {
     )
 }
 
+fn generate_chunk_summary_prompt(query: &str, chunk: &str) -> String {
+    format!(
+        "\"\"\"{}\"\"\" The above is a part of a longer document. Summarize the information relevant to the following question or topic: \"{}\", keeping facts, numbers and the page numbers they come from. If nothing is relevant, answer with a one sentence description of the part.",
+        chunk, query
+    )
+}
+
 fn generate_search_queries_prompt(question: &str) -> String {
     format!(
         "Write 4 google search queries to search online that form an objective opinion from the following: \"{}\" You must respond with a list of strings in the following format: [\"query 1\", \"query 2\", \"query 3\", \"query 4\"]",
@@ -349,3 +640,38 @@ fn auto_agent_instructions() -> String {
         }
     ".to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers every URL with its own name after the delay encoded in it, `broken` URLs
+    /// failing instead.
+    struct ScriptedFetcher;
+
+    #[async_trait]
+    impl PageFetcher for ScriptedFetcher {
+        async fn fetch(&self, url: &str) -> anyhow::Result<WebPage> {
+            let (name, delay) = url.split_once('@').unwrap_or((url, "0"));
+            tokio::time::sleep(Duration::from_millis(delay.parse()?)).await;
+            if name == "broken" {
+                anyhow::bail!("connection reset");
+            }
+            Ok(WebPage::Html(name.to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_browse() {
+        let agent = ResearchAgent::default()
+            .with_fetcher(Box::new(ScriptedFetcher))
+            .with_concurrency(2)
+            .with_fetch_timeout(Duration::from_millis(200));
+        let urls: Vec<String> = ["slow@100", "broken@0", "fast@0", "hanging@10000", "last@0"]
+            .iter()
+            .map(|url| url.to_string())
+            .collect();
+        // failures and timeouts are skipped, the rest keeps the order of the search results
+        assert_eq!(agent.browse(&urls, "snake").await, vec!["slow", "fast", "last"]);
+    }
+}
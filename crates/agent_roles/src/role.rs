@@ -1,15 +1,21 @@
 
-use std::{sync::{Arc, Mutex, MutexGuard}, collections::HashSet};
+use std::{sync::{Arc, Mutex, MutexGuard}, collections::HashSet, path::Path};
 
-use tracing::{info, debug};
+use tracing::{info, debug, warn};
 use async_trait::async_trait;
 
 
 use agent_schema::Message;
 use agent_actions::Action;
 use agent_memory::Memory;
+use agent_provider::LLMSettings;
 
-use crate::template::prefix_template;
+use crate::ask_human::{AskHuman, HumanFeedback};
+use crate::context_window::{ContextWindow, CONTEXT_SUMMARY};
+use crate::template::{prefix_template, reflection_template, state_template};
+
+/// How many times a rejected artifact is regenerated before the role gives up on it.
+const MAX_HUMAN_REVISIONS: usize = 3;
 
 /// role setting for agent
 #[derive(Default, Debug, Clone, PartialEq)]
@@ -41,39 +47,119 @@ impl RoleSetting {
 pub struct RoleContext {
     pub env_memory: Arc<Mutex<Memory>>,
     pub role_memory: Arc<Mutex<Memory>>,
-    pub state: i32,
+    /// Index of the current action, -1 before the first one. Shared by the clones of the context.
+    state: Arc<Mutex<i32>>,
+    /// Turn of the run the role is in, stamped on its messages. Shared like `state`.
+    round: Arc<Mutex<usize>>,
+    /// Messages that triggered the current turn. Shared like `state`.
+    news: Arc<Mutex<Vec<Message>>>,
     todo: Option<Arc<Mutex<dyn Role + Send + Sync>>>,
     watch: HashSet<String>,
+    /// Messages read as background context, which never trigger the role by themselves.
+    context: HashSet<String>,
+    /// Asked to approve the output of the actions in `human_gates`.
+    pub ask_human: Option<Arc<dyn AskHuman>>,
+    pub human_gates: HashSet<String>,
+    /// How many critique and revise rounds follow each action, 0 disables reflection.
+    pub reflection_rounds: usize,
+    /// Tokens of memory given to the actions, the rest is summarized.
+    pub context_window: ContextWindow,
 }
 
 impl RoleContext {
     pub fn new(watch: HashSet<String>) -> Self {
          // Initialize fields accordingly
-        let state = 1;
+        let state = Arc::new(Mutex::new(-1));
         Self {
             env_memory: Arc::new(Mutex::new(Memory::new())),
             role_memory: Arc::new(Mutex::new(Memory::new())),
             state,
+            round: Arc::new(Mutex::new(0)),
+            news: Arc::new(Mutex::new(vec![])),
             todo: None,
             watch,
+            context: HashSet::new(),
+            ask_human: None,
+            human_gates: HashSet::new(),
+            reflection_rounds: 0,
+            context_window: ContextWindow::for_llm(&LLMSettings::default()),
         }
     }
 
+    /// Also read messages caused by `actions` (e.g. `RepositorySummary`) as context.
+    pub fn with_context(mut self, actions: HashSet<String>) -> Self {
+        self.context = actions;
+        self
+    }
+
+    /// `cause_by` values that trigger the role.
+    pub fn watch(&self) -> &HashSet<String> {
+        &self.watch
+    }
+
+    pub fn state(&self) -> i32 {
+        *self.state.lock().unwrap()
+    }
+
+    pub fn set_state(&self, state: i32) {
+        *self.state.lock().unwrap() = state;
+    }
+
+    pub fn round(&self) -> usize {
+        *self.round.lock().unwrap()
+    }
+
+    pub fn set_round(&self, round: usize) {
+        *self.round.lock().unwrap() = round;
+    }
+
+    pub fn news(&self) -> Vec<Message> {
+        self.news.lock().unwrap().clone()
+    }
+
+    pub fn set_news(&self, news: Vec<Message>) {
+        *self.news.lock().unwrap() = news;
+    }
+
+    /// The latest message that triggered the current turn, the one the role answers.
+    pub fn trigger(&self) -> Option<Message> {
+        self.news.lock().unwrap().last().cloned()
+    }
+
+    /// Critique every action output against the role's goal and constraints, and revise it
+    /// up to `max_rounds` times.
+    pub fn with_reflection(mut self, max_rounds: usize) -> Self {
+        self.reflection_rounds = max_rounds;
+        self
+    }
+
+    /// Fit the memory given to the actions in `context_window`, e.g. the one of a role's own model.
+    pub fn with_context_window(mut self, context_window: ContextWindow) -> Self {
+        self.context_window = context_window;
+        self
+    }
+
+    /// The messages received by the role, one `role: content` entry per message.
     pub fn history(self) -> String{
-        let _msgs = self.env_memory.lock().unwrap().get(0);
-        "history".to_string()
+        let role_memory = self.role_memory.lock().unwrap();
+        role_memory
+            .get(0)
+            .iter()
+            .map(|msg| format!("{}: {}", msg.role, msg.content))
+            .collect::<Vec<String>>()
+            .join("\n")
     }
     /// 获得关注动作对应的信息
     /// The important_memory method also takes a mutable reference to self and returns a vector of Message instances.
     /// It locks the role_memory field, calls the get method of the locked Memory instance on index 0, 
     /// clones each element in the resulting vector, and adds them to the msg_data vector.
-    /// Finally, it returns the msg_data vector.
+    /// Finally, it returns the msg_data vector. The summaries of `Role::_compact_memory` are left out.
     pub fn important_memory(self) -> Vec<Message>{
         let role_memory = self.role_memory.lock().unwrap();
         let msgs = role_memory.get(0);
 
         let mut msg_data = Vec::new();
-        for msg in msgs.iter() {
+        for msg in msgs.iter().filter(|msg| msg.cause_by != CONTEXT_SUMMARY) {
             let msg: Message = msg.to_owned().clone();
             msg_data.push(msg);
         }
@@ -109,16 +195,26 @@ pub trait Role: Send + Sync {
     fn _watch(&mut self, actions: Vec<Box<dyn Action>>);
     /// Set the state for the role.
     fn _set_state(&mut self, state: i32);
+    /// Ask the role's own LLM, returns an empty answer on failure.
+    async fn _aask(&self, prompt: &str) -> String;
     /// Get states for the role.
     fn _get_states(&self)-> Vec<String>;
     /// Set the global environment memory for the role.
     fn set_env_global_memory(&mut self, memory: Arc<Mutex<Memory>>);
+    /// Pause after the given actions and ask a human to approve, edit or reject their output.
+    fn set_ask_human(&mut self, ask_human: Arc<dyn AskHuman>, actions: HashSet<String>);
     /// Get the profile of the role.
     fn _get_profile(&self) -> &str;
     /// Get the role's prefix.
     fn _get_prefix(&self) -> String;
     /// Get the role's context.
     fn _get_rc(&self) -> RoleContext;
+    /// Get the role's setting.
+    fn _get_setting(&self) -> &RoleSetting;
+    /// Critique and revise the output of every action up to `max_rounds` times, 0 disables it.
+    fn set_reflection(&mut self, max_rounds: usize);
+    /// Write the files of the role's actions under `workspace`.
+    fn set_workspace(&mut self, workspace: &Path);
     /// Get the environment memory within the role's context.
     fn _get_rc_env_memory(&self) -> MutexGuard<'_, Memory>;
     /// Get the role's memory within the role's context.
@@ -172,21 +268,20 @@ pub trait Role: Send + Sync {
         //** Note that env_memory is locked, and you need to release it before calling other functions.
         //** In general, you don't need to manually release the Mutex lock. The lock is automatically released when MutexGuard goes out of scope.
         {
+            let rc = self._get_rc();
+            let profile = self._get_profile();
             // Perform data operations while locked
             let env_memory = self._get_rc_env_memory();
-            let _env_msgs = env_memory.get(0);
-            // debug!("【{}】 Environment information env_msgs {:?}", self._get_profile(), env_memory.storage.clone());
-            // env_msgs = self._rc.env.memory.get()
-            // Retrieve messages based on the types of actions being watched (watch)
-            let observed = env_memory.get_by_actions(self._get_rc().watch);
-            // debug!("【{}】 Environment information Subscribed {:?} action information {:?}", self._get_profile(), self._get_rc().watch, observed);
+            // Messages addressed with `send_to` reach their recipients only, the others
+            // the roles watching their `cause_by`. Never observe our own messages.
+            let observed = env_memory
+                .get(0)
+                .into_iter()
+                .filter(|message| message.role != profile && message.is_delivered_to(profile, &rc.watch));
             // Already observed messages
             let role_memory = self._get_rc_memory();
-            let already_observed = role_memory.get(0);
-            // debug!("【{}】 Already observed information {:?}", self._get_profile(), already_observed);
-
             for message in observed {
-                if !already_observed.contains(&message) {
+                if !role_memory.contains(&message.id) {
                     news.push(message.clone())
                 }
             }
@@ -199,44 +294,166 @@ pub trait Role: Send + Sync {
 
     }
 
+    /// Gather the context messages the role has not seen yet. Unlike `_observe`,
+    /// these never trigger the role on their own.
+    fn _observe_context(&self) -> Vec<Message> {
+        let rc = self._get_rc();
+        if rc.context.is_empty() {
+            return Vec::new();
+        }
+        let env_memory = self._get_rc_env_memory();
+        let role_memory = self._get_rc_memory();
+        let profile = self._get_profile();
+        env_memory
+            .get_by_actions(rc.context)
+            .into_iter()
+            .filter(|message| !message.is_direct() || message.send_to.iter().any(|r| r == profile))
+            .filter(|message| !role_memory.contains(&message.id))
+            .cloned()
+            .collect()
+    }
+
     /// - Think about what to do next and decide the next action.
     /// - If there's only one action, then that's the only option.
+    /// - Otherwise ask the LLM to pick a stage from the memory fitted in the context window. An
+    ///   answer that is not a valid stage is logged and falls back to the action after the
+    ///   current one, the first at the start and the last once there.
     async fn _think_next_action(&self) -> i32 {
-        // "Think about what to do next and decide the next action."
-        let next_state = 0;
-        debug!("The current Agent has {} actions", self._get_action_count());
-        if self._get_action_count() == 1 {
+        let rc = self._get_rc();
+        let count = self._get_action_count();
+        debug!("The current Agent has {} actions", count);
+        if count <= 1 {
             // If there's only one action, that's the only option
-            return next_state
+            rc.set_state(0);
+            return 0
         }
         debug!("Multiple actions available, need to decide which action based on the message");
-        // TODO: This section is not yet enabled
-        // let prompt = self._get_prefix();
-        // // let _history = self._get_role_context().clone().history();
-        // let status_prompt = state_template(self._get_rc().history(), self._get_states().join("\n"), self._get_states().len());
-        // info!(status_prompt);
-        // // Call LLM to choose the next task
-        // next_state = self._get_llm().aask(prompt).await;
-        // debug!("_think ask llm next_state {}", next_state);
+        let states: Vec<String> = (0..count)
+            .filter_map(|idx| self._get_action_by_state(idx).map(|action| format!("{}. {}", idx, action.name())))
+            .collect();
+        let history = self
+            ._compact_memory()
+            .await
+            .iter()
+            .map(|msg| format!("{}: {}", msg.role, msg.content))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let prompt = format!("{}\n{}", self._get_prefix(), state_template(history, states.join("\n"), count - 1));
+        let answer = self._aask(&prompt).await;
+        let next_state = match parse_state(&answer, count) {
+            Some(state) => state as i32,
+            None => {
+                let fallback = (rc.state() + 1).clamp(0, count as i32 - 1);
+                warn!("【{}】invalid stage answer {:?}, falling back to {}", self._get_profile(), answer, fallback);
+                fallback
+            }
+        };
+        debug!("_think ask llm next_state {}", next_state);
+        rc.set_state(next_state);
         next_state
     }
 
-    /// Think first (_think) and then act.
-    async fn _execute_next_action(&self, action_state: usize) -> Message {
+    /// Run the action again with the previous draft and the feedback appended to its input.
+    async fn _revise(&self, action: &dyn Action, msgs: &[Message], draft: &str, feedback: &str) -> String {
+        let mut revised = msgs.to_vec();
+        if let Some(first) = revised.first_mut() {
+            first.content = format!("{}\n\n## Previous Draft\n{}\n\n{}", first.content, draft, feedback);
+        }
+        action.run(revised.iter().collect()).await
+    }
+
+    /// Opt-in self-reflection: critique the output against the role's goal and constraints,
+    /// asking the LLM of the action, and revise it while problems are found, up to
+    /// `reflection_rounds` times. Returns the final output and the critique trail.
+    async fn _reflect(&self, action: &dyn Action, msgs: &[Message], mut result: String) -> (String, Option<String>) {
+        let rounds = self._get_rc().reflection_rounds;
+        if rounds == 0 {
+            return (result, None);
+        }
+        let setting = self._get_setting().clone();
+        let mut trail = vec![];
+        for round in 1..=rounds {
+            let critique = action.aask(&reflection_template(&self._get_prefix(), &setting.goal, &setting.constraints, &result)).await;
+            let critique = critique.trim().to_string();
+            if critique.is_empty() || critique.starts_with("LGTM") {
+                trail.push(format!("## Critique {}\nLGTM", round));
+                break;
+            }
+            info!("【{}】critique {} of {}:\n{}", self._get_profile(), round, action.name(), critique);
+            trail.push(format!("## Critique {}\n{}", round, critique));
+            let feedback = format!("## Critique\nRevise the previous draft to fix these problems:\n{}", critique);
+            result = self._revise(action, msgs, &result, &feedback).await;
+        }
+        (result, Some(trail.join("\n\n")))
+    }
+
+    /// If the action is gated, present its output to a human and wait for the decision.
+    /// A rejection runs the action again with the previous draft and the feedback appended.
+    /// `None` once every revision was rejected or nobody answered: nothing may be published.
+    async fn _ask_human(&self, action: &dyn Action, msgs: &[Message], mut result: String) -> Option<String> {
+        let rc = self._get_rc();
+        let Some(ask_human) = rc.ask_human else { return Some(result) };
+        if !rc.human_gates.contains(action.name()) {
+            return Some(result);
+        }
+        for revision in 0..=MAX_HUMAN_REVISIONS {
+            match ask_human.review(self._get_profile(), action.name(), &result).await {
+                HumanFeedback::Approve => return Some(result),
+                HumanFeedback::Edit(content) => return Some(content),
+                HumanFeedback::Unanswered => {
+                    warn!("【{}】nobody answered the review of {}, holding it back", self._get_profile(), action.name());
+                    return None;
+                }
+                HumanFeedback::Reject(feedback) if revision == MAX_HUMAN_REVISIONS => {
+                    warn!("【{}】{} was rejected {} times, holding it back: {}", self._get_profile(), action.name(), revision + 1, feedback);
+                    return None;
+                }
+                HumanFeedback::Reject(feedback) => {
+                    info!("【{}】{} rejected: {}", self._get_profile(), action.name(), feedback);
+                    let feedback = format!("## Human Feedback\nThe previous draft was rejected, revise it according to this feedback: {}", feedback);
+                    result = self._revise(action, msgs, &result, &feedback).await;
+                }
+            }
+        }
+        None
+    }
+
+    /// The role's memory fitted in its context window: the original requirement, a rolling
+    /// summary of the older messages, kept in memory and updated as messages age out, and
+    /// the recent messages verbatim.
+    async fn _compact_memory(&self) -> Vec<Message> {
+        let rc = self._get_rc();
+        let memory: Vec<Message> = self._get_rc_memory().iter().cloned().collect();
+        let compaction = rc.context_window.compact(&memory);
+        let Some(prompt) = compaction.summary_prompt(&self._get_prefix()) else {
+            return compaction.into_messages(None);
+        };
+        debug!("【{}】summarizing {} messages to fit {} tokens", self._get_profile(), compaction.folded.len(), rc.context_window.budget);
+        let answer = self._aask(&prompt).await;
+        let summary = compaction.summarize(self._get_profile(), &answer).with_round(rc.round());
+        self._get_rc_memory().add(summary.clone());
+        compaction.into_messages(Some(summary))
+    }
+
+    /// Think first (_think) and then act. `None` when a human held the output back.
+    async fn _execute_next_action(&self, action_state: usize) -> Option<Message> {
         // let important_memory;
         // let env_msgs;
         // {
-        let role_msgs = self._get_rc().clone().important_memory();
+        let role_msgs = self._compact_memory().await;
         let env_msgs = self._get_rc().clone().get_env_memory();
 
         let mut action_result: String = "".into();
         let mut cause_by: String = "".into();
+        let mut critique = None;
 
         match self._get_action_by_state(action_state) {
             Some(action) => {
                 info!("【{}】action.run, will do  {:?}", self._get_profile(), action.name());
                 self._before_action(&env_msgs, &role_msgs);
                 action_result = action.run(role_msgs.iter().collect()).await;
+                (action_result, critique) = self._reflect(action.as_ref(), &role_msgs, action_result).await;
+                action_result = self._ask_human(action.as_ref(), &role_msgs, action_result).await?;
                 cause_by = action.name().to_owned();
             },
             None => println!("error occurred"),
@@ -252,11 +469,18 @@ pub trait Role: Send + Sync {
         // info!("【{}】action_result:\n {}", self._get_profile(),  termimad::inline(&action_result));
         // info!("【{}】action_result:\n {}", self._get_profile(),  termimad::inline(&action_result));
 
+        // A message addressed to this role by name is answered to its sender only.
+        let send_to = match self._get_rc().trigger() {
+            Some(trigger) if trigger.is_direct() => vec![trigger.role.clone()],
+            _ => vec![],
+        };
         let msg = Message {
             content: action_result,
-            instruct_content: None,
+            instruct_content: critique,
             role: self._get_profile().to_string(),
             cause_by,
+            send_to,
+            round: self._get_rc().round(),
             ..Default::default()
         };
         let msg = self._after_action(msg);
@@ -264,12 +488,12 @@ pub trait Role: Send + Sync {
         self._get_rc_env_memory().add(msg.clone());
         // Store in the Agent's memory
         self._get_rc_memory().add(msg.clone());
-        msg
+        Some(msg)
     }
 
-    /// Think first, then act. The same for every Agent.
+    /// Think first, then act. The same for every Agent. `None` ends the turn without a message.
     /// _think -> _act
-    async fn _react(&self) -> Message {
+    async fn _react(&self) -> Option<Message> {
         debug!("【{}】thinking about the next action...", self._get_profile());
         let next_state = self._think_next_action().await;
         debug!("【{}】executing the next action...  next_state: {}",self._get_profile(), next_state);
@@ -277,8 +501,9 @@ pub trait Role: Send + Sync {
     }
 
     /// Receive messages and respond with actions.
-    async fn handle(&self, message: Message) -> Message {
+    async fn handle(&self, message: Message) -> Option<Message> {
         // Store in the agent's memory
+        self._get_rc().set_news(vec![message.clone()]);
         self.recv(message);
         info!("No new messages. Waiting");
         self._react().await
@@ -293,26 +518,44 @@ pub trait Role: Send + Sync {
         match message {
             Some(message) => {
                 // If there's a message, store it
+                self._get_rc().set_news(vec![message.clone()]);
                 self.recv(message);
             }
             None => {
                 // If there's no message, observe until a new message appears
                 let news = self._observe().await;
-                // If there's no new information, suspend and return directly
-                if news.is_empty() {
-                    info!("No new information. Waiting");
-                    return None
-                }
-                // There are already new messages, continue processing
-                // debug!("【{}】New messages: {:?}", self._get_profile(), );
-                for msg in &news {
-                    // debug!("【{}】New message {:?} sent to recv", self._get_profile(), msg.cause_by);
-                    self.recv(msg.clone());
-                }
+                return self.run_with(news).await;
             },
         }
         debug!("---------------New messages to be processed-----------------");
-        let rsp = self._react().await;
+        let rsp = self._react().await?;
+        // Publish the response to the environment, waiting for the next subscriber to process
+        self._publish_message(rsp.clone());
+        Some(rsp)
+    }
+
+    /// React to the messages delivered to the role, e.g. by the environment's message broker,
+    /// instead of observing the whole environment memory.
+    async fn run_with(&self, news: Vec<Message>) -> Option<Message> {
+        // Skip what was already observed, e.g. messages replayed after resuming a run
+        let news: Vec<Message> = {
+            let role_memory = self._get_rc_memory();
+            news.into_iter().filter(|msg| !role_memory.has_message(msg, 0)).collect()
+        };
+        // If there's no new information, suspend and return directly
+        if news.is_empty() {
+            info!("No new information. Waiting");
+            return None
+        }
+        self._get_rc().set_news(news.clone());
+        for msg in news {
+            self.recv(msg);
+        }
+        for msg in self._observe_context() {
+            self.recv(msg);
+        }
+        debug!("---------------New messages to be processed-----------------");
+        let rsp = self._react().await?;
         // Publish the response to the environment, waiting for the next subscriber to process
         self._publish_message(rsp.clone());
         Some(rsp)
@@ -320,3 +563,218 @@ pub trait Role: Send + Sync {
 
 
 }
+
+/// Parse the stage number answered to `state_template`, `None` unless it is a single
+/// number in `0..n_states` (surrounding punctuation such as `1.` is tolerated).
+fn parse_state(answer: &str, n_states: usize) -> Option<usize> {
+    answer
+        .trim()
+        .trim_matches(|c: char| !c.is_ascii_digit())
+        .parse::<usize>()
+        .ok()
+        .filter(|state| *state < n_states)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+    use crate::declarative_role::{DeclarativeRole, RoleDefinition};
+    use agent_provider::with_fake_llm;
+
+    /// Answers the reviews from a script.
+    struct ScriptedAskHuman(Mutex<VecDeque<HumanFeedback>>);
+
+    #[async_trait]
+    impl AskHuman for ScriptedAskHuman {
+        async fn review(&self, _profile: &str, _action: &str, _artifact: &str) -> HumanFeedback {
+            self.0.lock().unwrap().pop_front().expect("no scripted answer left")
+        }
+    }
+
+    /// Answers with the input it was given.
+    struct Echo;
+
+    #[async_trait]
+    impl Action for Echo {
+        fn name(&self) -> &str {
+            "Echo"
+        }
+        fn set_prefix(&mut self, _prefix: &str, _profile: &str) {}
+        fn get_prefix(&self) -> &str {
+            ""
+        }
+        async fn aask(&self, _prompt: &str) -> String {
+            String::new()
+        }
+        async fn run(&self, msgs: Vec<&Message>) -> String {
+            msgs[0].content.clone()
+        }
+    }
+
+    /// `Echo` whose LLM answers from a script.
+    struct ScriptedEcho(Mutex<VecDeque<String>>);
+
+    impl ScriptedEcho {
+        fn new(answers: &[&str]) -> Self {
+            Self(Mutex::new(answers.iter().map(|answer| answer.to_string()).collect()))
+        }
+    }
+
+    #[async_trait]
+    impl Action for ScriptedEcho {
+        fn name(&self) -> &str {
+            "Echo"
+        }
+        fn set_prefix(&mut self, _prefix: &str, _profile: &str) {}
+        fn get_prefix(&self) -> &str {
+            ""
+        }
+        async fn aask(&self, _prompt: &str) -> String {
+            self.0.lock().unwrap().pop_front().expect("no scripted answer left")
+        }
+        async fn run(&self, msgs: Vec<&Message>) -> String {
+            Echo.run(msgs).await
+        }
+    }
+
+    fn product_manager() -> DeclarativeRole {
+        let definition = RoleDefinition {
+            name: "Alice".into(),
+            profile: "Product Manager".into(),
+            actions: vec!["WritePRD".into()],
+            watch: vec!["BossRequirement".into()],
+            context: vec!["RepositorySummary".into()],
+            ..Default::default()
+        };
+        DeclarativeRole::new(&definition).unwrap()
+    }
+
+    fn gated_role(script: Vec<HumanFeedback>) -> DeclarativeRole {
+        let mut role = product_manager();
+        role.set_ask_human(Arc::new(ScriptedAskHuman(Mutex::new(script.into()))), HashSet::from(["Echo".to_string()]));
+        role
+    }
+
+    #[tokio::test]
+    async fn test_reflect() {
+        let msgs = vec![Message { content: "snake game".into(), ..Default::default() }];
+        let mut role = product_manager();
+
+        // revised until the critique is LGTM
+        role.set_reflection(3);
+        let critic = ScriptedEcho::new(&["Missing error handling", "LGTM, ship it"]);
+        let (result, trail) = role._reflect(&critic, &msgs, "draft".into()).await;
+        assert!(result.starts_with("snake game\n\n## Previous Draft\ndraft"));
+        assert!(result.contains("Missing error handling"));
+        assert_eq!(trail.as_deref(), Some("## Critique 1\nMissing error handling\n\n## Critique 2\nLGTM"));
+
+        // never more rounds than configured, the last revision is kept
+        role.set_reflection(2);
+        let critic = ScriptedEcho::new(&["Too short", "Still too short"]);
+        let (result, trail) = role._reflect(&critic, &msgs, "draft".into()).await;
+        assert_eq!(result.matches("## Previous Draft").count(), 2);
+        assert!(trail.unwrap().ends_with("## Critique 2\nStill too short"));
+
+        // the trail is published in instruct_content
+        role.set_reflection(1);
+        role._init_actions(vec![Box::new(ScriptedEcho::new(&["LGTM"]))]);
+        let question = Message { content: "snake game".into(), role: "Boss".into(), cause_by: "BossRequirement".into(), ..Default::default() };
+        let answer = role.run_with(vec![question]).await.unwrap();
+        assert_eq!(answer.content, "snake game");
+        assert_eq!(answer.instruct_content.as_deref(), Some("## Critique 1\nLGTM"));
+    }
+
+    #[tokio::test]
+    async fn test_ask_human() {
+        let msgs = vec![Message { content: "snake game".into(), ..Default::default() }];
+        let role = gated_role(vec![HumanFeedback::Reject("add a leaderboard".into()), HumanFeedback::Approve]);
+        let result = role._ask_human(&Echo, &msgs, "draft".into()).await.unwrap();
+        assert!(result.starts_with("snake game\n\n## Previous Draft\ndraft"));
+        assert!(result.contains("add a leaderboard"));
+
+        let role = gated_role(vec![HumanFeedback::Edit("edited".into())]);
+        assert_eq!(role._ask_human(&Echo, &msgs, "draft".into()).await.as_deref(), Some("edited"));
+
+        // every revision rejected, or nobody answering, holds the artifact back
+        let role = gated_role(vec![HumanFeedback::Reject("no".into()); MAX_HUMAN_REVISIONS + 1]);
+        assert_eq!(role._ask_human(&Echo, &msgs, "draft".into()).await, None);
+        let mut role = gated_role(vec![HumanFeedback::Unanswered]);
+        assert_eq!(role._ask_human(&Echo, &msgs, "draft".into()).await, None);
+
+        // and the turn ends without a message
+        role._init_actions(vec![Box::new(Echo)]);
+        let question = Message { content: "snake game".into(), role: "Boss".into(), cause_by: "BossRequirement".into(), ..Default::default() };
+        role.set_ask_human(Arc::new(ScriptedAskHuman(Mutex::new(vec![HumanFeedback::Unanswered].into()))), HashSet::from(["Echo".to_string()]));
+        assert!(role.run_with(vec![question]).await.is_none());
+        assert!(role._get_rc_env_memory().get(0).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reply_to_the_trigger() {
+        let mut role = gated_role(vec![HumanFeedback::Approve; 2]);
+        role._init_actions(vec![Box::new(Echo)]);
+        let summary = Message { content: "src/main.rs".into(), role: "Indexer".into(), cause_by: "RepositorySummary".into(), ..Default::default() };
+        role._get_rc_env_memory().add(summary);
+
+        // the context read after the question does not change who is answered
+        let question = Message {
+            content: "snake game".into(),
+            role: "Boss".into(),
+            cause_by: "BossRequirement".into(),
+            send_to: vec!["Product Manager".into()],
+            ..Default::default()
+        };
+        let answer = role.run_with(vec![question]).await.unwrap();
+        assert_eq!(answer.send_to, vec!["Boss"]);
+
+        let broadcast = Message { content: "add a leaderboard".into(), role: "Boss".into(), cause_by: "BossRequirement".into(), ..Default::default() };
+        assert!(role.run_with(vec![broadcast]).await.unwrap().send_to.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_compacted_memory_in_prompt() {
+        with_fake_llm(async {
+            // 100 tokens of context, 25 of them for the summary
+            let definition = RoleDefinition {
+                name: "Bob".into(),
+                profile: "Architect".into(),
+                actions: vec!["WriteDesign".into()],
+                watch: vec!["WritePRD".into()],
+                llm: LLMSettings { model: Some("gpt-3.5-turbo".into()), max_tokens: Some(3896), ..Default::default() },
+                ..Default::default()
+            };
+            let mut role = DeclarativeRole::new(&definition).unwrap();
+            let workspace = tempfile::tempdir().unwrap();
+            role.set_workspace(workspace.path());
+            let draft = |idx: usize| Message {
+                content: format!("draft {} {}", idx, "words ".repeat(38)),
+                role: "Product Manager".into(),
+                cause_by: "WritePRD".into(),
+                ..Default::default()
+            };
+            for idx in 0..4 {
+                role._get_rc_memory().add(draft(idx));
+            }
+
+            let prd = Message { content: "final PRD".into(), role: "Product Manager".into(), cause_by: "WritePRD".into(), ..Default::default() };
+            let (_, prompts) = agent_provider::capture_prompts(role.run_with(vec![prd])).await;
+            let prompt = prompts.last().unwrap();
+            assert!(prompt.contains("[ContextSummary]: Product Manager (WritePRD): draft 0"));
+            assert!(prompt.contains("[WritePRD]: draft 3"));
+            assert!(prompt.contains("[WritePRD]: final PRD"));
+            assert!(!prompt.contains("[WritePRD]: draft 2"));
+        })
+        .await;
+    }
+
+    #[test]
+    fn test_parse_state() {
+        assert_eq!(parse_state("1", 3), Some(1));
+        assert_eq!(parse_state(" 2.\n", 3), Some(2));
+        assert_eq!(parse_state("3", 3), None);
+        assert_eq!(parse_state("0 or 1", 3), None);
+        assert_eq!(parse_state("the next stage", 3), None);
+    }
+}
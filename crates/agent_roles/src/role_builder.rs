@@ -7,13 +7,20 @@ use async_openai::types::ChatCompletionRequestMessage;
 // use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use tracing::{info, warn};
 
-use agent_actions::{Action, WritePRD};
+use agent_actions::{Action, WritePRD, ACTION_NAMES};
 // use agent_macro::RoleMacro;
 // use agent_memory::Memory;
 use agent_provider::LLM;
 
+use crate::declarative_role::RoleDefinition;
 use crate::role::RoleSetting;
+use crate::team_plan::TeamPlan;
+use crate::template::team_plan_template;
+
+/// LLM answers tried before giving up on a team plan, each retry sees the previous error.
+const MAX_PLAN_ATTEMPTS: usize = 3;
 
 /// reference from the GPT Researcher
 const AUTO_AGENT_INSTRUCTIONS: &str = r#"
@@ -105,4 +112,31 @@ impl AgentRoleBuilder {
 
         agent_role
     }
+
+    /// Propose a whole team for `task`: roles picked from `catalog` or newly written
+    /// personas, their watch relationships and the number of rounds.
+    /// `feedback` is the user's opinion on a previous proposal, empty for the first one.
+    pub async fn plan_team(&self, task: &str, catalog: &[RoleDefinition], feedback: &str) -> anyhow::Result<TeamPlan> {
+        let catalog = serde_yaml::to_string(catalog)?;
+        let mut feedback = if feedback.is_empty() { "None".to_string() } else { feedback.to_string() };
+        let llm = { self._llm.lock().unwrap().clone() };
+
+        let mut last_error = anyhow::anyhow!("no team plan was proposed");
+        for attempt in 1..=MAX_PLAN_ATTEMPTS {
+            let prompt = team_plan_template(task, &ACTION_NAMES.join(", "), &catalog, &feedback);
+            let answer = llm.aask(&prompt).await.map_err(|e| anyhow::anyhow!("team planning failed: {}", e))?;
+            match TeamPlan::from_answer(&answer) {
+                Ok(plan) => {
+                    info!("planned a team of {} roles for {} rounds", plan.roles.len(), plan.n_round);
+                    return Ok(plan);
+                }
+                Err(e) => {
+                    warn!("team plan {} rejected: {:#}", attempt, e);
+                    feedback = format!("{}\nYour last answer was invalid: {:#}", feedback, e);
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error.context(format!("no valid team plan after {} attempts", MAX_PLAN_ATTEMPTS)))
+    }
 }
@@ -0,0 +1,139 @@
+use std::collections::HashSet;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::declarative_role::{DeclarativeRole, RoleDefinition};
+use crate::role::Role;
+
+/// `cause_by` values published by the company itself rather than by a hired role.
+pub const COMPANY_MESSAGES: [&str; 2] = ["BossRequirement", "RepositorySummary"];
+
+/// Upper bound of `n_round` a plan may ask for.
+pub const MAX_TEAM_ROUNDS: i32 = 20;
+
+/// A team proposed for a task: the roles to hire and how many rounds to run.
+///
+/// It is written in the same YAML as a `--roles` file plus `n_round`, so a plan can be
+/// reviewed, edited and saved for later runs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TeamPlan {
+    pub n_round: i32,
+    pub roles: Vec<RoleDefinition>,
+}
+
+impl TeamPlan {
+    /// Parse a plan from an LLM answer or an edited plan, in YAML or JSON, with or
+    /// without a surrounding code block.
+    pub fn from_answer(answer: &str) -> anyhow::Result<TeamPlan> {
+        let plan: TeamPlan = serde_yaml::from_str(strip_code_block(answer)).context("the team plan is not valid YAML")?;
+        plan.validate()?;
+        Ok(plan)
+    }
+
+    pub fn to_yaml(&self) -> String {
+        serde_yaml::to_string(self).unwrap_or_default()
+    }
+
+    /// Check every role on its own, then that the team forms a pipeline starting from
+    /// the boss requirement in which every watched message is published by someone.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.roles.is_empty() {
+            bail!("the team has no roles");
+        }
+        if !(1..=MAX_TEAM_ROUNDS).contains(&self.n_round) {
+            bail!("n_round must be between 1 and {}, got {}", MAX_TEAM_ROUNDS, self.n_round);
+        }
+
+        let mut names = HashSet::new();
+        for role in &self.roles {
+            role.validate()?;
+            if !names.insert(role.name.as_str()) {
+                bail!("two roles are named {}", role.name);
+            }
+        }
+
+        let published: HashSet<&str> = self
+            .roles
+            .iter()
+            .flat_map(|role| role.actions.iter().map(String::as_str))
+            .chain(COMPANY_MESSAGES)
+            .collect();
+        for role in &self.roles {
+            for cause_by in role.watch.iter().chain(&role.context) {
+                if !published.contains(cause_by.as_str()) {
+                    bail!("role {} watches {} but nobody publishes it", role.name, cause_by);
+                }
+            }
+        }
+        if !self.roles.iter().any(|role| role.watch.iter().any(|cause_by| cause_by == "BossRequirement")) {
+            bail!("no role watches BossRequirement, the team would never start");
+        }
+        Ok(())
+    }
+
+    /// Instantiate the planned roles, ready for `SoftwareCompany::hire`.
+    pub fn hire(&self) -> anyhow::Result<Vec<Box<dyn Role>>> {
+        self.roles
+            .iter()
+            .map(|definition| DeclarativeRole::new(definition).map(|role| Box::new(role) as Box<dyn Role>))
+            .collect()
+    }
+}
+
+/// The roles the company knows how to staff, offered to the planner as a starting point.
+pub fn builtin_catalog() -> Vec<RoleDefinition> {
+    RoleDefinition::from_yaml(include_str!("../../../examples/roles.yaml")).unwrap_or_default()
+}
+
+fn strip_code_block(answer: &str) -> &str {
+    let answer = answer.trim();
+    let Some(start) = answer.find("```") else {
+        return answer;
+    };
+    let body = &answer[start + 3..];
+    // skip the language tag of the fence
+    let body = body.split_once('\n').map_or(body, |(_, rest)| rest);
+    body.find("```").map_or(body, |end| &body[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAN: &str = r#"Here is the team:
+```yaml
+n_round: 4
+roles:
+  - name: Alice
+    profile: Product Manager
+    actions: [WritePRD]
+    watch: [BossRequirement]
+  - name: Dana
+    profile: Data Engineer
+    goal: Design the data pipelines
+    actions: [WriteDesign]
+    watch: [WritePRD]
+```"#;
+
+    #[test]
+    fn test_team_plan_from_answer() {
+        let plan = TeamPlan::from_answer(PLAN).unwrap();
+        assert_eq!(plan.n_round, 4);
+        assert_eq!(plan.roles[1].profile, "Data Engineer");
+        assert_eq!(TeamPlan::from_answer(&plan.to_yaml()).unwrap(), plan);
+        assert_eq!(plan.hire().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_team_plan_validation() {
+        let orphan = PLAN.replace("watch: [WritePRD]", "watch: [WriteTasks]");
+        let error = TeamPlan::from_answer(&orphan).unwrap_err();
+        assert!(error.to_string().contains("watches WriteTasks but nobody publishes it"));
+
+        let no_start = PLAN.replace("watch: [BossRequirement]", "watch: [WriteDesign]");
+        assert!(TeamPlan::from_answer(&no_start).is_err());
+        assert!(TeamPlan::from_answer(&PLAN.replace("n_round: 4", "n_round: 0")).is_err());
+        assert_eq!(builtin_catalog().len(), 5);
+    }
+}
@@ -27,4 +27,80 @@ pub fn role_template(state: &str, history: &str, name: &str, result: &str) -> St
 ## Conversation history
 {history}
 {name}: {result}")
-}
\ No newline at end of file
+}
+pub fn react_template(prefix: &str, tools: &str, task: &str, scratchpad: &str) -> String {
+    format!("{prefix}
+Solve the task below step by step. You can use the following tools:
+{tools}
+
+Answer in exactly one of these two formats.
+To call a tool:
+Thought: what you need to find out or do next
+Action: the tool name
+Action Input: the input of the tool
+
+When you know the answer:
+Thought: why the task is complete
+Final Answer: the answer to the task
+
+## Task
+{task}
+
+## Previous steps
+{scratchpad}")
+}
+
+pub fn reflection_template(prefix: &str, goal: &str, constraints: &str, output: &str) -> String {
+    format!("{prefix}
+Critique the output below strictly against your goal and constraints before it is handed to the rest of the team.
+## Goal
+{goal}
+## Constraints
+{constraints}
+## Output
+{output}
+
+If the output fully meets the goal and respects every constraint, answer only LGTM.
+Otherwise list each problem on its own line starting with \"- \", and do not rewrite the output.")
+}
+
+pub fn team_plan_template(task: &str, actions: &str, catalog: &str, feedback: &str) -> String {
+    format!("You staff a team of AI roles for the task below. Reuse the known roles when they fit, \
+drop the ones the task does not need, and write new personas when the task needs another expertise.
+## Task
+{task}
+## Actions
+Every role runs some of these actions, each action publishes a message named after it:
+{actions}
+A role is triggered by the messages listed in `watch` and only reads the ones in `context`.
+BossRequirement is the task itself and RepositorySummary the code of an existing project.
+## Known roles
+{catalog}
+## Feedback on the previous proposal
+{feedback}
+
+Answer only a YAML document in this format:
+```yaml
+n_round: the number of rounds to run, about one per pipeline stage plus one
+roles:
+  - name: a first name
+    profile: the job title
+    goal: what the role must achieve
+    constraints: how it must work
+    actions: [one or more actions]
+    watch: [the messages triggering it]
+    context: [the messages it reads]
+```")
+}
+
+pub fn summary_template(prefix: &str, summary: &str, messages: &str, max_words: usize) -> String {
+    format!("{prefix}
+Your conversation records no longer fit in your context. Keep a summary of the older ones instead.
+## Summary so far
+{summary}
+## Messages to add to the summary
+{messages}
+
+Rewrite the summary so it also covers the messages above. Keep every requirement, decision, interface, file name and open question, drop greetings and repetition.
+Answer with the summary only, in at most {max_words} words.")
+}
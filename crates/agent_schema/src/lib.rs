@@ -1,5 +1,7 @@
 mod message;
 mod chat_history;
 
-pub use message::Message;
+pub use message::{
+    AIMessage, Attachment, ChatMessage, ChatRole, Message, SystemMessage, UserMessage, MESSAGE_ROUTE_TO_ALL,
+};
 pub use chat_history::ChatHistory;
\ No newline at end of file
@@ -1,5 +1,9 @@
 
-use std::{collections::HashMap, fmt};
+use std::{collections::{BTreeMap, HashMap, HashSet}, fmt, path::PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 
 #[derive(Debug, PartialEq)]
@@ -8,15 +12,89 @@ pub struct RawMessage {
     role: String,
 }
 
+/// `send_to` value delivering a message to every role, whatever they watch.
+pub const MESSAGE_ROUTE_TO_ALL: &str = "<all>";
+
+/// Typed content carried next to the text of a message.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Attachment {
+    /// A file of the workspace or of the repository.
+    File {
+        path: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mime_type: Option<String>,
+    },
+    /// An image, by URL or path.
+    Image {
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        alt: Option<String>,
+    },
+    /// A hit of a web search.
+    SearchResult {
+        title: String,
+        url: String,
+        #[serde(default)]
+        snippet: String,
+    },
+}
+
 // #[derive(Derivative)]
 // #[derivative(Default(new="true"), Clone, Debug, PartialEq)]
-#[derive(Default, Clone, Debug, PartialEq)]
+/// Messages are equal when they say the same thing: the same `content` from the same `role`
+/// and action, whatever their `id`, time or routing.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Message {
+    /// Unique and stable across clones, memories and checkpoints.
+    #[serde(default = "new_message_id")]
+    pub id: String,
+    /// When the message was created.
+    #[serde(default = "Utc::now")]
+    pub timestamp: DateTime<Utc>,
     pub content: String,
     pub role: String,
     pub cause_by: String,
     pub instruct_content: Option<String>,
-    pub send_to: Option<String>
+    /// Profiles of the roles the message is addressed to. Empty: delivered to the roles
+    /// watching `cause_by`. `MESSAGE_ROUTE_TO_ALL`: delivered to every role.
+    pub send_to: Vec<String>,
+    /// Turn of the run the message was published in, 0 before the first one.
+    #[serde(default)]
+    pub round: usize,
+    /// Free-form data about the message, e.g. the model that wrote it.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+}
+
+fn new_message_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Every message, including `..Default::default()` ones, gets a fresh id and timestamp.
+impl Default for Message {
+    fn default() -> Self {
+        Self {
+            id: new_message_id(),
+            timestamp: Utc::now(),
+            content: String::new(),
+            role: String::new(),
+            cause_by: String::new(),
+            instruct_content: None,
+            send_to: vec![],
+            round: 0,
+            metadata: BTreeMap::new(),
+            attachments: vec![],
+        }
+    }
+}
+
+impl PartialEq for Message {
+    fn eq(&self, other: &Self) -> bool {
+        self.content == other.content && self.role == other.role && self.cause_by == other.cause_by
+    }
 }
 
 impl fmt::Display for Message {
@@ -37,7 +115,7 @@ impl Message {
             role: role.to_string(),
             cause_by: cause_by.to_string(),
             instruct_content: Some(instruct_content.into()),
-            send_to:None
+            ..Default::default()
         }
     }
 
@@ -47,16 +125,84 @@ impl Message {
             role: role.to_string(),
             cause_by: cause_by.to_string(),
             instruct_content: Some(instruct_content.into()),
-            send_to:None
+            ..Default::default()
         }
     }
 
-    pub fn to_dict(&self) -> HashMap<String, String> {
-        let mut dict = HashMap::new();
-        dict.insert("role".to_string(), self.role.clone());
-        dict.insert("content".to_string(), self.content.clone());
-        dict
+    /// Address the message to the roles with these profiles only.
+    pub fn with_send_to(mut self, recipients: &[&str]) -> Self {
+        self.send_to = recipients.iter().map(|r| r.to_string()).collect();
+        self
+    }
+
+    pub fn with_round(mut self, round: usize) -> Self {
+        self.round = round;
+        self
     }
+
+    pub fn with_metadata(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.metadata.insert(key.to_string(), value.into());
+        self
+    }
+
+    pub fn with_attachment(mut self, attachment: Attachment) -> Self {
+        self.attachments.push(attachment);
+        self
+    }
+
+    /// Deliver the message to every role, whatever they watch.
+    pub fn broadcast(self) -> Self {
+        self.with_send_to(&[MESSAGE_ROUTE_TO_ALL])
+    }
+
+    /// Addressed to named roles rather than broadcast or routed by `cause_by`.
+    pub fn is_direct(&self) -> bool {
+        !self.send_to.is_empty() && !self.send_to.iter().any(|r| r == MESSAGE_ROUTE_TO_ALL)
+    }
+
+    /// Whether a role with this profile receives the message, `watch` being the `cause_by`
+    /// values it subscribes to.
+    pub fn is_delivered_to(&self, profile: &str, watch: &HashSet<String>) -> bool {
+        if self.send_to.is_empty() {
+            return watch.contains(&self.cause_by);
+        }
+        self.send_to.iter().any(|r| r == MESSAGE_ROUTE_TO_ALL || r == profile)
+    }
+
+    /// A message answering this one, addressed to its sender only.
+    pub fn reply(&self, content: &str, role: &str, cause_by: &str) -> Message {
+        Message {
+            content: content.to_string(),
+            role: role.to_string(),
+            cause_by: cause_by.to_string(),
+            send_to: vec![self.role.clone()],
+            ..Default::default()
+        }
+    }
+
+    /// Every field as a JSON value, lists and maps included.
+    pub fn to_dict(&self) -> HashMap<String, Value> {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(fields)) => fields.into_iter().collect(),
+            _ => HashMap::new(),
+        }
+    }
+}
+
+/// Author of a chat message, as the chat completion APIs know them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+}
+
+/// One message of a chat completion request, see `UserMessage`, `SystemMessage` and `AIMessage`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -86,8 +232,79 @@ impl AIMessage {
     }
 }
 
+macro_rules! chat_message {
+    ($wrapper:ident, $role:expr) => {
+        impl $wrapper {
+            pub fn message(&self) -> &Message {
+                &self.0
+            }
+        }
+
+        impl From<$wrapper> for Message {
+            fn from(message: $wrapper) -> Self {
+                message.0
+            }
+        }
+
+        impl From<$wrapper> for ChatMessage {
+            fn from(message: $wrapper) -> Self {
+                ChatMessage { role: $role, content: message.0.content }
+            }
+        }
+    };
+}
+
+chat_message!(UserMessage, ChatRole::User);
+chat_message!(SystemMessage, ChatRole::System);
+chat_message!(AIMessage, ChatRole::Assistant);
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routing() {
+        let watch = HashSet::from(["WritePRD".to_string()]);
+        let msg = Message { cause_by: "WritePRD".into(), role: "Product Manager".into(), ..Default::default() };
+        assert!(msg.is_delivered_to("Architect", &watch));
+        assert!(!msg.is_delivered_to("Engineer", &HashSet::new()));
+
+        let direct = msg.clone().with_send_to(&["Engineer", "QA Engineer"]);
+        assert!(direct.is_direct());
+        assert!(!direct.is_delivered_to("Architect", &watch));
+        assert!(direct.is_delivered_to("QA Engineer", &HashSet::new()));
+
+        let all = msg.clone().broadcast();
+        assert!(!all.is_direct());
+        assert!(all.is_delivered_to("Engineer", &HashSet::new()));
+
+        let reply = direct.reply("done", "Engineer", "WriteCode");
+        assert_eq!(reply.send_to, vec!["Product Manager"]);
+    }
+
+    #[test]
+    fn test_message_serde() {
+        let msg = Message::new("snake.py written", "Engineer", "WriteCode", "")
+            .with_round(3)
+            .with_metadata("model", "gpt-4")
+            .with_attachment(Attachment::File { path: "snake.py".into(), mime_type: None })
+            .with_attachment(Attachment::SearchResult { title: "Snake".into(), url: "https://example.com".into(), snippet: String::new() });
+        let json = serde_json::to_string(&msg).unwrap();
+        assert!(json.contains(r#"{"type":"file","path":"snake.py"}"#));
+        assert_eq!(serde_json::from_str::<Message>(&json).unwrap(), msg);
+        assert_eq!(msg.to_dict()["round"], Value::from(3));
+        assert_eq!(msg.to_dict()["metadata"]["model"], Value::from("gpt-4"));
+
+        // messages saved before these fields existed
+        let old: Message = serde_json::from_str(r#"{"content":"idea","role":"BOSS","cause_by":"BossRequirement","instruct_content":null,"send_to":[]}"#).unwrap();
+        assert_eq!((old.round, old.metadata.len(), old.attachments.len()), (0, 0, 0));
+        assert!(!old.id.is_empty());
+
+        let chat = ChatMessage::from(SystemMessage::new("You are an engineer"));
+        assert_eq!(chat.role, ChatRole::System);
+        assert_eq!(serde_json::to_value(&chat).unwrap()["role"], Value::from("system"));
+    }
+
     #[test]
     fn message() {
         // Placeholder for logs module
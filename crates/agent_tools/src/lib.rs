@@ -1,9 +1,94 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
 mod common;
 mod search_engine_serpapi;
 mod search_engine_google_native;
 mod search_engine_bing_native;
+mod tool;
 // pub mod downloader;
 pub mod types;
 
 pub use search_engine_serpapi::SerpAPIWrapper;
-pub use search_engine_google_native::GoogleSearchClient;
\ No newline at end of file
+pub use search_engine_google_native::GoogleSearchClient;
+pub use search_engine_bing_native::Bing;
+pub use tool::{Tool, SearchTool, FetchTool, FileTool, FileOperation};
+
+/// Search backend used by the search actions and tools.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchEngineType {
+    /// Google through serpapi.com, needs `SERPAPI_API_KEY`.
+    SerpApiGoogle,
+    /// Scrape the Google result page.
+    #[default]
+    DirectGoogle,
+    /// Scrape the Bing result page.
+    DirectBing,
+}
+
+impl FromStr for SearchEngineType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "_").as_str() {
+            "serp_api_google" | "serpapi" => Ok(SearchEngineType::SerpApiGoogle),
+            "direct_google" | "google" => Ok(SearchEngineType::DirectGoogle),
+            "direct_bing" | "bing" => Ok(SearchEngineType::DirectBing),
+            other => Err(format!("unknown search engine {}, expected serpapi, google or bing", other)),
+        }
+    }
+}
+
+impl fmt::Display for SearchEngineType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SearchEngineType::SerpApiGoogle => "serp_api_google",
+            SearchEngineType::DirectGoogle => "direct_google",
+            SearchEngineType::DirectBing => "direct_bing",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The engine `search_engine` creates.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchSettings {
+    pub engine: SearchEngineType,
+    pub serpapi_api_key: Option<String>,
+}
+
+impl SearchSettings {
+    /// `SEARCH_ENGINE` and `SERPAPI_API_KEY`, for the programs that do not configure the engine.
+    fn from_env() -> Self {
+        Self {
+            engine: std::env::var("SEARCH_ENGINE").ok().and_then(|name| name.parse().ok()).unwrap_or_default(),
+            serpapi_api_key: std::env::var("SERPAPI_API_KEY").ok(),
+        }
+    }
+}
+
+/// Set by `set_search_engine`, or read from the environment when a search runs first.
+static SEARCH: OnceLock<SearchSettings> = OnceLock::new();
+
+/// Search with `settings`, to be called at startup with the configured engine before any
+/// search action or tool is created.
+pub fn set_search_engine(settings: SearchSettings) {
+    if let Err(settings) = SEARCH.set(settings) {
+        warn!("the search engine was already set, ignoring {}", settings.engine);
+    }
+}
+
+/// The configured engine: `direct_google` by default.
+pub fn search_engine() -> Box<dyn types::SearchEngine> {
+    let settings = SEARCH.get_or_init(SearchSettings::from_env);
+    match settings.engine {
+        SearchEngineType::SerpApiGoogle => Box::new(SerpAPIWrapper::with_api_key(settings.serpapi_api_key.clone())),
+        SearchEngineType::DirectBing => Box::new(Bing),
+        SearchEngineType::DirectGoogle => Box::new(GoogleSearchClient),
+    }
+}
@@ -5,6 +5,7 @@ use scraper::{Html, Selector};
 
 use async_trait::async_trait;
 
+#[derive(Debug)]
 pub struct Bing;
 
 #[async_trait]
@@ -8,6 +8,7 @@ use scraper::{ElementRef, Html, Selector};
 use async_trait::async_trait;
 
 
+#[derive(Debug)]
 pub struct GoogleSearchClient;
 
 #[async_trait]
@@ -1,6 +1,9 @@
 use std::collections::HashMap;
 use std::error::Error;
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+
+use crate::types::{self, SearchEngine, SearchResult};
 // use reqwest;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -16,15 +19,18 @@ impl Default for SerpAPIWrapper {
 }
 
 impl SerpAPIWrapper {
+    /// Searching with `SERPAPI_API_KEY`.
     pub fn new() -> Self {
+        Self::with_api_key(std::env::var("SERPAPI_API_KEY").ok())
+    }
+
+    pub fn with_api_key(serpapi_api_key: Option<String>) -> Self {
         let mut params = HashMap::new();
         params.insert("engine".to_string(), "google".to_string());
         params.insert("google_domain".to_string(), "google.com".to_string());
         params.insert("gl".to_string(), "us".to_string());
         params.insert("hl".to_string(), "en".to_string());
 
-        let serpapi_api_key = std::env::var("SERPAPI_API_KEY").ok();
-
         SerpAPIWrapper {
             params,
             serpapi_api_key,
@@ -74,6 +80,29 @@ impl SerpAPIWrapper {
     
 }
 
+#[async_trait]
+impl SearchEngine for SerpAPIWrapper {
+    async fn search(&self, query: &str, _save_html_page: bool) -> Result<Vec<SearchResult>, types::Error> {
+        let response = self.results(query).await?;
+        let organic = response.get("organic_results").and_then(|results| results.as_array());
+        let field = |result: &serde_json::Value, key: &str| result.get(key).and_then(|value| value.as_str()).unwrap_or_default().to_string();
+        Ok(organic
+            .into_iter()
+            .flatten()
+            .map(|result| SearchResult {
+                title: field(result, "title"),
+                url: field(result, "link"),
+                content: field(result, "snippet"),
+                description: result.get("snippet").and_then(|value| value.as_str()).map(String::from),
+            })
+            .collect())
+    }
+
+    fn name(&self) -> String {
+        "SerpAPI".to_string()
+    }
+}
+
 // #[tokio::main]
 // async fn main() -> Result<(), Box<dyn Error>> {
 //     let query = "your_search_query_here";
@@ -0,0 +1,191 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use async_trait::async_trait;
+use reqwest::header::{ACCEPT_LANGUAGE, USER_AGENT};
+
+use crate::common::{APP_ACCEPT_LANGUAGE, APP_USER_AGENT};
+use crate::types::{Error, SearchEngine};
+
+/// Longest observation returned by a tool, longer results are truncated to keep prompts small.
+const MAX_OBSERVATION_CHARS: usize = 4000;
+
+/// A tool a role can call by name with a plain text input, e.g. from a ReAct loop.
+#[async_trait]
+pub trait Tool: Send + Sync {
+    fn name(&self) -> &str;
+    /// One line telling the LLM what the tool does and what input it expects.
+    fn description(&self) -> &str;
+    async fn call(&self, input: &str) -> Result<String, Error>;
+    /// Directory the project is written to, tools without files ignore it.
+    fn set_workspace(&mut self, _workspace: &Path) {}
+}
+
+/// Web search, the input is the query.
+pub struct SearchTool {
+    engine: Box<dyn SearchEngine>,
+}
+
+impl SearchTool {
+    pub fn new(engine: Box<dyn SearchEngine>) -> Self {
+        Self { engine }
+    }
+}
+
+#[async_trait]
+impl Tool for SearchTool {
+    fn name(&self) -> &str {
+        "search"
+    }
+
+    fn description(&self) -> &str {
+        "Search the web. Input: the search query."
+    }
+
+    async fn call(&self, input: &str) -> Result<String, Error> {
+        let results = self.engine.search(input.trim(), false).await?;
+        if results.is_empty() {
+            return Ok("No results.".to_string());
+        }
+        let text = results
+            .iter()
+            .map(|r| format!("- {} ({})\n  {}", r.title, r.url, r.description.as_deref().unwrap_or(&r.content)))
+            .collect::<Vec<String>>()
+            .join("\n");
+        Ok(truncate(text))
+    }
+}
+
+/// Download a web page and return its readable text.
+pub struct FetchTool;
+
+#[async_trait]
+impl Tool for FetchTool {
+    fn name(&self) -> &str {
+        "fetch"
+    }
+
+    fn description(&self) -> &str {
+        "Fetch a web page and return its main text. Input: the absolute URL."
+    }
+
+    async fn call(&self, input: &str) -> Result<String, Error> {
+        let url = url::Url::parse(input.trim()).map_err(|e| Error::Tool(format!("invalid url: {}", e)))?;
+        let body = reqwest::Client::new()
+            .get(url.clone())
+            .header(USER_AGENT, APP_USER_AGENT)
+            .header(ACCEPT_LANGUAGE, APP_ACCEPT_LANGUAGE)
+            .send()
+            .await?
+            .text()
+            .await?;
+        let text = match readability::extractor::extract(&mut body.as_bytes(), &url) {
+            Ok(product) => format!("{}\n{}", product.title, product.text),
+            Err(_) => body,
+        };
+        Ok(truncate(text))
+    }
+}
+
+/// Read, write and list files, confined to a root directory.
+pub struct FileTool {
+    root: PathBuf,
+    operation: FileOperation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileOperation {
+    Read,
+    Write,
+    List,
+}
+
+impl FileTool {
+    pub fn new(root: impl Into<PathBuf>, operation: FileOperation) -> Self {
+        Self { root: root.into(), operation }
+    }
+
+    /// Resolve `path` under the root, rejecting absolute paths and `..`.
+    fn resolve(&self, path: &str) -> Result<PathBuf, Error> {
+        let path = Path::new(path.trim());
+        if path.components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return Err(Error::Tool(format!("path {} must be relative to {}", path.display(), self.root.display())));
+        }
+        Ok(self.root.join(path))
+    }
+}
+
+#[async_trait]
+impl Tool for FileTool {
+    fn name(&self) -> &str {
+        match self.operation {
+            FileOperation::Read => "read_file",
+            FileOperation::Write => "write_file",
+            FileOperation::List => "list_files",
+        }
+    }
+
+    fn description(&self) -> &str {
+        match self.operation {
+            FileOperation::Read => "Read a file of the workspace. Input: the relative file path.",
+            FileOperation::Write => "Write a file of the workspace. Input: the relative file path on the first line, then the content.",
+            FileOperation::List => "List the files of a workspace directory. Input: the relative directory path, `.` for the root.",
+        }
+    }
+
+    async fn call(&self, input: &str) -> Result<String, Error> {
+        match self.operation {
+            FileOperation::Read => Ok(truncate(fs::read_to_string(self.resolve(input)?)?)),
+            FileOperation::Write => {
+                let (path, content) = input.trim_start().split_once('\n').unwrap_or((input, ""));
+                let path = self.resolve(path)?;
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&path, content)?;
+                Ok(format!("wrote {} bytes to {}", content.len(), path.display()))
+            }
+            FileOperation::List => {
+                let mut names: Vec<String> = fs::read_dir(self.resolve(input)?)?
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| {
+                        let name = entry.file_name().to_string_lossy().to_string();
+                        if entry.path().is_dir() { format!("{}/", name) } else { name }
+                    })
+                    .collect();
+                names.sort();
+                Ok(names.join("\n"))
+            }
+        }
+    }
+
+    fn set_workspace(&mut self, workspace: &Path) {
+        self.root = workspace.to_path_buf();
+    }
+}
+
+fn truncate(mut text: String) -> String {
+    if let Some((idx, _)) = text.char_indices().nth(MAX_OBSERVATION_CHARS) {
+        text.truncate(idx);
+        text.push_str("\n...(truncated)");
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_tools() {
+        let root = tempfile::tempdir().unwrap();
+        let write = FileTool::new(root.path(), FileOperation::Write);
+        let read = FileTool::new(root.path(), FileOperation::Read);
+        let list = FileTool::new(root.path(), FileOperation::List);
+
+        write.call("notes/a.txt\nhello").await.unwrap();
+        assert_eq!(read.call("notes/a.txt").await.unwrap(), "hello");
+        assert_eq!(list.call(".").await.unwrap(), "notes/");
+        assert!(read.call("../etc/passwd").await.is_err());
+    }
+}
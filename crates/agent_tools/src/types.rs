@@ -6,6 +6,9 @@ use std::fmt;
 pub enum Error {
     Reqwest(reqwest::Error),
     Serde(serde_json::Error),
+    Io(std::io::Error),
+    /// A tool rejected its input.
+    Tool(String),
 }
 
 impl From<reqwest::Error> for Error {
@@ -20,11 +23,19 @@ impl From<serde_json::Error> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Error::Reqwest(err) => write!(f, "Reqwest error: {}", err),
             Error::Serde(err) => write!(f, "Serde error: {}", err),
+            Error::Io(err) => write!(f, "IO error: {}", err),
+            Error::Tool(err) => write!(f, "Tool error: {}", err),
         }
     }
 }
@@ -40,7 +51,7 @@ pub struct SearchResult {
 }
 
 #[async_trait]
-pub trait SearchEngine: Send + Sync  {
+pub trait SearchEngine: Send + Sync + fmt::Debug {
     async fn search(&self, query: &str, save_html_page: bool) -> Result<Vec<SearchResult>, Error>;
 
     fn name(&self) -> String;
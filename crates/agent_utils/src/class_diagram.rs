@@ -0,0 +1,268 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref CLASS_RE: Regex = Regex::new(r"^class\s+([A-Za-z_][A-Za-z0-9_]*)(?:~[^~]*~)?\s*(\{)?\s*(\})?$").unwrap();
+    static ref MEMBER_LINE_RE: Regex = Regex::new(r"^([A-Za-z_][A-Za-z0-9_]*)\s*:\s*(.+)$").unwrap();
+    static ref RELATION_RE: Regex = Regex::new(
+        r#"^([A-Za-z_][A-Za-z0-9_]*)\s*(?:"[^"]*"\s*)?(<\|--|--\|>|\*--|--\*|o--|--o|<--|-->|\.\.\|>|<\|\.\.|\.\.>|<\.\.|--|\.\.)\s*(?:"[^"]*"\s*)?([A-Za-z_][A-Za-z0-9_]*)\s*(?::\s*(.*))?$"#
+    ).unwrap();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Visibility {
+    Public,
+    Private,
+    Protected,
+    Package,
+}
+
+impl Visibility {
+    fn parse(member: &str) -> (Self, &str) {
+        match member.chars().next() {
+            Some('+') => (Visibility::Public, &member[1..]),
+            Some('-') => (Visibility::Private, &member[1..]),
+            Some('#') => (Visibility::Protected, &member[1..]),
+            Some('~') => (Visibility::Package, &member[1..]),
+            _ => (Visibility::Public, member),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub visibility: Visibility,
+    pub name: String,
+    pub ty: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: String,
+    pub ty: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Method {
+    pub visibility: Visibility,
+    pub name: String,
+    pub params: Vec<Param>,
+    pub return_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClassDef {
+    pub name: String,
+    pub fields: Vec<Field>,
+    pub methods: Vec<Method>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RelationKind {
+    Inheritance,
+    Composition,
+    Aggregation,
+    Association,
+    Dependency,
+    Realization,
+    Link,
+}
+
+/// A relationship, normalized so that `from` is the child / owner / user, e.g. for
+/// `Animal <|-- Duck` the relation is `Duck` Inheritance `Animal`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relationship {
+    pub from: String,
+    pub to: String,
+    pub kind: RelationKind,
+    pub label: Option<String>,
+}
+
+/// Typed model of a mermaid `classDiagram`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClassDiagram {
+    pub classes: Vec<ClassDef>,
+    pub relationships: Vec<Relationship>,
+}
+
+impl ClassDiagram {
+    /// Parse the mermaid source, with or without the ```mermaid fence.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut lines = source
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with("%%") && !l.starts_with("```"));
+        match lines.next() {
+            Some(first) if first.starts_with("classDiagram") => {}
+            _ => return Err("not a mermaid classDiagram".to_string()),
+        }
+
+        let mut diagram = ClassDiagram::default();
+        let mut current: Option<usize> = None;
+        for line in lines {
+            if let Some(index) = current {
+                if line == "}" {
+                    current = None;
+                } else {
+                    diagram.classes[index].add_member(line);
+                }
+                continue;
+            }
+            if let Some(caps) = CLASS_RE.captures(line) {
+                let index = diagram.class_index(&caps[1]);
+                if caps.get(2).is_some() && caps.get(3).is_none() {
+                    current = Some(index);
+                }
+            } else if let Some(caps) = RELATION_RE.captures(line) {
+                diagram.class_index(&caps[1]);
+                diagram.class_index(&caps[3]);
+                let (left, right) = (caps[1].to_string(), caps[3].to_string());
+                let label = caps.get(4).map(|m| m.as_str().trim().to_string()).filter(|l| !l.is_empty());
+                let (kind, from, to) = match &caps[2] {
+                    "<|--" => (RelationKind::Inheritance, right, left),
+                    "--|>" => (RelationKind::Inheritance, left, right),
+                    "<|.." => (RelationKind::Realization, right, left),
+                    "..|>" => (RelationKind::Realization, left, right),
+                    "*--" => (RelationKind::Composition, left, right),
+                    "--*" => (RelationKind::Composition, right, left),
+                    "o--" => (RelationKind::Aggregation, left, right),
+                    "--o" => (RelationKind::Aggregation, right, left),
+                    "-->" => (RelationKind::Association, left, right),
+                    "<--" => (RelationKind::Association, right, left),
+                    "..>" => (RelationKind::Dependency, left, right),
+                    "<.." => (RelationKind::Dependency, right, left),
+                    _ => (RelationKind::Link, left, right),
+                };
+                diagram.relationships.push(Relationship { from, to, kind, label });
+            } else if let Some(caps) = MEMBER_LINE_RE.captures(line) {
+                let index = diagram.class_index(&caps[1]);
+                diagram.classes[index].add_member(caps[2].trim());
+            }
+        }
+        if current.is_some() {
+            return Err("unterminated class body".to_string());
+        }
+        Ok(diagram)
+    }
+
+    pub fn class(&self, name: &str) -> Option<&ClassDef> {
+        self.classes.iter().find(|c| c.name == name)
+    }
+
+    /// The classes `name` inherits from or realizes.
+    pub fn parents(&self, name: &str) -> Vec<&str> {
+        self.relationships
+            .iter()
+            .filter(|r| r.from == name && matches!(r.kind, RelationKind::Inheritance | RelationKind::Realization))
+            .map(|r| r.to.as_str())
+            .collect()
+    }
+
+    fn class_index(&mut self, name: &str) -> usize {
+        match self.classes.iter().position(|c| c.name == name) {
+            Some(index) => index,
+            None => {
+                self.classes.push(ClassDef { name: name.to_string(), ..Default::default() });
+                self.classes.len() - 1
+            }
+        }
+    }
+}
+
+impl ClassDef {
+    fn add_member(&mut self, member: &str) {
+        // drop the static / abstract classifiers
+        let member = member.trim_end_matches(['$', '*']).trim();
+        let (visibility, member) = Visibility::parse(member);
+        match (member.find('('), member.rfind(')')) {
+            (Some(open), Some(close)) if open < close => {
+                let name = member[..open].trim().to_string();
+                let params = member[open + 1..close]
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|p| !p.is_empty() && *p != "self")
+                    .map(parse_param)
+                    .collect();
+                let return_type = member[close + 1..]
+                    .trim()
+                    .trim_start_matches("->")
+                    .trim_start_matches(':')
+                    .trim();
+                self.methods.push(Method {
+                    visibility,
+                    name,
+                    params,
+                    return_type: (!return_type.is_empty()).then(|| return_type.to_string()),
+                });
+            }
+            _ => {
+                let (name, ty) = split_typed_name(member);
+                self.fields.push(Field { visibility, name, ty });
+            }
+        }
+    }
+}
+
+fn parse_param(param: &str) -> Param {
+    let param = param.split('=').next().unwrap_or(param).trim();
+    let (name, ty) = split_typed_name(param);
+    Param { name, ty }
+}
+
+/// Accept both `name: Type` and `Type name`.
+fn split_typed_name(text: &str) -> (String, Option<String>) {
+    if let Some((name, ty)) = text.split_once(':') {
+        return (name.trim().to_string(), Some(ty.trim().to_string()));
+    }
+    match text.trim().rsplit_once(char::is_whitespace) {
+        Some((ty, name)) => (name.trim().to_string(), Some(ty.trim().to_string())),
+        None => (text.trim().to_string(), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIAGRAM: &str = r#"
+classDiagram
+    class Game{
+        +int score
+        -List[Food] foods
+        +__init__(self, width: int, height: int)
+        +start_game() void
+        +update(dir: str) bool
+    }
+    class Snake{
+        +move(direction: str)
+    }
+    Animal <|-- Snake
+    Game "1" -- "1" Snake: has
+    Game *-- Food
+"#;
+
+    #[test]
+    fn test_parse_classes() {
+        let diagram = ClassDiagram::parse(DIAGRAM).unwrap();
+        let names: Vec<&str> = diagram.classes.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["Game", "Snake", "Animal", "Food"]);
+
+        let game = diagram.class("Game").unwrap();
+        assert_eq!(game.fields[0], Field { visibility: Visibility::Public, name: "score".into(), ty: Some("int".into()) });
+        assert_eq!(game.fields[1].visibility, Visibility::Private);
+        assert_eq!(game.fields[1].ty.as_deref(), Some("List[Food]"));
+        assert_eq!(game.methods[0].params.len(), 2);
+        assert_eq!(game.methods[1].return_type.as_deref(), Some("void"));
+        assert_eq!(game.methods[2].params[0], Param { name: "dir".into(), ty: Some("str".into()) });
+    }
+
+    #[test]
+    fn test_parse_relationships() {
+        let diagram = ClassDiagram::parse(DIAGRAM).unwrap();
+        assert_eq!(diagram.parents("Snake"), vec!["Animal"]);
+        assert_eq!(diagram.relationships[1].kind, RelationKind::Link);
+        assert_eq!(diagram.relationships[1].label.as_deref(), Some("has"));
+        assert_eq!(diagram.relationships[2].kind, RelationKind::Composition);
+        assert_eq!(diagram.relationships[2].from, "Game");
+    }
+}
@@ -1,9 +1,19 @@
 
 mod code_parser;
 mod mermaid;
+mod repo_index;
+mod openapi;
+mod class_diagram;
+mod skeleton;
 pub mod file_ops;
 pub mod url_ops;
 pub mod html_ops;
 pub mod download_pdf;
+mod pdf;
 pub use code_parser::CodeParser;
 pub use mermaid::{save_diagram, async_save_diagram};
+pub use repo_index::{RepoIndex, Symbol};
+pub use openapi::{validate_openapi, openapi_endpoints};
+pub use class_diagram::{ClassDiagram, ClassDef, Field, Method, Param, Relationship, RelationKind, Visibility};
+pub use skeleton::{SkeletonFile, SkeletonLanguage, generate_skeleton, skeleton_to_markdown};
+pub use pdf::{PdfDocument, WebPage, chunk_text, fetch_page, fetch_pdf, is_pdf_url};
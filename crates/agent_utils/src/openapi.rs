@@ -0,0 +1,176 @@
+use serde_yaml::Value;
+
+const HTTP_METHODS: [&str; 8] = ["get", "put", "post", "delete", "options", "head", "patch", "trace"];
+/// Keys allowed next to the operations of a path item.
+const PATH_ITEM_FIELDS: [&str; 4] = ["summary", "description", "parameters", "servers"];
+
+/// Structurally validate an OpenAPI 3 document written in YAML: required top-level fields,
+/// path and operation shape, and that every local `$ref` resolves.
+/// Returns every problem found instead of stopping at the first one.
+pub fn validate_openapi(yaml: &str) -> Result<(), Vec<String>> {
+    let doc: Value = match serde_yaml::from_str(yaml) {
+        Ok(doc) => doc,
+        Err(e) => return Err(vec![format!("invalid YAML: {}", e)]),
+    };
+    let mut errors = Vec::new();
+
+    match doc.get("openapi").and_then(yaml_to_string) {
+        Some(version) if version.starts_with("3.") => {}
+        Some(version) => errors.push(format!("unsupported openapi version {}", version)),
+        None => errors.push("missing `openapi` version".to_string()),
+    }
+    match doc.get("info") {
+        Some(info) => {
+            for field in ["title", "version"] {
+                if info.get(field).and_then(yaml_to_string).is_none() {
+                    errors.push(format!("missing `info.{}`", field));
+                }
+            }
+        }
+        None => errors.push("missing `info`".to_string()),
+    }
+
+    match doc.get("paths").and_then(Value::as_mapping) {
+        Some(paths) if !paths.is_empty() => {
+            for (path, item) in paths {
+                let path = path.as_str().unwrap_or_default();
+                if !path.starts_with('/') {
+                    errors.push(format!("path `{}` must start with `/`", path));
+                }
+                let Some(item) = item.as_mapping() else {
+                    errors.push(format!("path `{}` must be a mapping", path));
+                    continue;
+                };
+                for (method, operation) in item {
+                    let method = method.as_str().unwrap_or_default();
+                    if PATH_ITEM_FIELDS.contains(&method) {
+                        continue;
+                    }
+                    if !HTTP_METHODS.contains(&method) {
+                        errors.push(format!("{}: unknown operation `{}`", path, method));
+                        continue;
+                    }
+                    let has_responses = operation
+                        .get("responses")
+                        .and_then(Value::as_mapping)
+                        .map(|r| !r.is_empty())
+                        .unwrap_or(false);
+                    if !has_responses {
+                        errors.push(format!("{} {}: missing `responses`", method.to_uppercase(), path));
+                    }
+                }
+            }
+        }
+        _ => errors.push("missing or empty `paths`".to_string()),
+    }
+
+    let mut refs = Vec::new();
+    collect_refs(&doc, &mut refs);
+    for reference in refs {
+        if resolve_ref(&doc, &reference).is_none() {
+            errors.push(format!("unresolved $ref `{}`", reference));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// List the `METHOD /path` endpoints of a valid document, used to summarize it in prompts.
+pub fn openapi_endpoints(yaml: &str) -> Vec<String> {
+    let Ok(doc) = serde_yaml::from_str::<Value>(yaml) else { return vec![] };
+    let Some(paths) = doc.get("paths").and_then(Value::as_mapping) else { return vec![] };
+    let mut endpoints = Vec::new();
+    for (path, item) in paths {
+        let Some(item) = item.as_mapping() else { continue };
+        for method in item.keys().filter_map(Value::as_str) {
+            if HTTP_METHODS.contains(&method) {
+                endpoints.push(format!("{} {}", method.to_uppercase(), path.as_str().unwrap_or_default()));
+            }
+        }
+    }
+    endpoints
+}
+
+fn yaml_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) if !s.trim().is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn collect_refs(value: &Value, refs: &mut Vec<String>) {
+    match value {
+        Value::Mapping(map) => {
+            for (key, value) in map {
+                if key.as_str() == Some("$ref") {
+                    if let Some(reference) = value.as_str() {
+                        refs.push(reference.to_string());
+                    }
+                } else {
+                    collect_refs(value, refs);
+                }
+            }
+        }
+        Value::Sequence(seq) => seq.iter().for_each(|v| collect_refs(v, refs)),
+        _ => {}
+    }
+}
+
+/// Resolve a local `#/a/b` reference; external references are not checked.
+fn resolve_ref<'a>(doc: &'a Value, reference: &str) -> Option<&'a Value> {
+    let Some(pointer) = reference.strip_prefix("#/") else { return Some(doc) };
+    pointer
+        .split('/')
+        .try_fold(doc, |node, part| node.get(part.replace("~1", "/").replace("~0", "~").as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = r#"
+openapi: 3.0.0
+info:
+  title: Snake Game API
+  version: 1.0.0
+paths:
+  /start_game:
+    post:
+      summary: Start a new game
+      responses:
+        '200':
+          description: Started
+          content:
+            application/json:
+              schema:
+                $ref: '#/components/schemas/Game'
+components:
+  schemas:
+    Game:
+      type: object
+"#;
+
+    #[test]
+    fn test_validate_openapi() {
+        assert_eq!(validate_openapi(SPEC), Ok(()));
+        assert_eq!(openapi_endpoints(SPEC), vec!["POST /start_game"]);
+    }
+
+    #[test]
+    fn test_validate_openapi_errors() {
+        let spec = SPEC.replace("#/components/schemas/Game", "#/components/schemas/Food").replace("post:", "push:");
+        let errors = validate_openapi(&spec).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                "/start_game: unknown operation `push`".to_string(),
+                "unresolved $ref `#/components/schemas/Food`".to_string(),
+            ]
+        );
+    }
+}
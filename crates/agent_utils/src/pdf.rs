@@ -0,0 +1,236 @@
+use anyhow::{anyhow, bail, Result};
+use lopdf::{Dictionary, Document, Object};
+use readability::extractor;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use url::Url;
+
+const USER_AGENT: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/116.0.0.0 Safari/537.36";
+
+/// Text of a PDF, one entry per page, with the title and author of its Info dictionary.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PdfDocument {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub pages: Vec<String>,
+}
+
+impl PdfDocument {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let document = Document::load_mem(bytes)?;
+        let info = document
+            .trailer
+            .get(b"Info")
+            .and_then(|info| document.dereference(info))
+            .and_then(|(_, info)| info.as_dict())
+            .ok();
+
+        // a page whose content cannot be decoded is kept empty so page numbers stay right
+        let pages = document
+            .get_pages()
+            .keys()
+            .map(|page| document.extract_text(&[*page]).unwrap_or_default().trim().to_string())
+            .collect();
+        Ok(Self {
+            title: info.and_then(|info| info_string(&document, info, b"Title")),
+            author: info.and_then(|info| info_string(&document, info, b"Author")),
+            pages,
+        })
+    }
+
+    /// The whole document, every page under a `## Page N` heading.
+    pub fn to_text(&self) -> String {
+        self.sections().join("\n\n")
+    }
+
+    /// Split the document into chunks of at most `max_chars` characters.
+    /// Pages are kept together when they fit, longer pages are cut at paragraph
+    /// boundaries, and every piece keeps its `## Page N` heading.
+    pub fn chunks(&self, max_chars: usize) -> Vec<String> {
+        let mut chunks = vec![];
+        let mut current = String::new();
+        for (heading, text) in self.numbered_pages() {
+            let budget = max_chars.saturating_sub(heading.chars().count() + 1).max(1);
+            for piece in chunk_text(text, budget) {
+                let piece = format!("{}\n{}", heading, piece);
+                if !current.is_empty() && current.chars().count() + piece.chars().count() + 2 > max_chars {
+                    chunks.push(std::mem::take(&mut current));
+                }
+                if !current.is_empty() {
+                    current.push_str("\n\n");
+                }
+                current.push_str(&piece);
+            }
+        }
+        if !current.is_empty() {
+            chunks.push(current);
+        }
+        chunks
+    }
+
+    fn sections(&self) -> Vec<String> {
+        self.numbered_pages()
+            .map(|(heading, text)| format!("{}\n{}", heading, text))
+            .collect()
+    }
+
+    /// Non empty pages with their `## Page N` heading.
+    fn numbered_pages(&self) -> impl Iterator<Item = (String, &str)> {
+        self.pages
+            .iter()
+            .enumerate()
+            .filter(|(_, text)| !text.is_empty())
+            .map(|(idx, text)| (format!("## Page {}", idx + 1), text.as_str()))
+    }
+}
+
+/// Download a PDF and extract its text.
+pub async fn fetch_pdf(pdf_url: &str) -> Result<PdfDocument> {
+    let client = Client::builder().user_agent(USER_AGENT).build()?;
+    let response = client.get(pdf_url).send().await?;
+    if !response.status().is_success() {
+        bail!("failed to download {}: {}", pdf_url, response.status());
+    }
+    let bytes = response.bytes().await?;
+    PdfDocument::from_bytes(&bytes)
+}
+
+/// A downloaded page: the readable text of an HTML page, or a PDF.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WebPage {
+    Html(String),
+    Pdf(PdfDocument),
+}
+
+/// Whether the path of `url` names a PDF: a `.pdf` file in any case, whatever the query
+/// string (e.g. `?download=1`), or an arXiv `/pdf/<id>` link.
+pub fn is_pdf_url(url: &str) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return url.to_lowercase().ends_with(".pdf");
+    };
+    let path = url.path().to_lowercase();
+    let arxiv = url.host_str().is_some_and(|host| host == "arxiv.org" || host.ends_with(".arxiv.org"));
+    path.ends_with(".pdf") || (arxiv && path.starts_with("/pdf/"))
+}
+
+/// Download `url` and extract its text. A PDF is recognized by its `Content-Type`, or by
+/// its URL when the server does not say, see `is_pdf_url`.
+pub async fn fetch_page(url: &str) -> Result<WebPage> {
+    let parsed = Url::parse(url)?;
+    let client = Client::builder().user_agent(USER_AGENT).build()?;
+    let response = client.get(parsed.clone()).send().await?;
+    if !response.status().is_success() {
+        bail!("failed to download {}: {}", url, response.status());
+    }
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_lowercase());
+    let is_pdf = match content_type.as_deref() {
+        Some(content_type) if content_type.starts_with("application/pdf") => true,
+        None | Some("application/octet-stream") => is_pdf_url(url),
+        Some(_) => false,
+    };
+    let bytes = response.bytes().await?;
+    if is_pdf {
+        return Ok(WebPage::Pdf(PdfDocument::from_bytes(&bytes)?));
+    }
+    let product = extractor::extract(&mut bytes.as_ref(), &parsed).map_err(|e| anyhow!("{}", e))?;
+    Ok(WebPage::Html(product.text))
+}
+
+/// Split `text` into pieces of at most `max_chars` characters, preferring paragraph,
+/// then line boundaries, and cutting inside a line only when it is longer than `max_chars`.
+pub fn chunk_text(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut chunks = vec![];
+    let mut current = String::new();
+    let push = |chunks: &mut Vec<String>, current: &mut String, part: &str, separator: &str| {
+        if !current.is_empty() && current.chars().count() + separator.len() + part.chars().count() > max_chars {
+            chunks.push(std::mem::take(current));
+        }
+        if !current.is_empty() {
+            current.push_str(separator);
+        }
+        current.push_str(part);
+    };
+
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        if paragraph.chars().count() <= max_chars {
+            push(&mut chunks, &mut current, paragraph, "\n\n");
+            continue;
+        }
+        for line in paragraph.lines() {
+            let chars: Vec<char> = line.chars().collect();
+            for part in chars.chunks(max_chars) {
+                push(&mut chunks, &mut current, &part.iter().collect::<String>(), "\n");
+            }
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+fn info_string(document: &Document, info: &Dictionary, key: &[u8]) -> Option<String> {
+    let (_, value) = document.dereference(info.get(key).ok()?).ok()?;
+    let text = match value {
+        Object::String(bytes, _) => decode_pdf_string(bytes),
+        _ => return None,
+    };
+    let text = text.trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Info strings are UTF-16BE when they start with a byte order mark, PDFDocEncoding otherwise.
+fn decode_pdf_string(bytes: &[u8]) -> String {
+    match bytes {
+        [0xFE, 0xFF, rest @ ..] => {
+            let units: Vec<u16> = rest.chunks(2).map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => bytes.iter().map(|b| *b as char).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_text() {
+        let text = "first paragraph\n\nsecond paragraph\n\n".to_string() + &"x".repeat(25);
+        let chunks = chunk_text(&text, 20);
+        assert_eq!(chunks, vec!["first paragraph", "second paragraph", &"x".repeat(20), "xxxxx"]);
+        assert!(chunk_text("", 20).is_empty());
+    }
+
+    #[test]
+    fn test_pdf_chunks() {
+        let document = PdfDocument {
+            title: Some("Paper".into()),
+            author: None,
+            pages: vec!["intro".into(), String::new(), "a".repeat(30)],
+        };
+        assert_eq!(document.to_text(), format!("## Page 1\nintro\n\n## Page 3\n{}", "a".repeat(30)));
+        let chunks = document.chunks(40);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0], "## Page 1\nintro");
+        assert!(chunks[1].starts_with("## Page 3\n"));
+        assert_eq!(decode_pdf_string(&[0xFE, 0xFF, 0x00, 0x41, 0x00, 0x42]), "AB");
+        assert!(PdfDocument::from_bytes(b"not a pdf").is_err());
+    }
+
+    #[test]
+    fn test_is_pdf_url() {
+        assert!(is_pdf_url("https://example.com/paper.pdf"));
+        assert!(is_pdf_url("https://example.com/Paper.PDF?download=1"));
+        assert!(is_pdf_url("https://arxiv.org/pdf/2308.00352"));
+        assert!(is_pdf_url("https://export.arxiv.org/pdf/2308.00352v2"));
+        assert!(!is_pdf_url("https://arxiv.org/abs/2308.00352"));
+        assert!(!is_pdf_url("https://example.com/pdf/viewer"));
+        assert!(!is_pdf_url("https://example.com/page?file=paper.pdf"));
+    }
+}
@@ -0,0 +1,186 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// Directories that never contain code worth showing to the agents.
+const IGNORED_DIRS: [&str; 9] = [
+    ".git", "target", "node_modules", "__pycache__", ".venv", "venv", "dist", "build", "workshop",
+];
+const MAX_FILES: usize = 500;
+const MAX_SYMBOLS_PER_FILE: usize = 40;
+const MAX_README_CHARS: usize = 4000;
+
+lazy_static! {
+    static ref RUST_SYMBOL_RE: Regex =
+        Regex::new(r"^\s*pub\s+(?:async\s+)?(fn|struct|enum|trait|type|mod|const)\s+([A-Za-z_][A-Za-z0-9_]*)").unwrap();
+    static ref PYTHON_SYMBOL_RE: Regex =
+        Regex::new(r"^(?:async\s+)?(def|class)\s+([A-Za-z][A-Za-z0-9_]*)").unwrap();
+    static ref JS_SYMBOL_RE: Regex =
+        Regex::new(r"^export\s+(?:default\s+)?(?:async\s+)?(function|class|const|interface|type)\s+([A-Za-z_$][A-Za-z0-9_$]*)").unwrap();
+    static ref GO_SYMBOL_RE: Regex =
+        Regex::new(r"^(func|type)\s+(?:\([^)]*\)\s*)?([A-Z][A-Za-z0-9_]*)").unwrap();
+}
+
+/// A public symbol found in a source file, e.g. `("fn", "parse_code")`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub kind: String,
+    pub name: String,
+}
+
+/// Index of an existing repository: its file tree, the public symbols of each
+/// source file and the README, used to run the agents against existing code.
+#[derive(Debug, Default, Clone)]
+pub struct RepoIndex {
+    pub root: PathBuf,
+    pub files: Vec<String>,
+    pub symbols: Vec<(String, Vec<Symbol>)>,
+    pub readme: Option<String>,
+}
+
+impl RepoIndex {
+    /// Walk `root` and collect the file tree, public symbols and README.
+    pub fn scan(root: &Path) -> anyhow::Result<Self> {
+        if !root.is_dir() {
+            return Err(anyhow::anyhow!("{} is not a directory", root.display()));
+        }
+        let mut index = RepoIndex {
+            root: root.to_path_buf(),
+            ..Default::default()
+        };
+        let mut paths = Vec::new();
+        collect_files(root, &mut paths)?;
+        paths.sort();
+
+        for path in paths.into_iter().take(MAX_FILES) {
+            let relative = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+
+            if index.readme.is_none() && is_readme(&path) {
+                if let Ok(text) = fs::read_to_string(&path) {
+                    index.readme = Some(text.chars().take(MAX_README_CHARS).collect());
+                }
+            }
+            if let Some(re) = symbol_regex(&path) {
+                if let Ok(text) = fs::read_to_string(&path) {
+                    let symbols = extract_symbols(re, &text);
+                    if !symbols.is_empty() {
+                        index.symbols.push((relative.clone(), symbols));
+                    }
+                }
+            }
+            index.files.push(relative);
+        }
+        Ok(index)
+    }
+
+    /// Render the index as a markdown summary suitable for a prompt context.
+    pub fn to_summary(&self) -> String {
+        let name = self
+            .root
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.root.display().to_string());
+
+        let mut summary = format!("# Repository: {}\n\n## File tree\n```text\n", name);
+        for file in &self.files {
+            summary.push_str(file);
+            summary.push('\n');
+        }
+        summary.push_str("```\n\n## Public symbols\n");
+        if self.symbols.is_empty() {
+            summary.push_str("None found.\n");
+        }
+        for (file, symbols) in &self.symbols {
+            let list = symbols
+                .iter()
+                .map(|s| format!("{} {}", s.kind, s.name))
+                .collect::<Vec<String>>()
+                .join(", ");
+            summary.push_str(&format!("- {}: {}\n", file, list));
+        }
+        summary.push_str("\n## README\n");
+        match &self.readme {
+            Some(readme) => summary.push_str(readme.trim()),
+            None => summary.push_str("No README found."),
+        }
+        summary.push('\n');
+        summary
+    }
+}
+
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        if path.is_dir() {
+            if name.starts_with('.') || IGNORED_DIRS.contains(&name.as_str()) {
+                continue;
+            }
+            collect_files(&path, out)?;
+        } else if !name.starts_with('.') {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_readme(path: &Path) -> bool {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().eq_ignore_ascii_case("readme"))
+        .unwrap_or(false)
+}
+
+fn symbol_regex(path: &Path) -> Option<&'static Regex> {
+    match path.extension()?.to_str()? {
+        "rs" => Some(&RUST_SYMBOL_RE),
+        "py" => Some(&PYTHON_SYMBOL_RE),
+        "js" | "jsx" | "ts" | "tsx" => Some(&JS_SYMBOL_RE),
+        "go" => Some(&GO_SYMBOL_RE),
+        _ => None,
+    }
+}
+
+fn extract_symbols(re: &Regex, text: &str) -> Vec<Symbol> {
+    text.lines()
+        .filter_map(|line| re.captures(line))
+        .map(|caps| Symbol {
+            kind: caps[1].to_string(),
+            name: caps[2].to_string(),
+        })
+        .take(MAX_SYMBOLS_PER_FILE)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_rust_symbols() {
+        let text = "pub struct Game {\n}\nfn private() {}\npub async fn run() {}\n";
+        let symbols = extract_symbols(&RUST_SYMBOL_RE, text);
+        assert_eq!(
+            symbols,
+            vec![
+                Symbol { kind: "struct".into(), name: "Game".into() },
+                Symbol { kind: "fn".into(), name: "run".into() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_python_symbols() {
+        let text = "class Snake:\n    def move(self):\n        pass\ndef main():\n    pass\n";
+        let names: Vec<String> = extract_symbols(&PYTHON_SYMBOL_RE, text)
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, vec!["Snake", "main"]);
+    }
+}
@@ -0,0 +1,312 @@
+use serde::{Deserialize, Serialize};
+
+use crate::class_diagram::{ClassDef, ClassDiagram, RelationKind, Visibility};
+
+/// Target language of the generated skeleton files, Python by default like the design prompts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SkeletonLanguage {
+    #[default]
+    #[serde(alias = "py")]
+    Python,
+    #[serde(alias = "rs")]
+    Rust,
+}
+
+impl SkeletonLanguage {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "python" | "py" => Some(SkeletonLanguage::Python),
+            "rust" | "rs" => Some(SkeletonLanguage::Rust),
+            _ => None,
+        }
+    }
+
+    /// Extension of the source files.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SkeletonLanguage::Python => "py",
+            SkeletonLanguage::Rust => "rs",
+        }
+    }
+
+    /// Language of the markdown code blocks.
+    pub fn fence(&self) -> &'static str {
+        match self {
+            SkeletonLanguage::Python => "python",
+            SkeletonLanguage::Rust => "rust",
+        }
+    }
+}
+
+/// A generated file, `path` is relative to the skeleton root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkeletonFile {
+    pub path: String,
+    pub content: String,
+}
+
+/// Generate one compilable file per class, with every field and a stub for every method,
+/// so the engineer fills in bodies instead of inventing the structure.
+pub fn generate_skeleton(diagram: &ClassDiagram, language: SkeletonLanguage) -> Vec<SkeletonFile> {
+    match language {
+        SkeletonLanguage::Python => diagram.classes.iter().map(|c| python_class(diagram, c)).collect(),
+        SkeletonLanguage::Rust => {
+            let mut files: Vec<SkeletonFile> = diagram.classes.iter().map(|c| rust_struct(diagram, c)).collect();
+            let mut module = String::new();
+            for class in &diagram.classes {
+                module.push_str(&format!("mod {};\n", snake_case(&class.name)));
+            }
+            module.push('\n');
+            for class in &diagram.classes {
+                module.push_str(&format!("pub use {}::{};\n", snake_case(&class.name), class.name));
+            }
+            files.push(SkeletonFile { path: "mod.rs".to_string(), content: module });
+            files
+        }
+    }
+}
+
+/// Render the skeleton as markdown code blocks for a prompt.
+pub fn skeleton_to_markdown(files: &[SkeletonFile], language: SkeletonLanguage) -> String {
+    files
+        .iter()
+        .map(|f| format!("### {}\n```{}\n{}```\n", f.path, language.fence(), f.content))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// `HTTPServer` -> `http_server`: a word starts at a capital after a lower case letter or a
+/// digit, or at the last capital of a run followed by a lower case letter.
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() {
+            let prev = i.checked_sub(1).map(|j| chars[j]);
+            let next = chars.get(i + 1);
+            let starts_word = match prev {
+                Some(p) if p.is_lowercase() || p.is_ascii_digit() => true,
+                Some(p) if p.is_uppercase() => next.is_some_and(|n| n.is_lowercase()),
+                _ => false,
+            };
+            if starts_word && !out.ends_with('_') {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(*c);
+        }
+    }
+    out
+}
+
+/// Mermaid writes generics as `List~int~`, turn that into `List[int]`.
+fn normalize_generics(ty: &str) -> String {
+    let chars: Vec<char> = ty.trim().chars().collect();
+    let mut out = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        match (c, chars.get(i + 1)) {
+            ('~', None | Some('~' | ',' | ']' | ' ')) => out.push(']'),
+            ('~', _) => out.push('['),
+            _ => out.push(*c),
+        }
+    }
+    out
+}
+
+/// Split `a, B[c, d]` on the top-level commas only.
+fn split_type_args(args: &str) -> Vec<String> {
+    let (mut parts, mut current, mut depth) = (vec![], String::new(), 0);
+    for c in args.chars() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    parts.push(current.trim().to_string());
+    parts
+}
+
+fn python_type(ty: &Option<String>) -> String {
+    let Some(ty) = ty.as_deref().map(normalize_generics).filter(|t| !t.is_empty()) else { return "Any".to_string() };
+    if let Some(inner) = ty.strip_suffix("[]") {
+        return format!("List[{}]", python_type(&Some(inner.to_string())));
+    }
+    match ty.as_str() {
+        "void" => "None".to_string(),
+        "string" => "str".to_string(),
+        "boolean" => "bool".to_string(),
+        "integer" => "int".to_string(),
+        "double" | "number" => "float".to_string(),
+        _ => ty,
+    }
+}
+
+fn python_class(diagram: &ClassDiagram, class: &ClassDef) -> SkeletonFile {
+    let parents = diagram.parents(&class.name);
+    let mut code = String::from("from __future__ import annotations\n\nfrom typing import Any, Dict, List, Optional, Tuple\n");
+    for parent in &parents {
+        code.push_str(&format!("\nfrom {} import {}", snake_case(parent), parent));
+    }
+    let bases = if parents.is_empty() { String::new() } else { format!("({})", parents.join(", ")) };
+    code.push_str(&format!("\n\n\nclass {}{}:\n", class.name, bases));
+
+    let has_init = class.methods.iter().any(|m| m.name == "__init__");
+    if !has_init {
+        code.push_str("    def __init__(self) -> None:\n");
+        python_init_body(&mut code, class);
+    }
+    for method in &class.methods {
+        let mut params = vec!["self".to_string()];
+        params.extend(method.params.iter().map(|p| format!("{}: {}", p.name, python_type(&p.ty))));
+        let ret = if method.name == "__init__" { "None".to_string() } else { python_type(&method.return_type) };
+        code.push_str(&format!("    def {}({}) -> {}:\n", python_field_name(method.visibility, &method.name), params.join(", "), ret));
+        if method.name == "__init__" {
+            python_init_body(&mut code, class);
+        } else {
+            code.push_str("        raise NotImplementedError\n\n");
+        }
+    }
+    SkeletonFile { path: format!("{}.py", snake_case(&class.name)), content: code.trim_end().to_string() + "\n" }
+}
+
+fn python_init_body(code: &mut String, class: &ClassDef) {
+    if class.fields.is_empty() {
+        code.push_str("        pass\n");
+    }
+    for field in &class.fields {
+        code.push_str(&format!(
+            "        self.{}: {} = None  # type: ignore\n",
+            python_field_name(field.visibility, &field.name),
+            python_type(&field.ty)
+        ));
+    }
+    code.push('\n');
+}
+
+fn python_field_name(visibility: Visibility, name: &str) -> String {
+    match visibility {
+        Visibility::Private if !name.starts_with('_') => format!("_{}", name),
+        _ => name.to_string(),
+    }
+}
+
+fn rust_type(ty: &Option<String>) -> String {
+    let Some(ty) = ty.as_deref().map(normalize_generics).filter(|t| !t.is_empty()) else { return "()".to_string() };
+    if let Some(inner) = ty.strip_suffix("[]") {
+        return format!("Vec<{}>", rust_type(&Some(inner.to_string())));
+    }
+    if let Some((outer, inner)) = ty.split_once('[') {
+        let inner = inner.strip_suffix(']').unwrap_or(inner);
+        let args: Vec<String> = split_type_args(inner).into_iter().map(|t| rust_type(&Some(t))).collect();
+        return match (outer.trim().to_lowercase().as_str(), args.as_slice()) {
+            ("list" | "vec" | "sequence", [item]) => format!("Vec<{}>", item),
+            ("set", [item]) => format!("HashSet<{}>", item),
+            ("dict" | "map" | "hashmap", [key, value]) => format!("HashMap<{}, {}>", key, value),
+            ("optional" | "option", [item]) => format!("Option<{}>", item),
+            ("tuple", _) => format!("({})", args.join(", ")),
+            _ => format!("{}<{}>", outer.trim(), args.join(", ")),
+        };
+    }
+    match ty.as_str() {
+        "void" | "None" => "()".to_string(),
+        "int" | "integer" => "i64".to_string(),
+        "float" | "double" | "number" => "f64".to_string(),
+        "str" | "string" | "String" => "String".to_string(),
+        "bool" | "boolean" => "bool".to_string(),
+        "list" => "Vec<String>".to_string(),
+        "dict" => "HashMap<String, String>".to_string(),
+        _ => ty,
+    }
+}
+
+fn rust_struct(diagram: &ClassDiagram, class: &ClassDef) -> SkeletonFile {
+    let mut code = String::from("#![allow(unused_imports, unused_variables, dead_code)]\nuse std::collections::{HashMap, HashSet};\n\nuse super::*;\n\n");
+    for relation in diagram.relationships.iter().filter(|r| r.from == class.name) {
+        if matches!(relation.kind, RelationKind::Inheritance | RelationKind::Realization) {
+            code.push_str(&format!("/// Extends `{}` in the design.\n", relation.to));
+        }
+    }
+    code.push_str(&format!("#[derive(Debug, Default)]\npub struct {} {{\n", class.name));
+    for field in &class.fields {
+        let visibility = if field.visibility == Visibility::Public { "pub " } else { "" };
+        code.push_str(&format!("    {}{}: {},\n", visibility, snake_case(&field.name), rust_type(&field.ty)));
+    }
+    code.push_str("}\n\n");
+    code.push_str(&format!("impl {} {{\n", class.name));
+    for method in &class.methods {
+        let visibility = if method.visibility == Visibility::Public { "pub " } else { "" };
+        let params: Vec<String> = method.params.iter().map(|p| format!("{}: {}", snake_case(&p.name), rust_type(&p.ty))).collect();
+        if method.name == "__init__" || method.name == "new" || method.name == class.name {
+            code.push_str(&format!("    {}fn new({}) -> Self {{\n        todo!()\n    }}\n\n", visibility, params.join(", ")));
+            continue;
+        }
+        let mut all_params = vec!["&mut self".to_string()];
+        all_params.extend(params);
+        let ret = rust_type(&method.return_type);
+        let ret = if ret == "()" { String::new() } else { format!(" -> {}", ret) };
+        code.push_str(&format!("    {}fn {}({}){} {{\n        todo!()\n    }}\n\n", visibility, snake_case(&method.name), all_params.join(", "), ret));
+    }
+    let code = code.trim_end().to_string() + "\n}\n";
+    SkeletonFile { path: format!("{}.rs", snake_case(&class.name)), content: code }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIAGRAM: &str = "classDiagram
+    class Game{
+        +int score
+        +List~Food~ foods
+        +Dict~str, List~int~~ history
+        +start_game() void
+        +update(direction: str) bool
+    }
+    Entity <|-- Game
+";
+
+    #[test]
+    fn test_python_skeleton() {
+        let diagram = ClassDiagram::parse(DIAGRAM).unwrap();
+        let files = generate_skeleton(&diagram, SkeletonLanguage::Python);
+        assert_eq!(files[0].path, "game.py");
+        assert!(files[0].content.contains("from entity import Entity"));
+        assert!(files[0].content.contains("class Game(Entity):"));
+        assert!(files[0].content.contains("self.foods: List[Food] = None"));
+        assert!(files[0].content.contains("def update(self, direction: str) -> bool:\n        raise NotImplementedError"));
+    }
+
+    #[test]
+    fn test_rust_skeleton() {
+        let diagram = ClassDiagram::parse(DIAGRAM).unwrap();
+        let files = generate_skeleton(&diagram, SkeletonLanguage::Rust);
+        assert_eq!(files.last().unwrap().path, "mod.rs");
+        assert!(files[0].content.contains("pub foods: Vec<Food>,"));
+        assert!(files[0].content.contains("pub history: HashMap<String, Vec<i64>>,"));
+        assert!(files[0].content.contains("pub fn update(&mut self, direction: String) -> bool {"));
+        assert!(files[0].content.contains("pub fn start_game(&mut self) {"));
+        for file in &files {
+            if let Err(e) = syn::parse_file(&file.content) {
+                panic!("{} is not valid Rust: {}\n{}", file.path, e, file.content);
+            }
+        }
+    }
+
+    #[test]
+    fn test_snake_case() {
+        assert_eq!(snake_case("HTTPServer"), "http_server");
+        assert_eq!(snake_case("parseJSONResponse"), "parse_json_response");
+        assert_eq!(snake_case("GameAPI"), "game_api");
+        assert_eq!(snake_case("Player2Score"), "player2_score");
+        assert_eq!(snake_case("start_game"), "start_game");
+    }
+}
@@ -6,9 +6,13 @@ use serde::{Deserialize, Serialize};
 use tracing::{info, error};
 use chrono::Utc;
 use crate::error::{Result, ServerError};
+use agent_roles::{AskHuman, HumanFeedback};
 use crate::state::{AppState, TaskStatus};
 use crate::websocket::BroadcastMessage;
 
+/// How many times a rejected artifact is produced again before the task fails.
+const MAX_REVISIONS: usize = 3;
+
 #[derive(Debug, Deserialize)]
 pub struct CreateTaskRequest {
     pub idea: String,
@@ -18,6 +22,9 @@ pub struct CreateTaskRequest {
     pub model: String,
     #[serde(default = "default_n_round")]
     pub n_round: i32,
+    /// Actions paused for approval over the websocket, e.g. ["WritePRD", "WriteDesign"]
+    #[serde(default)]
+    pub approve_actions: Vec<String>,
 }
 
 fn default_agent() -> String {
@@ -60,6 +67,7 @@ pub async fn create_task(
         payload.agent,
         payload.model,
         payload.n_round,
+        payload.approve_actions,
     ).await;
 
     // Broadcast task creation event
@@ -174,9 +182,10 @@ async fn execute_task(task_id: String, state: AppState) -> anyhow::Result<()> {
     });
 
     // Step 2: Execute agents (mock for now)
-    let agents = vec!["ProductManager", "Architect", "Engineer"];
-    
-    for agent in agents {
+    let agents = vec![("ProductManager", "WritePRD"), ("Architect", "WriteDesign"), ("Engineer", "WriteCode")];
+    let approve_actions = state.get_task(&task_id).await.map(|t| t.approve_actions).unwrap_or_default();
+
+    for (agent, action) in agents {
         tokio::time::sleep(Duration::from_secs(3)).await;
         
         state.broadcaster.broadcast(BroadcastMessage::AgentEvent {
@@ -198,12 +207,31 @@ async fn execute_task(task_id: String, state: AppState) -> anyhow::Result<()> {
             });
         }
 
+        let mut artifact = format!("{} output for task {}", action, task_id);
+        if approve_actions.iter().any(|a| a == action) {
+            match review_artifact(&state, agent, action, artifact).await {
+                Some(approved) => artifact = approved,
+                None => {
+                    // nothing unapproved is handed to the next agent
+                    state.update_task_status(&task_id, TaskStatus::Failed).await;
+                    state.broadcaster.broadcast(BroadcastMessage::AgentEvent {
+                        timestamp: Utc::now(),
+                        agent: agent.to_string(),
+                        status: "rejected".to_string(),
+                        action: Some(action.to_string()),
+                        message: format!("{} was not approved, task {} stopped", action, task_id),
+                    });
+                    return Ok(());
+                }
+            }
+        }
+
         state.broadcaster.broadcast(BroadcastMessage::AgentEvent {
             timestamp: Utc::now(),
             agent: agent.to_string(),
             status: "complete".to_string(),
             action: Some(format!("{} completed", agent)),
-            message: format!("{} finished processing", agent),
+            message: format!("{} finished processing:\n{}", agent, artifact),
         });
     }
 
@@ -221,3 +249,29 @@ async fn execute_task(task_id: String, state: AppState) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Review `artifact` until it is approved or edited, producing it again after each rejection.
+/// `None` once every revision was rejected or nobody answered.
+async fn review_artifact(state: &AppState, agent: &str, action: &str, mut artifact: String) -> Option<String> {
+    for revision in 0..=MAX_REVISIONS {
+        let feedback = state.approvals.review(agent, action, &artifact).await;
+        info!("{} {} review: {:?}", agent, action, feedback);
+        match feedback {
+            HumanFeedback::Approve => return Some(artifact),
+            HumanFeedback::Edit(content) => return Some(content),
+            HumanFeedback::Unanswered => return None,
+            HumanFeedback::Reject(_) if revision == MAX_REVISIONS => return None,
+            HumanFeedback::Reject(reason) => {
+                state.broadcaster.broadcast(BroadcastMessage::AgentEvent {
+                    timestamp: Utc::now(),
+                    agent: agent.to_string(),
+                    status: "revising".to_string(),
+                    action: Some(action.to_string()),
+                    message: format!("{} rejected: {}", action, reason),
+                });
+                artifact = format!("{}\n\nRevised for: {}", artifact, reason);
+            }
+        }
+    }
+    None
+}
@@ -5,7 +5,7 @@ use uuid::Uuid;
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use crate::kasm::KasmClient;
-use crate::websocket::EventBroadcaster;
+use crate::websocket::{ApprovalRegistry, EventBroadcaster};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Task {
@@ -18,6 +18,9 @@ pub struct Task {
     pub created_at: DateTime<Utc>,
     pub completed_at: Option<DateTime<Utc>>,
     pub workspaces: Vec<String>,
+    /// Actions whose output must be approved over the websocket, e.g. `WritePRD`.
+    #[serde(default)]
+    pub approve_actions: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -33,19 +36,22 @@ pub enum TaskStatus {
 pub struct AppState {
     pub kasm_client: KasmClient,
     pub broadcaster: EventBroadcaster,
+    pub approvals: ApprovalRegistry,
     pub tasks: Arc<RwLock<HashMap<String, Task>>>,
 }
 
 impl AppState {
     pub async fn new(kasm_client: KasmClient) -> Self {
+        let broadcaster = EventBroadcaster::new(1000);
         Self {
             kasm_client,
-            broadcaster: EventBroadcaster::new(1000),
+            approvals: ApprovalRegistry::new(broadcaster.clone()),
+            broadcaster,
             tasks: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
-    pub async fn create_task(&self, idea: String, agent_type: String, model: String, n_round: i32) -> Task {
+    pub async fn create_task(&self, idea: String, agent_type: String, model: String, n_round: i32, approve_actions: Vec<String>) -> Task {
         let task = Task {
             id: Uuid::new_v4().to_string(),
             idea,
@@ -56,6 +62,7 @@ impl AppState {
             created_at: Utc::now(),
             completed_at: None,
             workspaces: Vec::new(),
+            approve_actions,
         };
 
         let mut tasks = self.tasks.write().await;
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use agent_roles::{AskHuman, HumanFeedback};
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::Deserialize;
+use tokio::sync::{oneshot, Mutex};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::broadcaster::{BroadcastMessage, EventBroadcaster};
+use crate::error::ServerError;
+
+/// Commands sent by websocket clients.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum ClientCommand {
+    #[serde(rename = "approval_response")]
+    ApprovalResponse {
+        request_id: String,
+        decision: ApprovalDecision,
+        #[serde(default)]
+        content: Option<String>,
+    },
+}
+
+/// How long a review waits for a client before the artifact is rejected.
+pub const APPROVAL_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApprovalDecision {
+    Approve,
+    Edit,
+    Reject,
+}
+
+/// `AskHuman` over the websocket: each review is broadcast as an `approval_request`
+/// and the role stays paused until a client answers with an `approval_response`, or
+/// `timeout` passes without one, which rejects the artifact.
+#[derive(Clone)]
+pub struct ApprovalRegistry {
+    broadcaster: EventBroadcaster,
+    pending: Arc<Mutex<HashMap<String, oneshot::Sender<HumanFeedback>>>>,
+    timeout: Duration,
+}
+
+impl ApprovalRegistry {
+    pub fn new(broadcaster: EventBroadcaster) -> Self {
+        Self {
+            broadcaster,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            timeout: APPROVAL_TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Resolve a pending review. An edit without content is refused and leaves the
+    /// review pending, so the client can answer again.
+    pub async fn respond(&self, request_id: &str, decision: ApprovalDecision, content: Option<String>) -> Result<(), ServerError> {
+        let mut pending = self.pending.lock().await;
+        if !pending.contains_key(request_id) {
+            return Err(ServerError::InvalidRequest(format!("no pending approval request {}", request_id)));
+        }
+        let feedback = match (decision, content) {
+            (ApprovalDecision::Approve, _) => HumanFeedback::Approve,
+            (ApprovalDecision::Edit, Some(content)) => HumanFeedback::Edit(content),
+            (ApprovalDecision::Edit, None) => {
+                return Err(ServerError::InvalidRequest(format!("edit of {} without content", request_id)));
+            }
+            (ApprovalDecision::Reject, content) => HumanFeedback::Reject(content.unwrap_or_default()),
+        };
+        let sender = pending.remove(request_id).expect("checked above");
+        sender
+            .send(feedback)
+            .map_err(|_| ServerError::InvalidRequest(format!("approval request {} is no longer awaited", request_id)))
+    }
+
+    pub async fn handle_command(&self, command: ClientCommand) {
+        match command {
+            ClientCommand::ApprovalResponse { request_id, decision, content } => {
+                if let Err(e) = self.respond(&request_id, decision, content).await {
+                    warn!("Ignoring approval response: {}", e);
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl AskHuman for ApprovalRegistry {
+    async fn review(&self, profile: &str, action: &str, artifact: &str) -> HumanFeedback {
+        let request_id = Uuid::new_v4().to_string();
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().await.insert(request_id.clone(), sender);
+
+        info!("Waiting for approval of {} from {} ({})", action, profile, request_id);
+        self.broadcaster.broadcast(BroadcastMessage::ApprovalRequest {
+            timestamp: Utc::now(),
+            request_id: request_id.clone(),
+            agent: profile.to_string(),
+            action: action.to_string(),
+            artifact: artifact.to_string(),
+        });
+
+        // The sender is only dropped without an answer when the registry itself goes away,
+        // which must not pass the artifact as approved.
+        match tokio::time::timeout(self.timeout, receiver).await {
+            Ok(Ok(feedback)) => feedback,
+            Ok(Err(_)) => {
+                warn!("Approval request {} was dropped without an answer, holding {} back", request_id, action);
+                HumanFeedback::Unanswered
+            }
+            Err(_) => {
+                self.pending.lock().await.remove(&request_id);
+                warn!("No answer to approval request {} within {:?}, rejecting {}", request_id, self.timeout, action);
+                HumanFeedback::Reject(format!("no answer within {} seconds", self.timeout.as_secs()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Start a review and return its task with the id of the request it broadcast.
+    async fn start_review(registry: &ApprovalRegistry) -> (tokio::task::JoinHandle<HumanFeedback>, String) {
+        let mut events = registry.broadcaster.subscribe();
+        let review = {
+            let registry = registry.clone();
+            tokio::spawn(async move { registry.review("Architect", "WriteDesign", "draft").await })
+        };
+        loop {
+            if let Ok(BroadcastMessage::ApprovalRequest { request_id, .. }) = events.recv().await {
+                return (review, request_id);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_approval_registry() {
+        let registry = ApprovalRegistry::new(EventBroadcaster::new(16));
+        let (review, request_id) = start_review(&registry).await;
+        assert!(registry.respond("unknown", ApprovalDecision::Approve, None).await.is_err());
+        // an edit without content is refused and the review stays pending
+        assert!(registry.respond(&request_id, ApprovalDecision::Edit, None).await.is_err());
+        registry.respond(&request_id, ApprovalDecision::Edit, Some("edited".into())).await.unwrap();
+        assert_eq!(review.await.unwrap(), HumanFeedback::Edit("edited".into()));
+        assert!(registry.respond(&request_id, ApprovalDecision::Approve, None).await.is_err());
+
+        // a dropped request holds the artifact back instead of approving it
+        let (review, request_id) = start_review(&registry).await;
+        registry.pending.lock().await.remove(&request_id);
+        assert_eq!(review.await.unwrap(), HumanFeedback::Unanswered);
+
+        // a client that never answers rejects it
+        let registry = registry.with_timeout(Duration::from_millis(10));
+        let (review, request_id) = start_review(&registry).await;
+        assert!(matches!(review.await.unwrap(), HumanFeedback::Reject(_)));
+        assert!(registry.respond(&request_id, ApprovalDecision::Approve, None).await.is_err());
+    }
+}
@@ -35,6 +35,14 @@ pub enum BroadcastMessage {
         agent_type: String,
         url: String,
     },
+    #[serde(rename = "approval_request")]
+    ApprovalRequest {
+        timestamp: DateTime<Utc>,
+        request_id: String,
+        agent: String,
+        action: String,
+        artifact: String,
+    },
     #[serde(rename = "task_complete")]
     TaskComplete {
         timestamp: DateTime<Utc>,
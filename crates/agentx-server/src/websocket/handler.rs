@@ -8,6 +8,7 @@ use axum::{
 use futures::{sink::SinkExt, stream::StreamExt};
 use tracing::{debug, error, info};
 use crate::state::AppState;
+use super::ClientCommand;
 
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
@@ -20,6 +21,7 @@ pub async fn ws_handler(
 async fn handle_socket(socket: WebSocket, state: AppState) {
     let (mut sender, mut receiver) = socket.split();
     let mut rx = state.broadcaster.subscribe();
+    let approvals = state.approvals.clone();
 
     info!("WebSocket client connected");
 
@@ -60,7 +62,10 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
             match msg {
                 Message::Text(text) => {
                     debug!("Received from client: {}", text);
-                    // Handle client commands here if needed
+                    match serde_json::from_str::<ClientCommand>(&text) {
+                        Ok(command) => approvals.handle_command(command).await,
+                        Err(e) => debug!("Ignoring unknown client message: {}", e),
+                    }
                 }
                 Message::Ping(_) => {
                     debug!("Received ping from client");
@@ -1,5 +1,7 @@
+pub mod approval;
 pub mod broadcaster;
 pub mod handler;
 
+pub use approval::{ApprovalRegistry, ClientCommand};
 pub use broadcaster::{BroadcastMessage, EventBroadcaster};
 pub use handler::ws_handler;
@@ -1,35 +1,153 @@
 
-use agent_roles::Role;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use agent_roles::{builtin_catalog, load_roles, AgentRoleBuilder, AskHuman, HumanFeedback, Role, TeamPlan};
+use agent_memory::{Embedder, MemoryBackend};
 use agent_schema::Message;
-use tracing::debug;
+use agent_utils::RepoIndex;
+use tracing::{info, warn};
 
-use agent_environment::Environment;
+use agent_environment::{AcceptanceEvaluator, AcceptanceReport, Checkpoint, ChecklistItem, Environment, LlmJudge, StopReason, TranscriptFiles};
 use crate::config::Config;
 
+/// Directory a run is checkpointed and exported to unless `set_run_dir` is called.
+pub const DEFAULT_RUN_DIR: &str = "runs/last";
+
+/// The outcome of `SoftwareCompany::run`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    pub reason: StopReason,
+    /// Messages published during the run, the requirement included.
+    pub messages: usize,
+    /// The exported transcript.
+    pub transcript: TranscriptFiles,
+    /// The last acceptance evaluation, see `SoftwareCompany::set_acceptance`.
+    pub acceptance: Option<AcceptanceReport>,
+}
+
 pub struct SoftwareCompany {
     environment: Environment,
     config: Config,
     investment: f64,
     idea: String,
+    run_dir: PathBuf,
 }
 
 impl SoftwareCompany {
-    pub fn new(yaml_file: &str) -> Self {
+    /// A company working in the workspace and run directory of `config`.
+    pub fn new(config: Config) -> Self {
         // let environment = Arc::new(Mutex::new(Environment::new()));
-        let environment = Environment::new();
+        let mut environment = Environment::new();
+        let run_dir = config.run_dir().to_path_buf();
+        environment.set_run_dir(&run_dir);
+        environment.set_workspace(config.workspace());
         Self {
             environment,
-            config: Config::new(yaml_file).unwrap(),
             investment: 0.0,
+            config,
             idea: String::new(),
+            run_dir,
         }
     }
 
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
     pub fn hire(&mut self, roles: Vec<Box<dyn Role>>) {
         // Placeholder for hire method
         self.environment.add_roles(roles);
     }
 
+    /// Hire the roles defined in a YAML file, see `agent_roles::RoleDefinition`.
+    pub fn hire_from_yaml(&mut self, path: &Path) -> anyhow::Result<()> {
+        let roles = load_roles(path)?;
+        info!("hired {} roles from {}", roles.len(), path.display());
+        self.hire(roles);
+        Ok(())
+    }
+
+    /// Let the LLM staff the team for `task` and hire it, returning the planned number of rounds.
+    /// With `ask_human` the plan is reviewed first: it can be approved, edited as YAML, or
+    /// rejected with feedback for a new proposal.
+    pub async fn assemble_team(&mut self, task: &str, ask_human: Option<Arc<dyn AskHuman>>) -> anyhow::Result<i32> {
+        let builder = AgentRoleBuilder::default();
+        let catalog = builtin_catalog();
+        let mut plan = builder.plan_team(task, &catalog, "").await?;
+        info!("team plan:\n{}", plan.to_yaml());
+        if let Some(ask_human) = ask_human {
+            loop {
+                match ask_human.review("Agent Role Manager", "TeamPlan", &plan.to_yaml()).await {
+                    HumanFeedback::Approve => break,
+                    HumanFeedback::Edit(yaml) => match TeamPlan::from_answer(&yaml) {
+                        Ok(edited) => {
+                            plan = edited;
+                            break;
+                        }
+                        Err(e) => warn!("the edited team plan is invalid: {:#}", e),
+                    },
+                    HumanFeedback::Reject(feedback) => plan = builder.plan_team(task, &catalog, &feedback).await?,
+                    HumanFeedback::Unanswered => anyhow::bail!("nobody answered the review of the team plan"),
+                }
+            }
+        }
+        self.hire(plan.hire()?);
+        Ok(plan.n_round)
+    }
+
+    /// Require a human decision on the output of `actions` (e.g. `WritePRD`, `WriteDesign`).
+    pub fn set_human_gates(&mut self, ask_human: Arc<dyn AskHuman>, actions: HashSet<String>) {
+        self.environment.set_ask_human(ask_human, actions);
+    }
+
+    /// Make the hired roles critique and revise each action output up to `max_rounds` times.
+    pub fn set_reflection(&mut self, max_rounds: usize) {
+        self.environment.set_reflection(max_rounds);
+    }
+
+    /// Keep the roles' memories in `backend` under `project`, they remember earlier runs.
+    pub fn set_memory_backend(&mut self, backend: Arc<dyn MemoryBackend>, project: &str) -> anyhow::Result<()> {
+        self.environment.set_memory_backend(backend, project)
+    }
+
+    /// Embed the roles' memories with `embedder` so that they recall messages by relevance.
+    pub fn set_embedder(&mut self, embedder: Arc<dyn Embedder>) {
+        self.environment.set_embedder(embedder);
+    }
+
+    /// Checkpoint the run in `run_dir` after every completed turn and export its transcript there.
+    pub fn set_run_dir(&mut self, run_dir: &Path) {
+        self.run_dir = run_dir.to_path_buf();
+        self.environment.set_run_dir(run_dir);
+    }
+
+    /// Continue the run checkpointed in `run_dir` instead of starting a project. The same
+    /// team must be hired first; the run keeps checkpointing to `run_dir`.
+    pub fn resume(&mut self, run_dir: &Path) -> anyhow::Result<()> {
+        let checkpoint = Checkpoint::load(run_dir)?;
+        if let Some(requirement) = checkpoint.memory.iter().find(|msg| msg.cause_by == "BossRequirement") {
+            self.idea = requirement.content.clone();
+        }
+        info!("resuming {} from {}", self.idea, run_dir.display());
+        self.environment.restore(checkpoint);
+        self.set_run_dir(run_dir);
+        Ok(())
+    }
+
+    /// End the run once a message caused by `cause_by` is published, e.g. `WriteCode`.
+    pub fn set_goal(&mut self, cause_by: &str) {
+        self.environment.set_goal(cause_by);
+    }
+
+    /// Check the deliverables against `checklist` as the run goes, ending it once they are
+    /// accepted. The items without checks are judged by the LLM.
+    pub fn set_acceptance(&mut self, checklist: Vec<ChecklistItem>) {
+        let judge = Box::new(LlmJudge::default());
+        self.environment.set_acceptance(AcceptanceEvaluator::new(checklist, judge));
+    }
+
     pub fn invest(&mut self, _money: f32) {
         // Placeholder for invest method
     }
@@ -50,23 +168,33 @@ impl SoftwareCompany {
         self.environment.publish_message(first_message)
     }
 
-    pub async fn run(&mut self, mut n_round: i32) -> String {
-        // Placeholder for run method
-        // while !self.environment.lock().await.message_queue.is_empty() {
-        while n_round > 0 {
-            self._check_balance();
-           
-            debug!("n_round: {}", n_round);
-            n_round -= 1;
-            // Placeholder for logging and round handling
-            if n_round == 0 {
-                break;
-            }
-            self.environment.run(n_round.try_into().unwrap()).await;
-            // Placeholder for running environment
-        }
-        // Placeholder for returning history
-        String::new()
+    /// Brownfield mode: index the existing repository at `repo_path` and publish it as a
+    /// `RepositorySummary` before the requirement, so the roles change that code instead of
+    /// starting a fresh project.
+    pub fn start_brownfield_project(&mut self, idea: &str, repo_path: &Path) -> anyhow::Result<()> {
+        let index = RepoIndex::scan(repo_path)?;
+        info!("indexed {} files from {}", index.files.len(), repo_path.display());
+        let summary = Message {
+            content: index.to_summary(),
+            role: "BOSS".to_owned(),
+            cause_by: "RepositorySummary".to_owned(),
+            ..Default::default()
+        };
+        self.environment.publish_message(summary);
+        self.start_project(idea);
+        Ok(())
+    }
+
+    /// Run the hired roles for at most `n_round` rounds, see `Environment::run`, then
+    /// export the transcript of the whole run to the run directory.
+    pub async fn run(&mut self, n_round: i32) -> anyhow::Result<RunSummary> {
+        self._check_balance();
+        let reason = self.environment.run(n_round.max(0) as usize).await;
+        info!("the company stopped: {}", reason);
+        let transcript = self.environment.transcript();
+        let files = transcript.export(&self.run_dir)?;
+        let acceptance = self.environment.acceptance_report();
+        Ok(RunSummary { reason, messages: transcript.entries.len(), transcript: files, acceptance })
     }
 }
 
@@ -1,10 +1,42 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+pub use agent_tools::SearchEngineType;
 
+use agent_memory::{Embedder, HashEmbedder, JsonMemoryBackend, MemoryBackend, OpenAIEmbedder, SqliteMemoryBackend, DEFAULT_EMBEDDING_MODEL};
+use agent_provider::{LLMSettings, ProviderSettings};
+use agent_tools::SearchSettings;
+use agent_utils::SkeletonLanguage;
+use config::{Environment, File, Map, Source, Value};
+use serde::{Deserialize, Serialize};
 
+/// Shared settings, committed with the repository.
+pub const CONFIG_FILE: &str = "config/config.yaml";
+/// Personal settings such as API keys, kept out of version control.
+pub const KEY_FILE: &str = "config/key.yaml";
 
-// Placeholder for logs module
-// Placeholder for tools module
+/// Keys read from the environment, the same names are used in the YAML files.
+const ENV_KEYS: [&str; 15] = [
+    "OPENAI_API_KEY",
+    "OPENAI_API_BASE",
+    "OPENAI_API_MODEL",
+    "MAX_TOKENS",
+    "TEMPERATURE",
+    "SEARCH_ENGINE",
+    "SERPAPI_API_KEY",
+    "WORKSPACE",
+    "SKELETON_LANGUAGE",
+    "RUN_DIR",
+    "PROJECT",
+    "MEMORY_BACKEND",
+    "MEMORY_PATH",
+    "SEMANTIC_MEMORY",
+    "EMBEDDING_MODEL",
+];
 
+/// Raised when a setting is asked for but set by no layer.
 #[derive(Debug)]
 pub struct NotConfiguredException(String);
 
@@ -14,51 +46,363 @@ impl NotConfiguredException {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-enum SearchEngineType {
-    SerpApiGoogle,
-    // ... Other possible values
+impl fmt::Display for NotConfiguredException {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for NotConfiguredException {}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// A layer could not be read or a value has the wrong type.
+    Load(config::ConfigError),
+    /// A value was read but makes no sense.
+    Invalid { key: &'static str, reason: String },
+}
+
+impl From<config::ConfigError> for ConfigError {
+    fn from(err: config::ConfigError) -> Self {
+        ConfigError::Load(err)
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Load(err) => write!(f, "failed to load the configuration: {}", err),
+            ConfigError::Invalid { key, reason } => write!(f, "invalid {}: {}", key, reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Where the roles' memories are kept between runs, see `Config::memory_backend`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryBackendType {
+    /// Not kept, every run starts afresh.
+    #[default]
+    Memory,
+    /// `MEMORY_PATH/memory.json`.
+    Json,
+    /// `MEMORY_PATH/memory.db`.
+    Sqlite,
+}
+
+/// How the roles' memories are embedded for `Memory::remember`, see `Config::embedder`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SemanticMemoryType {
+    /// Not embedded, `remember` matches the content.
+    #[default]
+    Off,
+    /// `HashEmbedder`, in process: relevance by shared words only, a synonym never matches.
+    Lexical,
+    /// `OpenAIEmbedder` with `EMBEDDING_MODEL`, relevance by meaning.
+    Provider,
+}
+
+/// The merged settings, see `ConfigLoader` for the layers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    pub openai_api_key: Option<String>,
+    /// OpenAI compatible endpoint, e.g. a local Ollama.
+    pub openai_api_base: Option<String>,
+    pub openai_api_model: String,
+    pub max_tokens: u32,
+    pub temperature: f32,
+    pub search_engine: SearchEngineType,
+    pub serpapi_api_key: Option<String>,
+    /// Where the roles write the project.
+    pub workspace: PathBuf,
+    /// Language of the skeleton generated from the design and of the code written from it.
+    pub skeleton_language: SkeletonLanguage,
+    /// Where the run is checkpointed and its transcript exported.
+    pub run_dir: PathBuf,
+    /// Name the roles' memories are kept under, runs of the same project share them.
+    pub project: String,
+    pub memory_backend: MemoryBackendType,
+    /// Directory of the memory file.
+    pub memory_path: PathBuf,
+    pub semantic_memory: SemanticMemoryType,
+    /// Model of the provider's embeddings endpoint, for `SemanticMemoryType::Provider`.
+    pub embedding_model: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            openai_api_key: None,
+            openai_api_base: None,
+            openai_api_model: "gpt-3.5-turbo".into(),
+            max_tokens: 1500,
+            temperature: 0.0,
+            search_engine: SearchEngineType::default(),
+            serpapi_api_key: None,
+            workspace: PathBuf::from(agent_environment::DEFAULT_WORKSPACE),
+            skeleton_language: SkeletonLanguage::default(),
+            run_dir: PathBuf::from(crate::company::DEFAULT_RUN_DIR),
+            project: "default".into(),
+            memory_backend: MemoryBackendType::default(),
+            memory_path: PathBuf::from("runs/memory"),
+            semantic_memory: SemanticMemoryType::default(),
+            embedding_model: DEFAULT_EMBEDDING_MODEL.into(),
+        }
+    }
+}
+
+/// Builds a `Config` from, lowest priority first: the defaults, `config/config.yaml`,
+/// `config/key.yaml`, an optional extra file, the environment, and the overrides of
+/// the command line.
+#[derive(Debug, Clone)]
+pub struct ConfigLoader {
+    files: Vec<PathBuf>,
+    env: Option<HashMap<String, String>>,
+    overrides: Vec<(String, String)>,
+}
+
+impl Default for ConfigLoader {
+    fn default() -> Self {
+        Self { files: vec![PathBuf::from(CONFIG_FILE), PathBuf::from(KEY_FILE)], env: None, overrides: vec![] }
+    }
+}
+
+impl ConfigLoader {
+    /// Read `path` after the default files. Unlike those, it must exist.
+    pub fn file(mut self, path: &Path) -> Self {
+        self.files.push(path.to_path_buf());
+        self
+    }
+
+    /// Use `vars` instead of the process environment.
+    pub fn env(mut self, vars: HashMap<String, String>) -> Self {
+        self.env = Some(vars);
+        self
+    }
+
+    /// Set `key` whatever the other layers say, for command line flags.
+    pub fn set(mut self, key: &str, value: impl ToString) -> Self {
+        self.overrides.push((key.to_lowercase(), value.to_string()));
+        self
+    }
+
+    pub fn load(self) -> Result<Config, ConfigError> {
+        let defaults = Settings::default();
+        let mut builder = config::Config::builder()
+            .set_default("openai_api_model", defaults.openai_api_model)?
+            .set_default("max_tokens", defaults.max_tokens as i64)?
+            .set_default("temperature", defaults.temperature as f64)?
+            .set_default("search_engine", defaults.search_engine.to_string())?
+            .set_default("workspace", defaults.workspace.to_string_lossy().to_string())?
+            .set_default("skeleton_language", "python")?
+            .set_default("run_dir", defaults.run_dir.to_string_lossy().to_string())?
+            .set_default("project", defaults.project)?
+            .set_default("memory_backend", "memory")?
+            .set_default("memory_path", defaults.memory_path.to_string_lossy().to_string())?
+            .set_default("semantic_memory", "off")?
+            .set_default("embedding_model", defaults.embedding_model)?;
+        for (idx, path) in self.files.iter().enumerate() {
+            // only the files asked for explicitly are required
+            builder = builder.add_source(LowercaseKeys(File::from(path.as_path()).required(idx >= 2)));
+        }
+        let vars = self.env.unwrap_or_else(|| std::env::vars().collect());
+        let vars = vars.into_iter().filter(|(key, value)| ENV_KEYS.contains(&key.as_str()) && !value.is_empty()).collect();
+        builder = builder.add_source(Environment::default().source(Some(vars)));
+        for (key, value) in self.overrides {
+            builder = builder.set_override(key, value)?;
+        }
+
+        let raw = builder.build()?;
+        let settings: Settings = raw.clone().try_deserialize()?;
+        let config = Config { settings, raw };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// The YAML files use the upper case names of the environment variables, but the
+/// `config` crate only lowercases the keys of the environment.
+#[derive(Debug, Clone)]
+struct LowercaseKeys<S>(S);
+
+impl<S: Source + Clone + Send + Sync + 'static> Source for LowercaseKeys<S> {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, config::ConfigError> {
+        Ok(self.0.collect()?.into_iter().map(|(key, value)| (key.to_lowercase(), value)).collect())
+    }
 }
 
+#[derive(Debug, Clone, Default)]
 pub struct Config {
-    // key_yaml_file : String,
-    
-    // Placeholder for configuration fields
+    pub settings: Settings,
+    raw: config::Config,
 }
 
 impl Config {
-    pub fn new(_yaml_file: &str) -> Result<Self, String> {
-        // Placeholder for loading configuration
-        Ok(Config {
-            // Initialize configuration fields
-        })
+    /// Load the default layers plus `yaml_file` if it exists.
+    pub fn new(yaml_file: &str) -> Result<Self, ConfigError> {
+        let path = Path::new(yaml_file);
+        let loader = ConfigLoader::default();
+        if path.exists() && !loader.files.iter().any(|file| file == path) {
+            loader.file(path).load()
+        } else {
+            loader.load()
+        }
     }
 
-    pub fn _get(&self, _key: &str) -> Option<&str> {
-        // Placeholder for getting configuration values
-        None
+    pub fn _get(&self, key: &str) -> Option<String> {
+        self.raw.get_string(&key.to_lowercase()).ok()
     }
 
-    pub fn get(&self, key: &str) -> Result<&str, NotConfiguredException> {
+    /// Any setting as a string, including keys without a typed accessor.
+    pub fn get(&self, key: &str) -> Result<String, NotConfiguredException> {
         match self._get(key) {
             Some(value) => Ok(value),
             None => Err(NotConfiguredException::new(&format!("Key '{}' not found", key))),
         }
     }
+
+    /// The OpenAI key, optional when a custom endpoint is configured.
+    pub fn openai_api_key(&self) -> Result<&str, NotConfiguredException> {
+        self.settings
+            .openai_api_key
+            .as_deref()
+            .ok_or_else(|| NotConfiguredException::new("Set OPENAI_API_KEY in config/key.yaml or the environment"))
+    }
+
+    pub fn search_engine(&self) -> SearchEngineType {
+        self.settings.search_engine
+    }
+
+    pub fn workspace(&self) -> &Path {
+        &self.settings.workspace
+    }
+
+    pub fn skeleton_language(&self) -> SkeletonLanguage {
+        self.settings.skeleton_language
+    }
+
+    pub fn run_dir(&self) -> &Path {
+        &self.settings.run_dir
+    }
+
+    /// Open the configured memory backend, none when memories are not kept between runs.
+    pub fn memory_backend(&self) -> anyhow::Result<Option<Arc<dyn MemoryBackend>>> {
+        let dir = &self.settings.memory_path;
+        Ok(match self.settings.memory_backend {
+            MemoryBackendType::Memory => None,
+            MemoryBackendType::Json => Some(Arc::new(JsonMemoryBackend::open(&dir.join("memory.json"))?)),
+            MemoryBackendType::Sqlite => Some(Arc::new(SqliteMemoryBackend::open(&dir.join("memory.db"))?)),
+        })
+    }
+
+    /// The embedder of the roles' memories, none when `SEMANTIC_MEMORY` is off.
+    pub fn embedder(&self) -> Option<Arc<dyn Embedder>> {
+        match self.settings.semantic_memory {
+            SemanticMemoryType::Off => None,
+            SemanticMemoryType::Lexical => Some(Arc::new(HashEmbedder::default())),
+            SemanticMemoryType::Provider => Some(Arc::new(OpenAIEmbedder::new(&self.settings.embedding_model))),
+        }
+    }
+
+    /// Check the values the layers agree on.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let settings = &self.settings;
+        if let Some(base) = &settings.openai_api_base {
+            if !base.starts_with("http://") && !base.starts_with("https://") {
+                return Err(ConfigError::Invalid { key: "OPENAI_API_BASE", reason: format!("{} is not an http(s) URL", base) });
+            }
+        }
+        if settings.openai_api_model.trim().is_empty() {
+            return Err(ConfigError::Invalid { key: "OPENAI_API_MODEL", reason: "is empty".into() });
+        }
+        if settings.max_tokens == 0 || settings.max_tokens > u32::from(u16::MAX) {
+            return Err(ConfigError::Invalid { key: "MAX_TOKENS", reason: format!("{} is not between 1 and {}", settings.max_tokens, u16::MAX) });
+        }
+        if !(0.0..=2.0).contains(&settings.temperature) {
+            return Err(ConfigError::Invalid { key: "TEMPERATURE", reason: format!("{} is not between 0 and 2", settings.temperature) });
+        }
+        if settings.search_engine == SearchEngineType::SerpApiGoogle && settings.serpapi_api_key.is_none() {
+            return Err(ConfigError::Invalid { key: "SERPAPI_API_KEY", reason: "is required by the serp_api_google search engine".into() });
+        }
+        if settings.project.trim().is_empty() || settings.project.contains('/') {
+            return Err(ConfigError::Invalid { key: "PROJECT", reason: format!("{:?} is not a project name", settings.project) });
+        }
+        if settings.workspace.as_os_str().is_empty() {
+            return Err(ConfigError::Invalid { key: "WORKSPACE", reason: "is empty".into() });
+        }
+        Ok(())
+    }
+
+    /// The endpoint and model defaults of the LLM clients, see `agent_provider::set_provider`.
+    pub fn provider_settings(&self) -> ProviderSettings {
+        let settings = &self.settings;
+        ProviderSettings {
+            api_key: settings.openai_api_key.clone(),
+            api_base: settings.openai_api_base.clone(),
+            defaults: LLMSettings {
+                model: Some(settings.openai_api_model.clone()),
+                temperature: Some(settings.temperature),
+                max_tokens: u16::try_from(settings.max_tokens).ok(),
+            },
+        }
+    }
+
+    /// The engine of the search actions and tools, see `agent_tools::set_search_engine`.
+    pub fn search_settings(&self) -> SearchSettings {
+        SearchSettings { engine: self.settings.search_engine, serpapi_api_key: self.settings.serpapi_api_key.clone() }
+    }
 }
 
-// fn main() {
-//     match Config::new("config.yaml") {
-//         Ok(config) => {
-//             let openai_api_key = config.get("OPENAI_API_KEY");
-//             match openai_api_key {
-//                 Ok(key) => println!("OpenAI API Key: {}", key),
-//                 Err(err) => eprintln!("Error: {:?}", err),
-//             }
-//             // Other configuration values
-//         }
-//         Err(error) => {
-//             eprintln!("Error: {}", error);
-//         }
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_layers() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("custom.yaml");
+        std::fs::write(&file, "OPENAI_API_MODEL: gpt-4\nTEMPERATURE: 0.5\nSEARCH_ENGINE: direct_bing\n").unwrap();
+
+        let env = HashMap::from([
+            ("OPENAI_API_KEY".to_string(), "sk-test".to_string()),
+            ("TEMPERATURE".to_string(), "0.7".to_string()),
+            ("HOME".to_string(), "/root".to_string()),
+        ]);
+        let config = ConfigLoader::default().file(&file).env(env).set("WORKSPACE", "out").load().unwrap();
+        assert_eq!(config.settings.openai_api_model, "gpt-4");
+        assert_eq!(config.openai_api_key().unwrap(), "sk-test");
+        assert_eq!(config.settings.temperature, 0.7);
+        assert_eq!(config.search_engine(), SearchEngineType::DirectBing);
+        assert_eq!(config.workspace(), Path::new("out"));
+        assert_eq!(config.skeleton_language(), SkeletonLanguage::Python);
+        assert_eq!(config.settings.max_tokens, 1500);
+        assert!(config.memory_backend().unwrap().is_none());
+        assert!(config.embedder().is_none());
+        assert_eq!(config.get("openai_api_model").unwrap(), "gpt-4");
+        assert!(config.get("HOME").is_err());
+
+        let serpapi = ConfigLoader::default().env(HashMap::new()).set("SEARCH_ENGINE", "serp_api_google").load();
+        assert!(matches!(serpapi, Err(ConfigError::Invalid { key: "SERPAPI_API_KEY", .. })));
+        let semantic = ConfigLoader::default().env(HashMap::new()).set("SEMANTIC_MEMORY", "provider").load().unwrap();
+        assert_eq!(semantic.embedder().unwrap().dimensions(), 1536);
+        let rust = ConfigLoader::default().env(HashMap::from([("SKELETON_LANGUAGE".to_string(), "rust".to_string())])).load().unwrap();
+        assert_eq!(rust.skeleton_language(), SkeletonLanguage::Rust);
+        let temperature = ConfigLoader::default().env(HashMap::new()).set("TEMPERATURE", "3").load();
+        assert!(matches!(temperature, Err(ConfigError::Invalid { key: "TEMPERATURE", .. })));
+
+        // a flag beats the model a `.env` file put in the environment, and reaches the clients
+        let env = HashMap::from([("OPENAI_API_MODEL".to_string(), "gpt-3.5-turbo-16k".to_string())]);
+        let config = ConfigLoader::default().env(env).set("OPENAI_API_MODEL", "gpt-4").load().unwrap();
+        let provider = config.provider_settings();
+        assert_eq!(provider.defaults.model.as_deref(), Some("gpt-4"));
+        assert_eq!(provider.defaults.max_tokens, Some(1500));
+        assert_eq!(config.search_settings().engine, SearchEngineType::DirectGoogle);
+    }
+}
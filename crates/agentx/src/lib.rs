@@ -1,5 +1,6 @@
 mod config;
 mod company;
 
-pub use agent_environment::Environment;
-pub use company::SoftwareCompany;
+pub use agent_environment::{AcceptanceEvaluator, AcceptanceReport, ChecklistItem, Environment, StopReason, TranscriptFiles};
+pub use company::{RunSummary, SoftwareCompany, DEFAULT_RUN_DIR};
+pub use config::{Config, ConfigError, ConfigLoader, MemoryBackendType, NotConfiguredException, SearchEngineType, SemanticMemoryType, Settings, CONFIG_FILE, KEY_FILE};
@@ -1,41 +1,100 @@
 #[warn(unused_imports)]
+use std::collections::HashSet;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use anyhow::Result;
 use clap::builder::TypedValueParser as _;
 use clap::Parser;
 use dotenv::dotenv;
 
-use tracing::info;
+use tracing::{error, info};
 use tracing_subscriber::fmt::time;
 
-use agentx_core::SoftwareCompany;
+use agent_roles::{AskHuman, StdinAskHuman};
+use agentx_core::{AcceptanceEvaluator, ConfigLoader, SearchEngineType, SoftwareCompany};
 
-async fn startup(
-    idea: String,
-    _investment: f64,
-    n_round: i32,
-    _code_review: bool,
-    _run_tests: bool,
-) -> Result<()> {
-    
+async fn startup(config: ConfigLoader, args: Args) -> Result<()> {
+    // taken from the checkpoint when resuming
+    let idea = args.idea.unwrap_or_default();
+    let mut n_round = args.n_round;
+
+    let config = config.load()?;
+    // the project's own prompts, in the repository changed or the workspace written to
+    let project_root = args.repo.as_deref().unwrap_or(config.workspace());
+    agent_actions::load_prompts(project_root).map_err(|e| anyhow::anyhow!("failed to load prompt templates:\n{}", e))?;
+    agent_actions::validate_prompts().map_err(|e| anyhow::anyhow!("invalid prompt templates:\n{}", e))?;
+    agent_actions::set_skeleton_language(config.skeleton_language());
 
-    let cfg = "config/key.yaml";
-    let mut company = SoftwareCompany::new(cfg);
+    agent_provider::set_provider(config.provider_settings());
+    agent_tools::set_search_engine(config.search_settings());
+    let run_dir = config.run_dir().to_path_buf();
+    let memory_backend = config.memory_backend()?;
+    let embedder = config.embedder();
+    let project = config.settings.project.clone();
+    let mut company = SoftwareCompany::new(config);
+    if let Some(backend) = memory_backend {
+        company.set_memory_backend(backend, &project)?;
+    }
+    if let Some(embedder) = embedder {
+        company.set_embedder(embedder);
+    }
     // let mut env = Environment::new();
 
-    company.hire(vec![
-        Box::new(agent_roles::ProductManager::default()),
-        Box::new(agent_roles::Architect::default()),
-        Box::new(agent_roles::ProjectManager::default()),
-        Box::new(agent_roles::Engineer::default()),
-    ]);
-
-    let investment = 3.0;
-    
-    company.invest(investment);
-    company.start_project(&idea);
-    company.run(n_round).await;
+    match args.roles {
+        Some(roles) => company.hire_from_yaml(&roles)?,
+        None if args.auto_team => {
+            let ask_human: Option<Arc<dyn AskHuman>> = if args.approve.iter().any(|action| action == "TeamPlan") {
+                Some(Arc::new(StdinAskHuman))
+            } else {
+                None
+            };
+            n_round = company.assemble_team(&idea, ask_human).await?;
+        }
+        None => company.hire(vec![
+            Box::new(agent_roles::ProductManager::default()),
+            Box::new(agent_roles::Architect::default()),
+            Box::new(agent_roles::ApiDesigner::default()),
+            Box::new(agent_roles::ProjectManager::default()),
+            Box::new(agent_roles::Engineer::default()),
+        ]),
+    }
+
+    if args.reflect > 0 {
+        company.set_reflection(args.reflect);
+    }
+
+    if let Some(goal) = args.until {
+        company.set_goal(&goal);
+    }
+
+    match args.checklist {
+        Some(path) => company.set_acceptance(AcceptanceEvaluator::load_checklist(&path)?),
+        None if args.accept => company.set_acceptance(AcceptanceEvaluator::default_checklist()),
+        None => {}
+    }
+
+    if !args.approve.is_empty() {
+        company.set_human_gates(Arc::new(StdinAskHuman), args.approve.into_iter().collect::<HashSet<String>>());
+    }
+
+    if args.resume {
+        company.resume(&run_dir)?;
+    } else {
+        company.set_run_dir(&run_dir);
+        match args.repo {
+            Some(repo) => company.start_brownfield_project(&idea, &repo)?,
+            None => company.start_project(&idea),
+        }
+    }
+    let summary = company.run(n_round).await?;
+    println!("Finished: {} ({} messages)", summary.reason, summary.messages);
+    println!("Transcript: {}", summary.transcript.html.display());
+    println!("            {}", summary.transcript.markdown.display());
+    println!("            {}", summary.transcript.jsonl.display());
+    if let Some(report) = summary.acceptance {
+        println!("Acceptance: {}", report);
+    }
     Ok(())
 }
 
@@ -47,20 +106,59 @@ async fn startup(
     long_about=None
 )]
 struct Args {
-    /// Sets a custom config file
+    /// Config file read after config/config.yaml and config/key.yaml
     #[arg(short, long, value_name = "FILE")]
     config: Option<PathBuf>,
+    /// Model to use, overrides OPENAI_API_MODEL
+    #[arg(long, value_name = "MODEL")]
+    model: Option<String>,
+    /// Search engine: serpapi, google or bing, overrides SEARCH_ENGINE
+    #[arg(long, value_name = "ENGINE")]
+    search_engine: Option<SearchEngineType>,
+    /// Project the roles' memories are kept under, overrides PROJECT
+    #[arg(long, value_name = "NAME")]
+    project: Option<String>,
+    /// Directory the roles write the project to, overrides WORKSPACE
+    #[arg(long, value_name = "DIR")]
+    workspace: Option<PathBuf>,
     /// Your innovative task, such as 'Creating a snake game.'
-    #[arg(short, long)]
-    idea: String,
+    #[arg(short, long, required_unless_present = "resume")]
+    idea: Option<String>,
+    /// Existing repository to change instead of starting a fresh project
+    #[arg(long, value_name = "DIR")]
+    repo: Option<PathBuf>,
+    /// Directory the run is checkpointed to after every turn, overrides RUN_DIR
+    #[arg(long, value_name = "DIR")]
+    run_dir: Option<PathBuf>,
+    /// Continue the run checkpointed in --run-dir, hiring the same team
+    #[arg(long)]
+    resume: bool,
+    /// YAML file defining the team to hire instead of the built-in roles
+    #[arg(long, value_name = "FILE")]
+    roles: Option<PathBuf>,
+    /// Let the LLM staff the team for the idea, add TeamPlan to --approve to review it first
+    #[arg(long, conflicts_with_all = ["roles", "resume"])]
+    auto_team: bool,
+    /// Actions whose output must be approved before the run continues, e.g. WritePRD,WriteDesign
+    #[arg(long, value_name = "ACTIONS", value_delimiter = ',')]
+    approve: Vec<String>,
+    /// Critique and revise rounds after each action, 0 disables self-reflection
+    #[arg(long, value_name = "ROUNDS", default_value_t = 0)]
+    reflect: usize,
+    /// Stop as soon as a message caused by this action is published, e.g. WriteCode
+    #[arg(long, value_name = "ACTION")]
+    until: Option<String>,
+    /// Check the deliverables against the default checklist and stop once they are accepted
+    #[arg(long)]
+    accept: bool,
+    /// YAML checklist to accept the deliverables with, implies --accept
+    #[arg(long, value_name = "FILE")]
+    checklist: Option<PathBuf>,
     /// Agent Name
     #[arg(short, long, default_value_t = String::from("MetaGPT"))]
     agent: String,
-    /// Investment amount
-    #[arg(short, long, default_value_t = 3.0)]
-    startup_investment: f64,
-    /// Number of startup rounds
-    #[arg(short, long, default_value_t = 4)]
+    /// Maximum number of rounds, the run also stops when no role has new input
+    #[arg(short, long, default_value_t = 10)]
     n_round: i32,
     /// Enable code review
     #[arg(short, long)]
@@ -91,7 +189,30 @@ async fn main() {
         // sets this to be the default, global collector for this application.
         .init();
 
-    info!("Hello, use {} for {}!", args.agent, args.idea);
+    info!("Hello, use {} for {}!", args.agent, args.idea.as_deref().unwrap_or("the resumed run"));
+
+    let mut config = ConfigLoader::default();
+    if let Some(file) = &args.config {
+        config = config.file(file);
+    }
+    if let Some(model) = &args.model {
+        config = config.set("OPENAI_API_MODEL", model);
+    }
+    if let Some(engine) = args.search_engine {
+        config = config.set("SEARCH_ENGINE", engine);
+    }
+    if let Some(project) = &args.project {
+        config = config.set("PROJECT", project);
+    }
+    if let Some(workspace) = &args.workspace {
+        config = config.set("WORKSPACE", workspace.display());
+    }
+    if let Some(run_dir) = &args.run_dir {
+        config = config.set("RUN_DIR", run_dir.display());
+    }
 
-    let _ = startup(args.idea, args.startup_investment, args.n_round, args.review, args.tests).await;
+    if let Err(e) = startup(config, args).await {
+        error!("{}", e);
+        std::process::exit(1);
+    }
 }